@@ -0,0 +1,564 @@
+//! Group entries by arbitrary fields and compute aggregates per group.
+//!
+//! Keys are any [`LogEntry::field_value`] name, including dotted paths into
+//! extra JSON fields (`error.code`), plus a few derived keys: `status_class`
+//! (`5xx`), `template`, and the time buckets `minute`, `hour` and `day`.
+
+use crate::log_analyzer::{AnalyzerError, LogEntry, LogLevel, percentile};
+use crate::text_report::{TextLayout, pad, truncate};
+use colored::*;
+use serde::Serialize;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+use unicode_width::UnicodeWidthStr;
+
+/// A function computed over the entries of a group, e.g. `p95(response_time)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Agg {
+    Count,
+    /// Percentage of the group's entries logged at `ERROR`.
+    ErrorRate,
+    /// The group's entries as a percentage of all entries.
+    Share,
+    CountDistinct(String),
+    Sum(String),
+    Avg(String),
+    Min(String),
+    Max(String),
+    Percentile(f64, String),
+}
+
+impl Agg {
+    /// The aggregate `func(field)`, e.g. `p95` of `response_time`; none for
+    /// an unknown function or one that takes no field.
+    pub fn call(func: &str, field: impl Into<String>) -> Option<Self> {
+        let field = field.into();
+        match func.trim().to_lowercase().as_str() {
+            "count_distinct" | "distinct" => Some(Agg::CountDistinct(field)),
+            "sum" => Some(Agg::Sum(field)),
+            "avg" | "mean" => Some(Agg::Avg(field)),
+            "min" => Some(Agg::Min(field)),
+            "max" => Some(Agg::Max(field)),
+            "median" => Some(Agg::Percentile(50.0, field)),
+            p => {
+                let pct: f64 = p.strip_prefix('p')?.parse().ok()?;
+                (0.0..=100.0).contains(&pct).then_some(Agg::Percentile(pct, field))
+            }
+        }
+    }
+
+    /// The field the aggregate reads, if any.
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            Agg::Count | Agg::ErrorRate | Agg::Share => None,
+            Agg::CountDistinct(f) | Agg::Sum(f) | Agg::Avg(f) | Agg::Min(f) | Agg::Max(f) => Some(f),
+            Agg::Percentile(_, f) => Some(f),
+        }
+    }
+
+    /// The aggregate of `numbers`, the field's values; `count` counts them
+    /// and `count_distinct` their distinct values. Undefined without numbers
+    /// for everything but the counts, and always for `error_rate` and `share`,
+    /// which need the entries.
+    pub fn of_numbers(&self, numbers: &[f64]) -> Option<f64> {
+        match self {
+            Agg::Count => Some(numbers.len() as f64),
+            Agg::ErrorRate | Agg::Share => None,
+            Agg::CountDistinct(_) => {
+                let mut distinct: Vec<u64> = numbers.iter().map(|n| n.to_bits()).collect();
+                distinct.sort_unstable();
+                distinct.dedup();
+                Some(distinct.len() as f64)
+            }
+            Agg::Sum(_) => Some(numbers.iter().sum()),
+            Agg::Avg(_) => (!numbers.is_empty()).then(|| numbers.iter().sum::<f64>() / numbers.len() as f64),
+            Agg::Min(_) => numbers.iter().copied().reduce(f64::min),
+            Agg::Max(_) => numbers.iter().copied().reduce(f64::max),
+            Agg::Percentile(pct, _) => percentile(numbers, *pct),
+        }
+    }
+
+    /// The aggregate over `entries`, one group out of `total` entries.
+    pub fn compute<'a>(&self, entries: impl IntoIterator<Item = &'a LogEntry>, total: usize) -> Option<f64> {
+        let mut accumulator = Accumulator::default();
+        for entry in entries {
+            accumulator.add(self, entry);
+        }
+        accumulator.value(self, total)
+    }
+}
+
+/// Running state of an [`Agg`] fed one entry at a time, for callers that
+/// don't keep a group's entries around, such as rule windows.
+#[derive(Debug, Clone, Default)]
+pub struct Accumulator {
+    entries: usize,
+    errors: usize,
+    distinct: BTreeSet<String>,
+    numbers: Vec<f64>,
+}
+
+impl Accumulator {
+    /// Add `entry`, reading what `agg` needs from it.
+    pub fn add(&mut self, agg: &Agg, entry: &LogEntry) {
+        self.entries += 1;
+        if entry.level == Some(LogLevel::Error) {
+            self.errors += 1;
+        }
+        match agg {
+            Agg::Count | Agg::ErrorRate | Agg::Share => {}
+            Agg::CountDistinct(field) => self.distinct.extend(entry.field_value(field)),
+            Agg::Sum(field) | Agg::Avg(field) | Agg::Min(field) | Agg::Max(field) | Agg::Percentile(_, field) => {
+                self.numbers.extend(entry.field_value(field).and_then(|v| v.parse::<f64>().ok()));
+            }
+        }
+    }
+
+    /// Entries added so far.
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// The aggregate's value; `share` is relative to `total` entries.
+    pub fn value(&self, agg: &Agg, total: usize) -> Option<f64> {
+        match agg {
+            Agg::Count => Some(self.entries as f64),
+            Agg::ErrorRate => (self.entries > 0).then(|| self.errors as f64 / self.entries as f64 * 100.0),
+            Agg::Share => (total > 0).then(|| self.entries as f64 / total as f64 * 100.0),
+            Agg::CountDistinct(_) => Some(self.distinct.len() as f64),
+            _ => agg.of_numbers(&self.numbers),
+        }
+    }
+}
+
+impl FromStr for Agg {
+    type Err = AnalyzerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || AnalyzerError::AggregationError(format!("unknown aggregate '{s}'"));
+        match s {
+            "count" => return Ok(Agg::Count),
+            "error_rate" => return Ok(Agg::ErrorRate),
+            "share" => return Ok(Agg::Share),
+            _ => {}
+        }
+        let (func, rest) = s.split_once('(').ok_or_else(invalid)?;
+        let field = rest.strip_suffix(')').ok_or_else(invalid)?.trim();
+        if field.is_empty() {
+            return Err(AnalyzerError::AggregationError(format!("missing field in '{s}'")));
+        }
+        Agg::call(func, field).ok_or_else(invalid)
+    }
+}
+
+impl Display for Agg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Agg::Count => write!(f, "count"),
+            Agg::ErrorRate => write!(f, "error_rate"),
+            Agg::Share => write!(f, "share"),
+            Agg::CountDistinct(field) => write!(f, "count_distinct({field})"),
+            Agg::Sum(field) => write!(f, "sum({field})"),
+            Agg::Avg(field) => write!(f, "avg({field})"),
+            Agg::Min(field) => write!(f, "min({field})"),
+            Agg::Max(field) => write!(f, "max({field})"),
+            Agg::Percentile(pct, field) => write!(f, "p{pct}({field})"),
+        }
+    }
+}
+
+/// Column to sort groups by: a key or an aggregate, as written on the
+/// command line, optionally suffixed with `:asc` or `:desc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortBy {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl FromStr for SortBy {
+    type Err = AnalyzerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, descending) = match s.trim().rsplit_once(':') {
+            Some((column, "asc")) => (column, false),
+            Some((column, "desc")) => (column, true),
+            Some(_) => {
+                return Err(AnalyzerError::AggregationError(format!(
+                    "invalid sort '{s}' (expected COLUMN[:asc|:desc])"
+                )));
+            }
+            None => (s.trim(), true),
+        };
+        Ok(Self {
+            column: column.trim().to_string(),
+            descending,
+        })
+    }
+}
+
+/// The value of a grouping key for one entry.
+pub fn key_value(entry: &LogEntry, key: &str) -> Option<String> {
+    let time_bucket = |fmt: &str| entry.parsed_timestamp().map(|ts| ts.format(fmt).to_string());
+    match key {
+        "status_class" => entry.status_code.map(|s| format!("{}xx", s / 100)),
+        "template" => entry.message_template(),
+        "minute" => time_bucket("%Y-%m-%d %H:%M"),
+        "hour" => time_bucket("%Y-%m-%d %H:00"),
+        "day" => time_bucket("%Y-%m-%d"),
+        field => entry.field_value(field),
+    }
+}
+
+/// A group-by query. Without a sort, groups are ordered by the first
+/// aggregate, largest first.
+#[derive(Debug, Clone, Default)]
+pub struct GroupBy {
+    pub keys: Vec<String>,
+    pub aggregates: Vec<Agg>,
+    pub sort: Option<SortBy>,
+    pub top: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupRow {
+    /// One value per key; `None` when the entry lacks the field.
+    pub key: Vec<Option<String>>,
+    /// One value per aggregate; `None` when undefined for the group (e.g. the
+    /// average of a field no entry has).
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupTable {
+    pub keys: Vec<String>,
+    pub aggregates: Vec<String>,
+    /// Number of groups before `top` was applied.
+    pub total_groups: usize,
+    pub rows: Vec<GroupRow>,
+}
+
+impl GroupBy {
+    pub fn run(&self, entries: &[LogEntry]) -> Result<GroupTable, AnalyzerError> {
+        if self.keys.is_empty() {
+            return Err(AnalyzerError::AggregationError("no group-by keys given".to_string()));
+        }
+        let aggregates = if self.aggregates.is_empty() {
+            vec![Agg::Count]
+        } else {
+            self.aggregates.clone()
+        };
+        let agg_names: Vec<String> = aggregates.iter().map(Agg::to_string).collect();
+
+        let mut groups: HashMap<Vec<Option<String>>, Vec<&LogEntry>> = HashMap::new();
+        for entry in entries {
+            let key = self.keys.iter().map(|k| key_value(entry, k)).collect();
+            groups.entry(key).or_default().push(entry);
+        }
+
+        let mut rows: Vec<GroupRow> = groups
+            .into_iter()
+            .map(|(key, members)| GroupRow {
+                values: aggregates.iter().map(|a| a.compute(members.iter().copied(), entries.len())).collect(),
+                key,
+            })
+            .collect();
+
+        let sort = match &self.sort {
+            Some(sort) => sort.clone(),
+            None => SortBy {
+                column: agg_names[0].clone(),
+                descending: true,
+            },
+        };
+        let column = self.column(&sort.column, &agg_names)?;
+        rows.sort_by(|a, b| {
+            let ordering = match column {
+                Column::Key(i) => a.key[i].cmp(&b.key[i]),
+                Column::Agg(i) => a.values[i]
+                    .partial_cmp(&b.values[i])
+                    .unwrap_or(Ordering::Equal),
+            };
+            let ordering = if sort.descending { ordering.reverse() } else { ordering };
+            // Ties fall back to the keys so the output is deterministic.
+            ordering.then_with(|| a.key.cmp(&b.key))
+        });
+
+        let total_groups = rows.len();
+        if let Some(top) = self.top {
+            rows.truncate(top);
+        }
+        Ok(GroupTable {
+            keys: self.keys.clone(),
+            aggregates: agg_names,
+            total_groups,
+            rows,
+        })
+    }
+
+    fn column(&self, name: &str, agg_names: &[String]) -> Result<Column, AnalyzerError> {
+        if let Some(i) = agg_names.iter().position(|a| a == name) {
+            return Ok(Column::Agg(i));
+        }
+        if let Ok(agg) = name.parse::<Agg>()
+            && let Some(i) = agg_names.iter().position(|a| *a == agg.to_string())
+        {
+            return Ok(Column::Agg(i));
+        }
+        if let Some(i) = self.keys.iter().position(|k| k == name) {
+            return Ok(Column::Key(i));
+        }
+        Err(AnalyzerError::AggregationError(format!(
+            "cannot sort by '{name}': not a group-by key or aggregate"
+        )))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Column {
+    Key(usize),
+    Agg(usize),
+}
+
+fn format_value(agg: &str, value: Option<f64>) -> String {
+    match value {
+        None => "-".to_string(),
+        Some(v) if agg == "error_rate" || agg == "share" => format!("{v:.2}%"),
+        Some(v) if v.fract() == 0.0 => format!("{v}"),
+        Some(v) => format!("{v:.2}"),
+    }
+}
+
+/// Print the table in the same style as the main report.
+pub fn print_group_table(table: &GroupTable, layout: &TextLayout) {
+    print!("{}", render_group_table(table, layout));
+}
+
+/// Key columns are not shrunk below this many columns to fit the layout.
+const MIN_KEY_WIDTH: usize = 8;
+
+/// The group-by section: key columns are truncated so rows fit
+/// `layout.width` when they can, aggregates are never cut.
+fn render_group_table(table: &GroupTable, layout: &TextLayout) -> String {
+    let mut out = format!("{}\n", layout.title("🧮", &format!("GROUPED BY {}", table.keys.join(", "))));
+
+    let cells: Vec<Vec<String>> = table
+        .rows
+        .iter()
+        .map(|row| {
+            row.key
+                .iter()
+                .map(|k| k.clone().unwrap_or_else(|| "-".to_string()))
+                .chain(
+                    table
+                        .aggregates
+                        .iter()
+                        .zip(&row.values)
+                        .map(|(agg, value)| format_value(agg, *value)),
+                )
+                .collect()
+        })
+        .collect();
+    let headers: Vec<&String> = table.keys.iter().chain(&table.aggregates).collect();
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| cells.iter().map(|row| row[i].width()).chain([h.width()]).max().unwrap_or(0))
+        .collect();
+    let key_count = table.keys.len();
+    // Two columns of indent and two between columns.
+    let total = |widths: &[usize]| 2 + widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1);
+    while total(&widths) > layout.width {
+        let Some(widest) = (0..key_count).filter(|&i| widths[i] > MIN_KEY_WIDTH).max_by_key(|&i| widths[i]) else {
+            break;
+        };
+        widths[widest] -= 1;
+    }
+    let cell = |i: usize, text: &str| pad(&truncate(text, widths[i], layout.ascii), widths[i], i < key_count);
+
+    let header: Vec<String> = headers.iter().enumerate().map(|(i, h)| cell(i, h)).collect();
+    out.push_str(&format!("  {}\n", header.join("  ").bright_black()));
+    for row in &cells {
+        let line: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let text = cell(i, text);
+                if i < key_count {
+                    text.bright_cyan().to_string()
+                } else {
+                    text.yellow().to_string()
+                }
+            })
+            .collect();
+        out.push_str(&format!("  {}\n", line.join("  ")));
+    }
+    if table.rows.len() < table.total_groups {
+        out.push_str(&format!(
+            "  {}\n",
+            format!("{} {} more groups", if layout.ascii { "..." } else { "…" }, table.total_groups - table.rows.len())
+                .bright_black()
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: [&str; 6] = [
+        "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/orders 500 900ms Order 17 failed",
+        "2024-01-15 08:15:20.000 INFO 10.0.0.2 GET /api/users 200 40ms",
+        "2024-01-15 08:15:40.000 ERROR 10.0.0.3 POST /api/orders 503 1200ms Order 18 failed",
+        "2024-01-15 08:16:10.000 INFO 10.0.0.1 GET /api/users 200 60ms",
+        "2024-01-15 08:16:30.000 INFO 10.0.0.2 POST /api/orders 201 300ms",
+        "2024-01-15 08:16:50.000 WARNING 10.0.0.2 GET /api/users 404 50ms",
+    ];
+
+    fn entries() -> Vec<LogEntry> {
+        LINES.iter().map(|line| LogEntry::parse_log(line).unwrap()).collect()
+    }
+
+    fn group_by(keys: &[&str], aggregates: &str) -> GroupBy {
+        GroupBy {
+            keys: keys.iter().map(|k| k.to_string()).collect(),
+            aggregates: aggregates.split(';').filter(|a| !a.is_empty()).map(|a| a.parse().unwrap()).collect(),
+            ..GroupBy::default()
+        }
+    }
+
+    fn keys(table: &GroupTable) -> Vec<Vec<&str>> {
+        table.rows.iter().map(|row| row.key.iter().map(|k| k.as_deref().unwrap_or("-")).collect()).collect()
+    }
+
+    #[test]
+    fn aggregates_parse_and_print() {
+        let cases = [
+            ("count", Agg::Count),
+            ("error_rate", Agg::ErrorRate),
+            ("share", Agg::Share),
+            ("distinct(ip)", Agg::CountDistinct("ip".to_string())),
+            ("SUM( bytes )", Agg::Sum("bytes".to_string())),
+            ("mean(response_time)", Agg::Avg("response_time".to_string())),
+            ("median(response_time)", Agg::Percentile(50.0, "response_time".to_string())),
+            ("p99.9(response_time)", Agg::Percentile(99.9, "response_time".to_string())),
+            ("max(error.code)", Agg::Max("error.code".to_string())),
+        ];
+        for (s, agg) in cases {
+            assert_eq!(s.parse::<Agg>().unwrap(), agg, "{s}");
+        }
+        assert_eq!("median(response_time)".parse::<Agg>().unwrap().to_string(), "p50(response_time)");
+        assert_eq!("distinct(ip)".parse::<Agg>().unwrap().to_string(), "count_distinct(ip)");
+        for (s, message) in [
+            ("p101(response_time)", "unknown aggregate"),
+            ("avg", "unknown aggregate"),
+            ("avg(response_time", "unknown aggregate"),
+            ("stddev(response_time)", "unknown aggregate"),
+            ("avg()", "missing field"),
+        ] {
+            let err = s.parse::<Agg>().unwrap_err().to_string();
+            assert!(err.contains(message), "{s}: {err}");
+        }
+        assert!("status:up".parse::<SortBy>().is_err());
+        assert_eq!("count:asc".parse::<SortBy>().unwrap(), SortBy { column: "count".to_string(), descending: false });
+    }
+
+    #[test]
+    fn groups_by_several_keys() {
+        let table = group_by(&["method", "endpoint"], "count;avg(response_time);error_rate;share")
+            .run(&entries())
+            .unwrap();
+        assert_eq!(table.aggregates, ["count", "avg(response_time)", "error_rate", "share"]);
+        assert_eq!(keys(&table), [["GET", "/api/users"], ["POST", "/api/orders"]]);
+        let orders = &table.rows[1].values;
+        assert_eq!(orders[0], Some(3.0));
+        assert_eq!(orders[1], Some(800.0));
+        assert!((orders[2].unwrap() - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(orders[3], Some(50.0));
+
+        let table = group_by(&["ip", "level"], "").run(&entries()).unwrap();
+        assert_eq!(table.aggregates, ["count"], "count is the default aggregate");
+        assert_eq!(table.total_groups, 5);
+        assert_eq!(
+            keys(&table),
+            [
+                ["10.0.0.2", "INFO"],
+                ["10.0.0.1", "ERROR"],
+                ["10.0.0.1", "INFO"],
+                ["10.0.0.2", "WARNING"],
+                ["10.0.0.3", "ERROR"],
+            ]
+        );
+    }
+
+    #[test]
+    fn sorting_breaks_ties_by_key_and_top_keeps_the_total() {
+        let entries = entries();
+        // Two groups of three: the tie goes to the smaller key.
+        let table = group_by(&["endpoint"], "count").run(&entries).unwrap();
+        assert_eq!(keys(&table), [["/api/orders"], ["/api/users"]]);
+
+        let mut query = group_by(&["ip"], "count;max(response_time)");
+        query.sort = Some("max(response_time):asc".parse().unwrap());
+        assert_eq!(keys(&query.run(&entries).unwrap()), [["10.0.0.2"], ["10.0.0.1"], ["10.0.0.3"]]);
+        query.sort = Some("ip".parse().unwrap());
+        assert_eq!(keys(&query.run(&entries).unwrap()), [["10.0.0.3"], ["10.0.0.2"], ["10.0.0.1"]]);
+        query.sort = Some("count".parse().unwrap());
+        query.top = Some(1);
+        let table = query.run(&entries).unwrap();
+        assert_eq!(keys(&table), [["10.0.0.2"]]);
+        assert_eq!(table.total_groups, 3);
+
+        query.sort = Some("status".parse().unwrap());
+        assert!(query.run(&entries).unwrap_err().to_string().contains("cannot sort by 'status'"));
+        assert!(group_by(&[], "count").run(&entries).is_err());
+    }
+
+    #[test]
+    fn derived_keys() {
+        let entries = entries();
+        let table = group_by(&["status_class"], "count").run(&entries).unwrap();
+        assert_eq!(keys(&table), [["2xx"], ["5xx"], ["4xx"]]);
+
+        let table = group_by(&["minute"], "count").run(&entries).unwrap();
+        assert_eq!(keys(&table), [["2024-01-15 08:15"], ["2024-01-15 08:16"]]);
+        assert_eq!(key_value(&entries[0], "hour").as_deref(), Some("2024-01-15 08:00"));
+        assert_eq!(key_value(&entries[0], "day").as_deref(), Some("2024-01-15"));
+
+        let table = group_by(&["template"], "count").run(&entries).unwrap();
+        assert_eq!(keys(&table), [["-"], ["Order <*> failed"]]);
+        assert_eq!(table.rows[1].values, [Some(2.0)]);
+    }
+
+    #[test]
+    fn group_table_fits_the_width_and_aligns_wide_keys() {
+        let lines = [
+            "2024-01-15 08:15:01.000 INFO 10.0.0.1 GET /api/日本語/users 200 40ms",
+            "2024-01-15 08:15:02.000 INFO 10.0.0.1 GET /api/a/really/long/endpoint/that/cannot/fit/in/forty/columns 200 60ms",
+            "2024-01-15 08:15:03.000 INFO 10.0.0.1 GET /x 200 60ms",
+        ];
+        let entries: Vec<LogEntry> = lines.iter().map(|line| LogEntry::parse_log(line).unwrap()).collect();
+        let table = group_by(&["endpoint"], "count;avg(response_time)").run(&entries).unwrap();
+        let ansi = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        for width in [40, 60, 120] {
+            let layout = TextLayout { width, ..TextLayout::default() };
+            let rendered = render_group_table(&table, &layout);
+            let rendered = ansi.replace_all(&rendered, "");
+            let rows: Vec<&str> = rendered.lines().skip(3).collect();
+            assert_eq!(rows.len(), 4, "{rendered}");
+            let row_width = rows[0].width();
+            for row in &rows {
+                assert!(row.width() <= width, "{width}: {row:?}");
+                assert_eq!(row.width(), row_width, "{width}: {row:?}");
+            }
+        }
+        let layout = TextLayout { width: 40, ascii: true, ..TextLayout::default() };
+        let rendered = render_group_table(&table, &layout);
+        assert!(rendered.contains("  /api/a/r...      1"), "{rendered}");
+    }
+}
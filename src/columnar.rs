@@ -0,0 +1,434 @@
+//! Parquet and Arrow IPC export of parsed entries, for loading into columnar
+//! tools.
+//!
+//! The typed fields keep their types: `timestamp` is a millisecond timestamp
+//! (no time zone, like the logs), `status` a `u16`, `response_time` an `f64`
+//! of milliseconds, and `level`/`method` are dictionary-encoded. Entries are
+//! read, converted and written one row group at a time, so the whole file is
+//! never held in memory; flattening extra fields reads the entries twice, the
+//! first time only to find the columns.
+
+use crate::export::write_atomically;
+use crate::log_analyzer::{AnalyzerError, LogEntry, LogLevel, LogMethod};
+use arrow_array::builder::{
+    Float64Builder, MapBuilder, StringBuilder, StringDictionaryBuilder, TimestampMillisecondBuilder,
+    UInt16Builder, UInt64Builder,
+};
+use arrow_array::types::Int8Type;
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Rows per row group (Parquet) or record batch (Arrow IPC) by default.
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 65_536;
+
+/// Typed columns, in schema order.
+const COLUMNS: [&str; 9] = [
+    "line_number",
+    "timestamp",
+    "level",
+    "ip",
+    "method",
+    "endpoint",
+    "status",
+    "response_time",
+    "message",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnarFormat {
+    Parquet,
+    /// Arrow IPC file format (Feather v2).
+    ArrowIpc,
+}
+
+/// How the extra JSON fields of structured lines are stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExtraFields {
+    /// A single `extra` column mapping each key to its value; strings are
+    /// stored as is, other values as JSON text.
+    #[default]
+    Map,
+    /// One column per top-level key, typed as int64, float64 or bool when
+    /// every value fits, otherwise as text.
+    Flatten,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExtraType {
+    Int,
+    Float,
+    Bool,
+    Text,
+}
+
+impl ExtraType {
+    fn of(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::Bool(_) => Some(ExtraType::Bool),
+            serde_json::Value::Number(n) if n.is_i64() => Some(ExtraType::Int),
+            serde_json::Value::Number(_) => Some(ExtraType::Float),
+            _ => Some(ExtraType::Text),
+        }
+    }
+
+    /// The narrowest type holding values of both types.
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ExtraType::Int, ExtraType::Float) | (ExtraType::Float, ExtraType::Int) => ExtraType::Float,
+            _ => ExtraType::Text,
+        }
+    }
+}
+
+/// A flattened extra key and the column it is written to.
+struct ExtraColumn {
+    key: String,
+    name: String,
+    kind: ExtraType,
+}
+
+/// Scan the entries for the top-level extra keys and their types. Keys that
+/// clash with a typed column are prefixed with `extra.`.
+fn extra_columns(
+    entries: impl Iterator<Item = Result<LogEntry, AnalyzerError>>,
+) -> Result<Vec<ExtraColumn>, AnalyzerError> {
+    let mut kinds: BTreeMap<String, Option<ExtraType>> = BTreeMap::new();
+    for entry in entries {
+        for (key, value) in entry?.extra {
+            let kind = kinds.entry(key).or_default();
+            *kind = match (*kind, ExtraType::of(&value)) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => a.or(b),
+            };
+        }
+    }
+    Ok(kinds
+        .into_iter()
+        .map(|(key, kind)| ExtraColumn {
+            name: if COLUMNS.contains(&key.as_str()) { format!("extra.{key}") } else { key.clone() },
+            key,
+            kind: kind.unwrap_or(ExtraType::Text),
+        })
+        .collect())
+}
+
+fn json_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// A dictionary column holding every possible value up front, so all
+/// batches share one dictionary as Arrow IPC files require.
+fn dictionary_builder(values: &[impl ToString]) -> Result<StringDictionaryBuilder<Int8Type>, AnalyzerError> {
+    let values: StringArray = values.iter().map(|v| Some(v.to_string())).collect();
+    StringDictionaryBuilder::new_with_dictionary(0, &values).map_err(export_error)
+}
+
+/// Convert one chunk of entries into columns.
+fn batch(
+    entries: &[LogEntry],
+    extra: ExtraFields,
+    extra_columns: &[ExtraColumn],
+) -> Result<Vec<(String, ArrayRef, bool)>, AnalyzerError> {
+    let mut line_number = UInt64Builder::with_capacity(entries.len());
+    let mut timestamp = TimestampMillisecondBuilder::with_capacity(entries.len());
+    let mut level = dictionary_builder(&[LogLevel::Info, LogLevel::Warning, LogLevel::Error])?;
+    let mut ip = StringBuilder::new();
+    let mut method = dictionary_builder(&[
+        LogMethod::Get,
+        LogMethod::Post,
+        LogMethod::Patch,
+        LogMethod::Put,
+        LogMethod::Delete,
+    ])?;
+    let mut endpoint = StringBuilder::new();
+    let mut status = UInt16Builder::with_capacity(entries.len());
+    let mut response_time = Float64Builder::with_capacity(entries.len());
+    let mut message = StringBuilder::new();
+    let mut extra_map = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+
+    for entry in entries {
+        line_number.append_value(entry.line_number as u64);
+        timestamp.append_option(entry.parsed_timestamp().map(|ts| ts.and_utc().timestamp_millis()));
+        level.append_option(entry.level.as_ref().map(|l| l.to_string()));
+        ip.append_option(entry.ip_address.map(|ip| ip.to_string()));
+        method.append_option(entry.method.as_ref().map(|m| m.to_string()));
+        endpoint.append_option(entry.endpoint.as_deref());
+        status.append_option(entry.status_code);
+        response_time.append_option(entry.response_time);
+        message.append_option(entry.message.as_deref());
+        if extra == ExtraFields::Map {
+            for (key, value) in &entry.extra {
+                extra_map.keys().append_value(key);
+                match value {
+                    serde_json::Value::Null => extra_map.values().append_null(),
+                    value => extra_map.values().append_value(json_text(value)),
+                }
+            }
+            extra_map.append(true).map_err(export_error)?;
+        }
+    }
+
+    let mut columns: Vec<(String, ArrayRef, bool)> = vec![
+        ("line_number".to_string(), Arc::new(line_number.finish()), false),
+        ("timestamp".to_string(), Arc::new(timestamp.finish()), true),
+        ("level".to_string(), Arc::new(level.finish()), true),
+        ("ip".to_string(), Arc::new(ip.finish()), true),
+        ("method".to_string(), Arc::new(method.finish()), true),
+        ("endpoint".to_string(), Arc::new(endpoint.finish()), true),
+        ("status".to_string(), Arc::new(status.finish()), true),
+        ("response_time".to_string(), Arc::new(response_time.finish()), true),
+        ("message".to_string(), Arc::new(message.finish()), true),
+    ];
+    match extra {
+        ExtraFields::Map => columns.push(("extra".to_string(), Arc::new(extra_map.finish()), false)),
+        ExtraFields::Flatten => {
+            for column in extra_columns {
+                let values = entries.iter().map(|e| e.extra.get(&column.key).filter(|v| !v.is_null()));
+                let array: ArrayRef = match column.kind {
+                    ExtraType::Int => Arc::new(values.map(|v| v.and_then(|v| v.as_i64())).collect::<Int64Array>()),
+                    ExtraType::Float => Arc::new(values.map(|v| v.and_then(|v| v.as_f64())).collect::<Float64Array>()),
+                    ExtraType::Bool => Arc::new(values.map(|v| v.and_then(|v| v.as_bool())).collect::<BooleanArray>()),
+                    ExtraType::Text => Arc::new(values.map(|v| v.map(json_text)).collect::<StringArray>()),
+                };
+                columns.push((column.name.clone(), array, true));
+            }
+        }
+    }
+    Ok(columns)
+}
+
+fn export_error(e: impl std::fmt::Display) -> AnalyzerError {
+    AnalyzerError::ExportError(e.to_string())
+}
+
+/// The schema shared by every batch, with the response time unit recorded in
+/// the field metadata.
+fn schema(extra: ExtraFields, extra_columns: &[ExtraColumn]) -> Result<SchemaRef, AnalyzerError> {
+    let empty = batch(&[], extra, extra_columns)?;
+    let schema = RecordBatch::try_from_iter_with_nullable(empty).map_err(export_error)?.schema();
+    let fields = schema.fields().iter().map(|field| {
+        let field = field.as_ref().clone();
+        match field.name().as_str() {
+            "response_time" => field.with_metadata(HashMap::from([("unit".to_string(), "ms".to_string())])),
+            _ => field,
+        }
+    });
+    Ok(Arc::new(Schema::new(fields.collect::<Vec<_>>())))
+}
+
+enum Writer {
+    Parquet(ArrowWriter<File>),
+    Ipc(arrow_ipc::writer::FileWriter<File>),
+}
+
+/// Write the entries to `path`, `row_group_size` rows at a time, through a
+/// temporary file so a failed export leaves no truncated file. `entries`
+/// starts a pass over them; flattening extra fields takes two. Returns the
+/// number of rows written.
+pub fn write<I>(
+    path: &Path,
+    format: ColumnarFormat,
+    mut entries: impl FnMut() -> Result<I, AnalyzerError>,
+    extra: ExtraFields,
+    row_group_size: usize,
+) -> Result<usize, AnalyzerError>
+where
+    I: Iterator<Item = Result<LogEntry, AnalyzerError>>,
+{
+    if row_group_size == 0 {
+        return Err(AnalyzerError::OutputError("--row-group-size must be positive".to_string()));
+    }
+    let extra_columns = match extra {
+        ExtraFields::Map => Vec::new(),
+        ExtraFields::Flatten => extra_columns(entries()?)?,
+    };
+    let schema = schema(extra, &extra_columns)?;
+    write_atomically(path, |file| {
+        let mut writer = match format {
+            ColumnarFormat::Parquet => {
+                let props = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .set_max_row_group_size(row_group_size)
+                    .build();
+                Writer::Parquet(ArrowWriter::try_new(file, schema.clone(), Some(props)).map_err(export_error)?)
+            }
+            ColumnarFormat::ArrowIpc => {
+                Writer::Ipc(arrow_ipc::writer::FileWriter::try_new(file, &schema).map_err(export_error)?)
+            }
+        };
+
+        let mut entries = entries()?;
+        let mut rows = 0;
+        loop {
+            let chunk = entries.by_ref().take(row_group_size).collect::<Result<Vec<_>, _>>()?;
+            if chunk.is_empty() {
+                break;
+            }
+            rows += chunk.len();
+            let columns = batch(&chunk, extra, &extra_columns)?
+                .into_iter()
+                .map(|(_, array, _)| array)
+                .collect();
+            let batch = RecordBatch::try_new(schema.clone(), columns).map_err(export_error)?;
+            match &mut writer {
+                Writer::Parquet(w) => {
+                    w.write(&batch).map_err(export_error)?;
+                    w.flush().map_err(export_error)?;
+                }
+                Writer::Ipc(w) => w.write(&batch).map_err(export_error)?,
+            }
+        }
+        match writer {
+            Writer::Parquet(w) => {
+                w.close().map_err(export_error)?;
+            }
+            Writer::Ipc(mut w) => w.finish().map_err(export_error)?,
+        }
+        Ok(rows)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, TimestampMillisecondType, UInt16Type};
+    use arrow_schema::{DataType, TimeUnit};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn arrow_batches_share_one_dictionary() {
+        let lines = [
+            r#"{"level": "info", "method": "GET", "attempt": 1}"#,
+            r#"{"level": "error", "method": "POST", "attempt": 2.5}"#,
+            r#"{"level": "warn", "user": "alice"}"#,
+        ];
+        let entries = || Ok(lines.iter().map(|line| LogEntry::parse_log(line)));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("entries.arrow");
+        let rows = write(&path, ColumnarFormat::ArrowIpc, entries, ExtraFields::Flatten, 1).unwrap();
+        assert_eq!(rows, 3);
+
+        let reader = arrow_ipc::reader::FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let schema = reader.schema();
+        assert_eq!(schema.field_with_name("attempt").unwrap().data_type(), &arrow_schema::DataType::Float64);
+        let mut levels = Vec::new();
+        for batch in reader {
+            let batch = batch.unwrap();
+            assert_eq!(batch.num_rows(), 1);
+            let level = batch.column_by_name("level").unwrap().as_dictionary::<Int8Type>();
+            let values = level.values().as_string::<i32>();
+            levels.extend(level.keys().iter().map(|k| values.value(k.unwrap() as usize).to_string()));
+        }
+        assert_eq!(levels, ["INFO", "ERROR", "WARNING"]);
+    }
+
+    const LINES: [&str; 3] = [
+        "2024-01-15 08:15:01.250 ERROR 10.0.0.1 POST /api/orders 503 900ms",
+        r#"{"timestamp":"2024-01-15T08:15:20","level":"info","method":"GET","path":"/api/users","status":200,"user":"alice","tags":["a"],"note":null}"#,
+        r#"{"level":"warn","message":"disk almost full"}"#,
+    ];
+
+    fn entries() -> Result<impl Iterator<Item = Result<LogEntry, AnalyzerError>>, AnalyzerError> {
+        Ok(LINES.iter().enumerate().map(|(i, line)| {
+            LogEntry::parse_log(line).map(|entry| LogEntry { line_number: i + 1, ..entry })
+        }))
+    }
+
+    fn read_parquet(path: &Path) -> RecordBatch {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap().build().unwrap();
+        let mut batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.len(), 1);
+        batches.remove(0)
+    }
+
+    #[test]
+    fn parquet_keeps_the_typed_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("entries.parquet");
+        assert_eq!(write(&path, ColumnarFormat::Parquet, entries, ExtraFields::Map, 100).unwrap(), 3);
+
+        let batch = read_parquet(&path);
+        let schema = batch.schema();
+        let dictionary = DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8));
+        let types: Vec<(&str, &DataType)> = schema.fields().iter().map(|f| (f.name().as_str(), f.data_type())).collect();
+        assert_eq!(&types[..7], &[
+            ("line_number", &DataType::UInt64),
+            ("timestamp", &DataType::Timestamp(TimeUnit::Millisecond, None)),
+            ("level", &dictionary),
+            ("ip", &DataType::Utf8),
+            ("method", &dictionary),
+            ("endpoint", &DataType::Utf8),
+            ("status", &DataType::UInt16),
+        ]);
+        let response_time = schema.field_with_name("response_time").unwrap();
+        assert_eq!(response_time.data_type(), &DataType::Float64);
+        assert_eq!(response_time.metadata().get("unit").map(String::as_str), Some("ms"));
+
+        let timestamps = batch.column_by_name("timestamp").unwrap().as_primitive::<TimestampMillisecondType>();
+        assert_eq!(timestamps.value(0), 1_705_306_501_250);
+        assert!(timestamps.is_null(2));
+        let status = batch.column_by_name("status").unwrap().as_primitive::<UInt16Type>();
+        assert_eq!((status.value(0), status.value(1)), (503, 200));
+        let method = batch.column_by_name("method").unwrap().as_dictionary::<Int8Type>();
+        let methods = method.values().as_string::<i32>();
+        assert_eq!(methods.value(method.keys().value(1) as usize), "GET");
+        let times = batch.column_by_name("response_time").unwrap().as_primitive::<Float64Type>();
+        assert_eq!(times.value(0), 900.0);
+        assert!(times.is_null(1));
+    }
+
+    #[test]
+    fn map_mode_stores_extra_fields_as_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("entries.parquet");
+        write(&path, ColumnarFormat::Parquet, entries, ExtraFields::Map, 100).unwrap();
+
+        let batch = read_parquet(&path);
+        assert!(batch.column_by_name("user").is_none());
+        let extra = batch.column_by_name("extra").unwrap().as_map();
+        let pairs = |row: usize| -> Vec<(String, Option<String>)> {
+            let entries = extra.value(row);
+            let keys = entries.column(0).as_string::<i32>();
+            let values = entries.column(1).as_string::<i32>();
+            (0..entries.len())
+                .map(|i| (keys.value(i).to_string(), values.is_valid(i).then(|| values.value(i).to_string())))
+                .collect()
+        };
+        assert!(pairs(0).is_empty());
+        assert_eq!(
+            pairs(1),
+            [
+                ("note".to_string(), None),
+                ("tags".to_string(), Some(r#"["a"]"#.to_string())),
+                ("user".to_string(), Some("alice".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_failed_export_leaves_the_previous_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("entries.arrow");
+        std::fs::write(&path, "previous").unwrap();
+        let failing = || {
+            Ok(entries()?.chain(std::iter::once(Err(AnalyzerError::ExportError("read failed".to_string())))))
+        };
+        assert!(write(&path, ColumnarFormat::ArrowIpc, failing, ExtraFields::Map, 1).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
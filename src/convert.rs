@@ -0,0 +1,322 @@
+//! Rewriting parsed entries in another log format, e.g. to normalize text
+//! archives into JSON lines.
+//!
+//! The typed fields are written under the names the JSON parser reads back
+//! (`timestamp`, `level`, `ip`, `method`, `endpoint`, `status`,
+//! `response_time`, `message`); extra fields follow where the format has room
+//! for them. Whatever a format can't hold is counted per field in
+//! [`Conversion::dropped`] instead of being lost silently.
+
+use crate::export::{Delimited, entry_column};
+use crate::log_analyzer::LogEntry;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Target formats of the `convert` command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConvertFormat {
+    /// One JSON object per line; extra fields are kept as they are.
+    #[default]
+    Jsonl,
+    /// `key=value` pairs; nested extra objects become dotted keys.
+    Logfmt,
+    /// Common Log Format; no room for levels, messages, response times or
+    /// extra fields.
+    Clf,
+    /// A header row and a column per typed and extra field; nested values
+    /// are written as JSON.
+    Csv,
+}
+
+/// The converted text and what didn't fit.
+#[derive(Debug, Default)]
+pub struct Conversion {
+    pub output: String,
+    /// Entries per field whose value could not be represented.
+    pub dropped: BTreeMap<String, usize>,
+}
+
+impl Conversion {
+    fn skip(&mut self, field: &str) {
+        *self.dropped.entry(field.to_string()).or_default() += 1;
+    }
+}
+
+/// The typed fields by their JSON names, in output order.
+fn typed_fields(entry: &LogEntry) -> Vec<(&'static str, Value)> {
+    let mut fields = Vec::new();
+    if let Some(ts) = &entry.timestamp {
+        fields.push(("timestamp", Value::from(ts.as_str())));
+    }
+    if let Some(level) = &entry.level {
+        fields.push(("level", Value::from(level.to_string())));
+    }
+    if let Some(ip) = entry.ip_address {
+        fields.push(("ip", Value::from(ip.to_string())));
+    }
+    if let Some(method) = &entry.method {
+        fields.push(("method", Value::from(method.to_string())));
+    }
+    if let Some(endpoint) = &entry.endpoint {
+        fields.push(("endpoint", Value::from(endpoint.as_str())));
+    }
+    if let Some(status) = entry.status_code {
+        fields.push(("status", Value::from(status)));
+    }
+    if let Some(rt) = entry.response_time {
+        fields.push(("response_time", Value::from(rt)));
+    }
+    if let Some(message) = &entry.message {
+        fields.push(("message", Value::from(message.as_str())));
+    }
+    fields
+}
+
+fn jsonl(entries: &[LogEntry], conversion: &mut Conversion) {
+    for entry in entries {
+        let mut object: Map<String, Value> =
+            typed_fields(entry).into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        for (key, value) in &entry.extra {
+            if object.contains_key(key) {
+                conversion.skip(key);
+            } else {
+                object.insert(key.clone(), value.clone());
+            }
+        }
+        conversion.output.push_str(&Value::Object(object).to_string());
+        conversion.output.push('\n');
+    }
+}
+
+/// A logfmt value, quoted when empty or when it contains spaces, `=`, quotes
+/// or control characters.
+fn logfmt_value(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c == ' ' || c == '=' || c == '"' || c.is_control()) {
+        return value.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn logfmt_key_ok(key: &str) -> bool {
+    !key.is_empty() && !key.contains(|c: char| c == '=' || c == '"' || c.is_whitespace() || c.is_control())
+}
+
+/// Flatten `value` into `key=value` pairs; objects become dotted keys,
+/// arrays are written as JSON text and reported.
+fn logfmt_pairs(key: &str, value: &Value, pairs: &mut Vec<String>, conversion: &mut Conversion) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (child, value) in map {
+                logfmt_pairs(&format!("{key}.{child}"), value, pairs, conversion);
+            }
+        }
+        _ if !logfmt_key_ok(key) => conversion.skip(key),
+        Value::Null => pairs.push(format!("{key}=")),
+        Value::String(s) => pairs.push(format!("{key}={}", logfmt_value(s))),
+        Value::Array(_) | Value::Object(_) => {
+            conversion.skip(key);
+            pairs.push(format!("{key}={}", logfmt_value(&value.to_string())));
+        }
+        other => pairs.push(format!("{key}={other}")),
+    }
+}
+
+fn logfmt(entries: &[LogEntry], conversion: &mut Conversion) {
+    for entry in entries {
+        let typed = typed_fields(entry);
+        let mut pairs: Vec<String> = Vec::new();
+        for (key, value) in &typed {
+            logfmt_pairs(key, value, &mut pairs, conversion);
+        }
+        for (key, value) in &entry.extra {
+            if typed.iter().any(|(k, _)| k == key) {
+                conversion.skip(key);
+            } else {
+                logfmt_pairs(key, value, &mut pairs, conversion);
+            }
+        }
+        conversion.output.push_str(&pairs.join(" "));
+        conversion.output.push('\n');
+    }
+}
+
+/// `host ident authuser [date] "request" status bytes`, with `-` for what is
+/// unknown. Timestamps are written in UTC.
+fn clf(entries: &[LogEntry], conversion: &mut Conversion) {
+    for entry in entries {
+        let ip = entry.ip_address.map_or_else(|| "-".to_string(), |ip| ip.to_string());
+        let date = match entry.parsed_timestamp() {
+            Some(ts) => ts.format("%d/%b/%Y:%H:%M:%S +0000").to_string(),
+            None => {
+                if entry.timestamp.is_some() {
+                    conversion.skip("timestamp");
+                }
+                "-".to_string()
+            }
+        };
+        let request = match (&entry.method, &entry.endpoint) {
+            (Some(method), Some(endpoint)) => format!("{method} {endpoint} HTTP/1.1"),
+            (None, Some(endpoint)) => format!("- {endpoint} -"),
+            (Some(_), None) => {
+                conversion.skip("method");
+                "-".to_string()
+            }
+            (None, None) => "-".to_string(),
+        };
+        let status = entry.status_code.map_or_else(|| "-".to_string(), |s| s.to_string());
+        let _ = writeln!(
+            conversion.output,
+            "{ip} - - [{date}] \"{}\" {status} -",
+            request.replace('"', "\\\"")
+        );
+        if entry.level.is_some() {
+            conversion.skip("level");
+        }
+        if entry.response_time.is_some() {
+            conversion.skip("response_time");
+        }
+        if entry.message.is_some() {
+            conversion.skip("message");
+        }
+        for key in entry.extra.keys() {
+            conversion.skip(key);
+        }
+    }
+}
+
+fn csv(entries: &[LogEntry], conversion: &mut Conversion) {
+    const TYPED: [&str; 8] = ["timestamp", "level", "ip", "method", "endpoint", "status", "response_time", "message"];
+    let extra: BTreeSet<&str> = entries
+        .iter()
+        .flat_map(|e| e.extra.keys().map(String::as_str))
+        .filter(|key| !TYPED.contains(key))
+        .collect();
+    // Extra keys named like a typed field share its column.
+    let columns: Vec<&str> = TYPED.iter().copied().chain(extra).collect();
+    conversion.output.push_str(&Delimited::Csv.row(&columns));
+    for entry in entries {
+        let row: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                let typed = if TYPED.contains(column) { entry_column(entry, column) } else { String::new() };
+                match entry.extra.get(*column) {
+                    Some(_) if !typed.is_empty() => {
+                        conversion.skip(column);
+                        typed
+                    }
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Null) | None => typed,
+                    Some(other) => other.to_string(),
+                }
+            })
+            .collect();
+        conversion.output.push_str(&Delimited::Csv.row(&row));
+    }
+}
+
+/// Write `entries` in `format`.
+pub fn convert(entries: &[LogEntry], format: ConvertFormat) -> Conversion {
+    let mut conversion = Conversion::default();
+    match format {
+        ConvertFormat::Jsonl => jsonl(entries, &mut conversion),
+        ConvertFormat::Logfmt => logfmt(entries, &mut conversion),
+        ConvertFormat::Clf => clf(entries, &mut conversion),
+        ConvertFormat::Csv => csv(entries, &mut conversion),
+    }
+    conversion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> LogEntry {
+        LogEntry::parse_log(line).unwrap()
+    }
+
+    const TEXT: [&str; 3] = [
+        "2024-01-15 08:15:23.145 INFO 192.168.1.105 GET /api/users 200 45ms",
+        "2024-01-15 08:15:25.891 ERROR 192.168.1.112 POST /api/orders 500 1234.5ms Payment failed",
+        "2024-01-15 08:16:00.000 WARNING Disk usage high",
+    ];
+
+    #[test]
+    fn jsonl_round_trips_the_typed_fields() {
+        let entries: Vec<LogEntry> = TEXT.iter().map(|line| parse(line)).collect();
+        let conversion = convert(&entries, ConvertFormat::Jsonl);
+        assert!(conversion.dropped.is_empty());
+        let reparsed: Vec<LogEntry> = conversion.output.lines().map(parse).collect();
+        assert_eq!(reparsed.len(), entries.len());
+        for (before, after) in entries.iter().zip(&reparsed) {
+            assert_eq!(serde_json::to_value(before).unwrap(), serde_json::to_value(after).unwrap(), "{}", after.raw);
+            assert!(after.extra.is_empty());
+        }
+    }
+
+    #[test]
+    fn jsonl_keeps_extra_fields_unless_a_typed_field_has_the_name() {
+        let entry = parse(r#"{"status_code":200,"status":70000,"user":{"id":7}}"#);
+        let conversion = convert(&[entry], ConvertFormat::Jsonl);
+        assert_eq!(conversion.output, "{\"status\":200,\"user\":{\"id\":7}}\n");
+        assert_eq!(conversion.dropped, BTreeMap::from([("status".to_string(), 1)]));
+    }
+
+    #[test]
+    fn clf_counts_what_it_cannot_hold() {
+        let entries = [parse(TEXT[1]), parse(r#"{"timestamp":"2024-01-15T08:15:25Z","path":"/a","trace":"abc","duration_ms":5}"#)];
+        let conversion = convert(&entries, ConvertFormat::Clf);
+        assert_eq!(
+            conversion.output,
+            "192.168.1.112 - - [15/Jan/2024:08:15:25 +0000] \"POST /api/orders HTTP/1.1\" 500 -\n\
+             - - - [15/Jan/2024:08:15:25 +0000] \"- /a -\" - -\n"
+        );
+        let dropped: Vec<(&str, usize)> = conversion.dropped.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(dropped, [("level", 1), ("message", 1), ("response_time", 2), ("trace", 1)]);
+    }
+
+    #[test]
+    fn logfmt_quotes_values_and_flattens_objects() {
+        let entry = parse(
+            r#"{"level":"info","message":"said \"hi\" a=b","empty":"","user":{"id":7,"name":"Ann Lee","tags":["a"]},"bad key":1}"#,
+        );
+        let conversion = convert(&[entry], ConvertFormat::Logfmt);
+        assert_eq!(
+            conversion.output,
+            "level=INFO message=\"said \\\"hi\\\" a=b\" empty=\"\" user.id=7 user.name=\"Ann Lee\" user.tags=\"[\\\"a\\\"]\"\n"
+        );
+        assert_eq!(
+            conversion.dropped,
+            BTreeMap::from([("bad key".to_string(), 1), ("user.tags".to_string(), 1)])
+        );
+        assert_eq!(logfmt_value("line\nbreak\\"), "\"line\\nbreak\\\\\"");
+    }
+
+    #[test]
+    fn csv_extra_columns_share_typed_columns_by_name() {
+        let entries = [
+            // The typed status wins over the extra one of the same name.
+            parse(r#"{"status_code":200,"status":70000,"region":"eu"}"#),
+            // Without a typed status, the extra value fills the column.
+            parse(r#"{"status":"n/a","detail":{"code":1},"region":null}"#),
+        ];
+        let conversion = convert(&entries, ConvertFormat::Csv);
+        let lines: Vec<&str> = conversion.output.lines().collect();
+        assert_eq!(lines[0], "timestamp,level,ip,method,endpoint,status,response_time,message,detail,region");
+        assert_eq!(lines[1], ",,,,,200,,,,eu");
+        assert_eq!(lines[2], ",,,,,n/a,,,\"{\"\"code\"\":1}\",");
+        assert_eq!(conversion.dropped, BTreeMap::from([("status".to_string(), 1)]));
+    }
+}
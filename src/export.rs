@@ -0,0 +1,258 @@
+//! CSV/TSV export of entries and of the [`LogStats`] tables. Parquet and
+//! Arrow IPC are written by [`crate::columnar`].
+
+use crate::columnar::ColumnarFormat;
+use crate::log_analyzer::{AnalyzerError, LogEntry, LogStats, slowest_first};
+use std::fs::File;
+use std::path::Path;
+
+/// Write `path` through a temporary file in the same directory, renamed into
+/// place once `write` succeeds, so readers never see a partial file and a
+/// failed write leaves an earlier file untouched.
+pub fn write_atomically<T>(
+    path: &Path,
+    write: impl FnOnce(File) -> Result<T, AnalyzerError>,
+) -> Result<T, AnalyzerError> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let result = File::create(&tmp).map_err(AnalyzerError::from).and_then(write);
+    match result {
+        Ok(value) => {
+            std::fs::rename(&tmp, path)?;
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// Columns exported when none are selected.
+pub const DEFAULT_COLUMNS: [&str; 9] = [
+    "line_number",
+    "timestamp",
+    "level",
+    "ip",
+    "method",
+    "endpoint",
+    "status",
+    "response_time",
+    "message",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Delimited {
+    /// RFC 4180: fields containing commas, quotes or line breaks are quoted,
+    /// with embedded quotes doubled.
+    #[default]
+    Csv,
+    /// Tab-separated; tabs, line breaks and backslashes inside fields are
+    /// escaped as `\t`, `\n`, `\r` and `\\`.
+    Tsv,
+}
+
+impl Delimited {
+    pub fn field(&self, value: &str) -> String {
+        match self {
+            Delimited::Csv if value.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            }
+            Delimited::Csv => value.to_string(),
+            Delimited::Tsv => value
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r"),
+        }
+    }
+
+    /// One line, terminated by `\n`.
+    pub fn row<S: AsRef<str>>(&self, fields: &[S]) -> String {
+        let separator = match self {
+            Delimited::Csv => ",",
+            Delimited::Tsv => "\t",
+        };
+        let mut line = fields
+            .iter()
+            .map(|f| self.field(f.as_ref()))
+            .collect::<Vec<_>>()
+            .join(separator);
+        line.push('\n');
+        line
+    }
+
+    /// Header row followed by the data rows.
+    pub fn table<S: AsRef<str>>(&self, header: &[S], rows: &[Vec<String>]) -> String {
+        let mut out = self.row(header);
+        for row in rows {
+            out.push_str(&self.row(row));
+        }
+        out
+    }
+}
+
+/// File formats of the `export` command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Tsv,
+    /// Typed columns, entries only; needs --out.
+    Parquet,
+    /// Arrow IPC file with typed columns, entries only; needs --out.
+    Arrow,
+    /// SQLite database with indexed entries and per-run aggregates; needs
+    /// --out.
+    Sqlite,
+}
+
+impl ExportFormat {
+    pub fn delimited(self) -> Option<Delimited> {
+        match self {
+            ExportFormat::Csv => Some(Delimited::Csv),
+            ExportFormat::Tsv => Some(Delimited::Tsv),
+            _ => None,
+        }
+    }
+
+    pub fn columnar(self) -> Option<ColumnarFormat> {
+        match self {
+            ExportFormat::Parquet => Some(ColumnarFormat::Parquet),
+            ExportFormat::Arrow => Some(ColumnarFormat::ArrowIpc),
+            _ => None,
+        }
+    }
+}
+
+/// Which table to export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportTable {
+    /// The filtered entries, one row each.
+    #[default]
+    Entries,
+    /// Request count per endpoint.
+    Endpoints,
+    /// Error count per endpoint.
+    Errors,
+    /// Entries with a response time, slowest first.
+    Slowest,
+}
+
+/// Value of an entry column: any [`LogEntry::field_value`] name (including
+/// extra JSON paths), `line_number`, `raw` or `template`.
+pub fn entry_column(entry: &LogEntry, column: &str) -> String {
+    match column {
+        "line_number" => entry.line_number.to_string(),
+        "raw" => entry.raw.clone(),
+        "template" => entry.message_template().unwrap_or_default(),
+        field => entry.field_value(field).unwrap_or_default(),
+    }
+}
+
+fn entry_rows<'a>(entries: impl Iterator<Item = &'a LogEntry>, columns: &[String]) -> Vec<Vec<String>> {
+    entries
+        .map(|entry| columns.iter().map(|c| entry_column(entry, c)).collect())
+        .collect()
+}
+
+fn count_rows(counts: &std::collections::HashMap<String, usize>) -> Vec<Vec<String>> {
+    let mut counts: Vec<_> = counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    counts
+        .into_iter()
+        .map(|(endpoint, count)| vec![endpoint.clone(), count.to_string()])
+        .collect()
+}
+
+/// Render `table` with a header row. `columns` applies to the entry tables
+/// (`entries` and `slowest`); empty means [`DEFAULT_COLUMNS`].
+pub fn export(
+    table: ExportTable,
+    format: Delimited,
+    columns: &[String],
+    entries: &[LogEntry],
+    stats: &LogStats,
+) -> Result<String, AnalyzerError> {
+    if !columns.is_empty() && !matches!(table, ExportTable::Entries | ExportTable::Slowest) {
+        return Err(AnalyzerError::OutputError(
+            "--columns only applies to the entries and slowest tables".to_string(),
+        ));
+    }
+    let columns: Vec<String> = if columns.is_empty() {
+        DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect()
+    } else {
+        columns.to_vec()
+    };
+    Ok(match table {
+        ExportTable::Entries => format.table(&columns, &entry_rows(entries.iter(), &columns)),
+        ExportTable::Slowest => {
+            format.table(&columns, &entry_rows(slowest_first(entries).into_iter(), &columns))
+        }
+        ExportTable::Endpoints => format.table(&["endpoint", "count"], &count_rows(&stats.endpoint_frequency)),
+        ExportTable::Errors => format.table(&["endpoint", "errors"], &count_rows(&stats.errors_by_endpoint)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<LogEntry> {
+        [
+            "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/orders 500 900ms",
+            "2024-01-15 08:15:20.000 INFO 10.0.0.2 GET /api/users 200 40ms",
+            r#"{"level":"error","path":"/api/orders","message":"a, \"b\"\r\nc","response_time":5}"#,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, line)| LogEntry { line_number: i + 1, ..LogEntry::parse_log(line).unwrap() })
+        .collect()
+    }
+
+    #[test]
+    fn csv_quotes_per_rfc_4180() {
+        let csv = Delimited::Csv;
+        assert_eq!(csv.field("plain text"), "plain text");
+        assert_eq!(csv.field("a,b"), "\"a,b\"");
+        assert_eq!(csv.field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv.field("one\ntwo"), "\"one\ntwo\"");
+        assert_eq!(csv.field("one\rtwo"), "\"one\rtwo\"");
+        assert_eq!(csv.field("tab\there"), "tab\there");
+        assert_eq!(csv.row(&["a", "b,c", ""]), "a,\"b,c\",\n");
+    }
+
+    #[test]
+    fn tsv_escapes_separators_and_backslashes() {
+        let tsv = Delimited::Tsv;
+        assert_eq!(tsv.field("a\tb\nc\rd\\e"), "a\\tb\\nc\\rd\\\\e");
+        assert_eq!(tsv.field("C:\\new"), "C:\\\\new");
+        assert_eq!(tsv.field("a,\"b\""), "a,\"b\"");
+        assert_eq!(tsv.row(&["a\tb", "c"]), "a\\tb\tc\n");
+    }
+
+    #[test]
+    fn entries_export_selected_columns() {
+        let entries = entries();
+        let stats = LogStats::from_entries(&entries);
+        let columns = ["line_number".to_string(), "message".to_string(), "status".to_string()];
+        let csv = export(ExportTable::Entries, Delimited::Csv, &columns, &entries, &stats).unwrap();
+        assert_eq!(csv, "line_number,message,status\n1,,500\n2,,200\n3,\"a, \"\"b\"\"\r\nc\",\n");
+        let tsv = export(ExportTable::Slowest, Delimited::Tsv, &columns[..2], &entries, &stats).unwrap();
+        assert_eq!(tsv, "line_number\tmessage\n1\t\n2\t\n3\ta, \"b\"\\r\\nc\n");
+    }
+
+    #[test]
+    fn count_tables_reject_columns() {
+        let entries = entries();
+        let stats = LogStats::from_entries(&entries);
+        for table in [ExportTable::Endpoints, ExportTable::Errors] {
+            let err = export(table, Delimited::Csv, &["endpoint".to_string()], &entries, &stats).unwrap_err();
+            assert!(err.to_string().contains("--columns only applies"), "{err}");
+        }
+        let errors = export(ExportTable::Errors, Delimited::Csv, &[], &entries, &stats).unwrap();
+        assert_eq!(errors, "endpoint,errors\n/api/orders,2\n");
+        let endpoints = export(ExportTable::Endpoints, Delimited::Csv, &[], &entries, &stats).unwrap();
+        assert_eq!(endpoints, "endpoint,count\n/api/orders,2\n/api/users,1\n");
+    }
+}
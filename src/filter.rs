@@ -0,0 +1,512 @@
+//! Composable predicates over [`LogEntry`].
+//!
+//! Every filter implements [`Filter`], so they can be stored, boxed, passed
+//! around and combined with [`And`], [`Or`] and [`Not`] (or the
+//! [`FilterExt`] helpers). Constructors that take user input return
+//! [`AnalyzerError`] instead of panicking.
+
+use crate::log_analyzer::{AnalyzerError, LogEntry, LogLevel, LogMethod};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    net::IpAddr,
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+pub trait Filter: Debug + Send + Sync {
+    fn matches(&self, entry: &LogEntry) -> bool;
+}
+
+impl Filter for Box<dyn Filter> {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        self.as_ref().matches(entry)
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for &F {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        (**self).matches(entry)
+    }
+}
+
+/// Combinator methods available on every sized filter.
+pub trait FilterExt: Filter + Sized + 'static {
+    fn and<F: Filter + 'static>(self, other: F) -> And {
+        And(vec![Box::new(self), Box::new(other)])
+    }
+
+    fn or<F: Filter + 'static>(self, other: F) -> Or {
+        Or(vec![Box::new(self), Box::new(other)])
+    }
+
+    fn not(self) -> Not {
+        Not(Box::new(self))
+    }
+
+    fn boxed(self) -> Box<dyn Filter> {
+        Box::new(self)
+    }
+}
+
+impl<F: Filter + Sized + 'static> FilterExt for F {}
+
+/// Matches when every inner filter matches; an empty `And` matches everything.
+#[derive(Debug, Default)]
+pub struct And(pub Vec<Box<dyn Filter>>);
+
+impl Filter for And {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        self.0.iter().all(|f| f.matches(entry))
+    }
+}
+
+/// Matches when any inner filter matches; an empty `Or` matches nothing.
+#[derive(Debug, Default)]
+pub struct Or(pub Vec<Box<dyn Filter>>);
+
+impl Filter for Or {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        self.0.iter().any(|f| f.matches(entry))
+    }
+}
+
+#[derive(Debug)]
+pub struct Not(pub Box<dyn Filter>);
+
+impl Filter for Not {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        !self.0.matches(entry)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelMatch {
+    Exact,
+    /// The level or anything more severe.
+    AtLeast,
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelFilter {
+    pub level: LogLevel,
+    pub mode: LevelMatch,
+}
+
+impl LevelFilter {
+    pub fn exact(level: &str) -> Result<Self, AnalyzerError> {
+        Ok(Self {
+            level: parse_level(level)?,
+            mode: LevelMatch::Exact,
+        })
+    }
+
+    pub fn at_least(level: &str) -> Result<Self, AnalyzerError> {
+        Ok(Self {
+            level: parse_level(level)?,
+            mode: LevelMatch::AtLeast,
+        })
+    }
+}
+
+/// Parse a level name case-insensitively, accepting `WARN` for `WARNING`.
+pub fn parse_level(level: &str) -> Result<LogLevel, AnalyzerError> {
+    match level.trim().to_uppercase().as_str() {
+        "WARN" => Ok(LogLevel::Warning),
+        upper => upper
+            .parse()
+            .map_err(|_| AnalyzerError::LogLevelParseError(level.to_string())),
+    }
+}
+
+impl Filter for LevelFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.level.as_ref().is_some_and(|level| match self.mode {
+            LevelMatch::Exact => *level == self.level,
+            LevelMatch::AtLeast => *level >= self.level,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MethodFilter(pub String);
+
+impl MethodFilter {
+    pub fn new(method: &str) -> Result<Self, AnalyzerError> {
+        let method: LogMethod = method.trim().to_uppercase().parse()?;
+        Ok(Self(method.to_string()))
+    }
+}
+
+impl Filter for MethodFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.method.as_ref().is_some_and(|m| m.to_string() == self.0)
+    }
+}
+
+/// Entries whose timestamp falls in `[start, end)`. Either bound may be open.
+/// Entries without a parsable timestamp never match.
+#[derive(Debug, Clone, Default)]
+pub struct TimeRangeFilter {
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+}
+
+impl TimeRangeFilter {
+    pub fn new(start: Option<NaiveDateTime>, end: Option<NaiveDateTime>) -> Self {
+        Self { start, end }
+    }
+
+    /// Whole-day range from `start` to `end` inclusive, both `%Y-%m-%d`.
+    pub fn from_dates(start: &str, end: &str) -> Result<Self, AnalyzerError> {
+        let start = NaiveDate::parse_from_str(start, "%Y-%m-%d")?;
+        let end = NaiveDate::parse_from_str(end, "%Y-%m-%d")?;
+        Ok(Self {
+            start: Some(start.and_time(NaiveTime::MIN)),
+            end: end.succ_opt().map(|d| d.and_time(NaiveTime::MIN)),
+        })
+    }
+
+    pub fn contains(&self, ts: &NaiveDateTime) -> bool {
+        self.start.is_none_or(|start| *ts >= start) && self.end.is_none_or(|end| *ts < end)
+    }
+}
+
+impl Filter for TimeRangeFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.parsed_timestamp().is_some_and(|ts| self.contains(&ts))
+    }
+}
+
+/// How a text field is compared against a pattern.
+#[derive(Debug, Clone)]
+pub enum TextMatcher {
+    Exact(String),
+    Prefix(String),
+    /// Shell-style glob: `*` matches within a path segment, `**` across
+    /// segments, `?` a single character.
+    Glob(Regex),
+    Regex(Regex),
+}
+
+impl TextMatcher {
+    pub fn glob(pattern: &str) -> Result<Self, AnalyzerError> {
+        let mut regex = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    regex.push_str(".*");
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+        Ok(TextMatcher::Glob(Regex::new(&regex)?))
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, AnalyzerError> {
+        Ok(TextMatcher::Regex(Regex::new(pattern)?))
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            TextMatcher::Exact(expected) => text == expected,
+            TextMatcher::Prefix(prefix) => text.starts_with(prefix.as_str()),
+            TextMatcher::Glob(re) | TextMatcher::Regex(re) => re.is_match(text),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EndpointFilter(pub TextMatcher);
+
+impl EndpointFilter {
+    pub fn exact(endpoint: &str) -> Self {
+        Self(TextMatcher::Exact(endpoint.to_string()))
+    }
+
+    pub fn prefix(prefix: &str) -> Self {
+        Self(TextMatcher::Prefix(prefix.to_string()))
+    }
+
+    pub fn glob(pattern: &str) -> Result<Self, AnalyzerError> {
+        Ok(Self(TextMatcher::glob(pattern)?))
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, AnalyzerError> {
+        Ok(Self(TextMatcher::regex(pattern)?))
+    }
+}
+
+impl Filter for EndpointFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.endpoint.as_deref().is_some_and(|e| self.0.is_match(e))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MessageFilter(pub Regex);
+
+impl MessageFilter {
+    pub fn new(pattern: &str) -> Result<Self, AnalyzerError> {
+        Ok(Self(Regex::new(pattern)?))
+    }
+}
+
+impl Filter for MessageFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.message.as_deref().is_some_and(|m| self.0.is_match(m))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusFilter(pub RangeInclusive<u16>);
+
+impl StatusFilter {
+    pub fn exact(status: u16) -> Self {
+        Self(status..=status)
+    }
+
+    /// Statuses from `low` to `high`; fails when `low` is above `high`.
+    pub fn range(low: u16, high: u16) -> Result<Self, AnalyzerError> {
+        if low > high {
+            return Err(AnalyzerError::InvalidFilter(format!(
+                "empty status range {low}-{high}"
+            )));
+        }
+        Ok(Self(low..=high))
+    }
+
+    /// A status class such as `5` for all 5xx responses; fails outside 1 to 9.
+    pub fn class(class: u16) -> Result<Self, AnalyzerError> {
+        if !(1..=9).contains(&class) {
+            return Err(AnalyzerError::InvalidFilter(format!(
+                "invalid status class '{class}xx' (expected 1xx to 9xx)"
+            )));
+        }
+        Ok(Self(class * 100..=class * 100 + 99))
+    }
+}
+
+impl FromStr for StatusFilter {
+    type Err = AnalyzerError;
+
+    /// Accepts `404`, `500-599`, `500..599` or `5xx`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AnalyzerError::InvalidFilter(format!("invalid status '{s}'"));
+        let s = s.trim();
+        if let Some(class) = s.strip_suffix("xx").or_else(|| s.strip_suffix("XX")) {
+            return Self::class(class.parse().map_err(|_| invalid())?);
+        }
+        if let Some((low, high)) = s.split_once("..").or_else(|| s.split_once('-')) {
+            let low = low.trim().parse().map_err(|_| invalid())?;
+            let high = high.trim().parse().map_err(|_| invalid())?;
+            return Self::range(low, high);
+        }
+        s.parse().map(Self::exact).map_err(|_| invalid())
+    }
+}
+
+impl Filter for StatusFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.status_code.is_some_and(|s| self.0.contains(&s))
+    }
+}
+
+/// An IPv4 or IPv6 network in CIDR notation. A bare address is a /32 (or
+/// /128) network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    pub network: IpAddr,
+    pub prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (network, prefix) = match s.split_once('/') {
+            Some((network, prefix)) => (network, Some(prefix)),
+            None => (s, None),
+        };
+        let network: IpAddr = network
+            .parse()
+            .map_err(|_| format!("invalid IP address '{network}'"))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("invalid prefix length '{p}'"))?,
+            None => max,
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IpFilter(pub Cidr);
+
+impl IpFilter {
+    /// Accepts a single address or a CIDR network.
+    pub fn new(spec: &str) -> Result<Self, AnalyzerError> {
+        spec.trim()
+            .parse()
+            .map(Self)
+            .map_err(AnalyzerError::InvalidFilter)
+    }
+}
+
+impl Filter for IpFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.ip_address.is_some_and(|ip| self.0.contains(&ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: &str) -> LogEntry {
+        LogEntry::parse_log(line).unwrap()
+    }
+
+    const ORDER_FAILED: &str = "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/v1/orders/42 500 900ms";
+    const USERS_OK: &str = "2024-01-15 23:59:59.000 INFO 192.168.1.20 GET /api/users 200 40ms";
+    const STARTED: &str = r#"{"timestamp":"2024-01-16T00:00:00","level":"WARN","message":"Service restarted"}"#;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn globs_stay_within_segments_unless_doubled() {
+        let matches = |pattern: &str, endpoint: &str| TextMatcher::glob(pattern).unwrap().is_match(endpoint);
+        assert!(matches("/api/*", "/api/users"));
+        assert!(!matches("/api/*", "/api/v1/orders"));
+        assert!(matches("/api/**", "/api/v1/orders/42"));
+        assert!(matches("/api/**/42", "/api/v1/orders/42"));
+        assert!(matches("/api/v?/*/42", "/api/v1/orders/42"));
+        assert!(!matches("/api/v?", "/api/v10"));
+        assert!(matches("/a.b+(c)", "/a.b+(c)"), "regex characters are literal");
+        assert!(!matches("/a.b", "/axb"));
+        assert!(!matches("/api", "/api/users"), "globs are anchored");
+        assert!(EndpointFilter::glob("/api/*/orders/*").unwrap().matches(&entry(ORDER_FAILED)));
+        assert!(!EndpointFilter::glob("/api/*").unwrap().matches(&entry(ORDER_FAILED)));
+        assert!(!EndpointFilter::glob("/**").unwrap().matches(&entry(STARTED)), "no endpoint never matches");
+    }
+
+    #[test]
+    fn cidr_networks_contain_their_addresses() {
+        let cidr = |s: &str| s.parse::<Cidr>().unwrap();
+        assert!(cidr("10.0.0.0/8").contains(&ip("10.255.0.1")));
+        assert!(!cidr("10.0.0.0/8").contains(&ip("11.0.0.1")));
+        assert!(cidr("192.168.1.16/28").contains(&ip("192.168.1.31")));
+        assert!(!cidr("192.168.1.16/28").contains(&ip("192.168.1.32")));
+        assert!(cidr("0.0.0.0/0").contains(&ip("203.0.113.9")));
+        assert!(cidr("10.0.0.1").contains(&ip("10.0.0.1")));
+        assert!(!cidr("10.0.0.1").contains(&ip("10.0.0.2")));
+        assert_eq!(cidr("10.0.0.1").to_string(), "10.0.0.1/32");
+
+        assert!(cidr("2001:db8::/32").contains(&ip("2001:db8:ffff::1")));
+        assert!(!cidr("2001:db8::/32").contains(&ip("2001:db9::1")));
+        assert!(cidr("::/0").contains(&ip("fe80::1")));
+        assert_eq!(cidr("::1").prefix, 128);
+        assert!(!cidr("::/0").contains(&ip("10.0.0.1")), "families never mix");
+        assert!(!cidr("0.0.0.0/0").contains(&ip("::1")));
+
+        for spec in ["10.0.0.0/33", "::/129", "10.0.0/8", "10.0.0.0/x"] {
+            assert!(IpFilter::new(spec).is_err(), "{spec}");
+        }
+        assert!(IpFilter::new(" 192.168.0.0/16 ").unwrap().matches(&entry(USERS_OK)));
+        assert!(!IpFilter::new("192.168.0.0/16").unwrap().matches(&entry(STARTED)));
+    }
+
+    #[test]
+    fn statuses_parse_as_codes_ranges_and_classes() {
+        let status = |s: &str| s.parse::<StatusFilter>().unwrap().0;
+        assert_eq!(status("404"), 404..=404);
+        assert_eq!(status("500-599"), 500..=599);
+        assert_eq!(status("400..499"), 400..=499);
+        assert_eq!(status(" 5xx "), 500..=599);
+        assert_eq!(status("2XX"), 200..=299);
+        for s in ["599-500", "0xx", "10xx", "abc", "5x", "-1"] {
+            assert!(s.parse::<StatusFilter>().is_err(), "{s}");
+        }
+        assert!(status("5xx").contains(&500) && !status("5xx").contains(&600));
+        assert!(StatusFilter::class(5).unwrap().matches(&entry(ORDER_FAILED)));
+        assert!(!StatusFilter::class(5).unwrap().matches(&entry(USERS_OK)));
+        assert!(!StatusFilter::range(0, 999).unwrap().matches(&entry(STARTED)));
+    }
+
+    #[test]
+    fn levels_match_exactly_or_at_least() {
+        let (error, info, warning) = (entry(ORDER_FAILED), entry(USERS_OK), entry(STARTED));
+        let at_least = LevelFilter::at_least("warn").unwrap();
+        assert!(at_least.matches(&error) && at_least.matches(&warning) && !at_least.matches(&info));
+        let exact = LevelFilter::exact("Warning").unwrap();
+        assert!(exact.matches(&warning) && !exact.matches(&error) && !exact.matches(&info));
+        assert!(LevelFilter::at_least("info").unwrap().matches(&info));
+        assert!(LevelFilter::exact("verbose").is_err());
+    }
+
+    #[test]
+    fn time_ranges_are_half_open() {
+        let (first, last_of_day, midnight) = (entry(ORDER_FAILED), entry(USERS_OK), entry(STARTED));
+        let day = TimeRangeFilter::from_dates("2024-01-15", "2024-01-15").unwrap();
+        assert!(day.matches(&first) && day.matches(&last_of_day) && !day.matches(&midnight));
+        let start = first.parsed_timestamp();
+        let from_first = TimeRangeFilter::new(start, None);
+        assert!(from_first.matches(&first) && from_first.matches(&midnight));
+        let until_first = TimeRangeFilter::new(None, start);
+        assert!(!until_first.matches(&first));
+        assert!(TimeRangeFilter::default().matches(&first));
+        assert!(!TimeRangeFilter::default().matches(&entry("GET /api 200 no timestamp here")));
+        assert!(TimeRangeFilter::from_dates("2024-01-15", "15/01/2024").is_err());
+    }
+
+    #[test]
+    fn combinators_compose() {
+        let (error, info, warning) = (entry(ORDER_FAILED), entry(USERS_OK), entry(STARTED));
+        let post_errors = LevelFilter::exact("ERROR").unwrap().and(MethodFilter::new("post").unwrap());
+        assert!(post_errors.matches(&error) && !post_errors.matches(&info));
+        let loud = LevelFilter::exact("ERROR").unwrap().or(LevelFilter::exact("WARNING").unwrap());
+        assert!(loud.matches(&error) && loud.matches(&warning) && !loud.matches(&info));
+        let quiet = loud.not();
+        assert!(quiet.matches(&info) && !quiet.matches(&error));
+        assert!(And::default().matches(&info), "empty And matches everything");
+        assert!(!Or::default().matches(&info), "empty Or matches nothing");
+        let boxed: Vec<Box<dyn Filter>> =
+            vec![MessageFilter::new("restart").unwrap().boxed(), LevelFilter::exact("ERROR").unwrap().not().boxed()];
+        let restarts = And(boxed);
+        assert!(restarts.matches(&warning) && !restarts.matches(&error));
+    }
+}
@@ -0,0 +1,352 @@
+use crate::aggregate::Agg;
+use crate::filter::parse_level;
+use crate::log_analyzer::{AnalyzerError, LogEntry, LogLevel, LogStats};
+use crate::rules::Comparison;
+use crate::text_report::{TextLayout, truncate};
+use colored::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+/// A value derived from a log file that a CI check can be gated on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Metric {
+    /// An aggregate over all entries, as for `--agg`, e.g. `error_rate`,
+    /// `max(response_time)` or `count_distinct(ip)`. Bare percentiles such as
+    /// `p95` are shorthands for response times, `total` for `count`.
+    Aggregate(Agg),
+    /// The average response time shown in the report (`avg`).
+    AvgResponseTime,
+    /// Number of entries at the given level, e.g. `count(WARNING)`.
+    LevelCount(LogLevel),
+    /// Message templates not present in the baseline.
+    NewTemplates,
+}
+
+impl FromStr for Metric {
+    type Err = AnalyzerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s {
+            "avg" | "avg_response_time" => return Ok(Metric::AvgResponseTime),
+            "total" | "total_requests" => return Ok(Metric::Aggregate(Agg::Count)),
+            "new_templates" => return Ok(Metric::NewTemplates),
+            _ => {}
+        }
+        if let Some(level) = s.strip_prefix("count(").and_then(|r| r.strip_suffix(')')) {
+            return Ok(Metric::LevelCount(parse_level(level)?));
+        }
+        if s.starts_with('p')
+            && !s.contains('(')
+            && let Some(agg) = Agg::call(s, "response_time")
+        {
+            return Ok(Metric::Aggregate(agg));
+        }
+        s.parse()
+            .map(Metric::Aggregate)
+            .map_err(|_| AnalyzerError::CheckParseError(format!("unknown metric '{s}'")))
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Aggregate(agg) => write!(f, "{agg}"),
+            Metric::AvgResponseTime => write!(f, "avg_response_time"),
+            Metric::LevelCount(level) => write!(f, "count({level})"),
+            Metric::NewTemplates => write!(f, "new_templates"),
+        }
+    }
+}
+
+/// A single `--fail-on` threshold such as `error_rate>5` or `p95>=800`.
+/// A bare `new_templates` is shorthand for `new_templates>0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+}
+
+impl FromStr for Check {
+    type Err = AnalyzerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let operators = [
+            (">=", Comparison::AtLeast),
+            ("<=", Comparison::AtMost),
+            (">", Comparison::Above),
+            ("<", Comparison::Below),
+        ];
+        for (op, comparison) in operators {
+            if let Some((metric, threshold)) = s.split_once(op) {
+                let threshold = threshold.trim().parse().map_err(|_| {
+                    AnalyzerError::CheckParseError(format!("invalid threshold in '{s}'"))
+                })?;
+                return Ok(Self {
+                    metric: metric.parse()?,
+                    comparison,
+                    threshold,
+                });
+            }
+        }
+        match s.parse()? {
+            Metric::NewTemplates => Ok(Self {
+                metric: Metric::NewTemplates,
+                comparison: Comparison::Above,
+                threshold: 0.0,
+            }),
+            _ => Err(AnalyzerError::CheckParseError(format!(
+                "missing comparison in '{s}' (expected e.g. error_rate>5)"
+            ))),
+        }
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.metric, self.comparison, self.threshold)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CheckResult {
+    pub check: String,
+    pub metric: String,
+    pub actual: f64,
+    pub threshold: f64,
+    pub failed: bool,
+    /// Offending values, e.g. the new message templates.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+}
+
+/// Outcome of all checks, suitable for CI consumption.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GateReport {
+    pub passed: bool,
+    pub failed: Vec<String>,
+    pub checks: Vec<CheckResult>,
+}
+
+/// Known message templates from a previous run, one per line.
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    pub templates: BTreeSet<String>,
+}
+
+impl Baseline {
+    pub fn from_file(path: &Path) -> Result<Self, AnalyzerError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self {
+            templates: contents
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+
+    pub fn from_entries(entries: &[LogEntry]) -> Self {
+        Self {
+            templates: entries.iter().filter_map(|e| e.message_template()).collect(),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), AnalyzerError> {
+        let mut contents = String::new();
+        for template in &self.templates {
+            contents.push_str(template);
+            contents.push('\n');
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Evaluate each check against the stats. `new_templates` compares against
+/// `baseline` and fails to evaluate without one, and a metric with no data
+/// (say `p95` of a file without response times) fails with
+/// [`AnalyzerError::NoMetricData`] rather than passing without checking
+/// anything.
+pub fn evaluate(
+    checks: &[Check],
+    stats: &LogStats,
+    entries: &[LogEntry],
+    baseline: Option<&Baseline>,
+) -> Result<GateReport, AnalyzerError> {
+    let results: Vec<CheckResult> = checks
+        .iter()
+        .map(|check| {
+            let mut details = Vec::new();
+            let actual = match &check.metric {
+                Metric::Aggregate(agg) => agg
+                    .compute(entries, entries.len())
+                    .ok_or_else(|| AnalyzerError::NoMetricData(check.metric.to_string()))?,
+                Metric::AvgResponseTime if stats.total_requests == 0 => {
+                    return Err(AnalyzerError::NoMetricData(check.metric.to_string()));
+                }
+                Metric::AvgResponseTime => stats.avg_response_time,
+                Metric::LevelCount(level) => stats.level_count(level) as f64,
+                Metric::NewTemplates => {
+                    let baseline = baseline.ok_or_else(|| {
+                        AnalyzerError::CheckParseError(format!("{} needs --baseline FILE", check.metric))
+                    })?;
+                    let current = Baseline::from_entries(entries);
+                    details = current
+                        .templates
+                        .difference(&baseline.templates)
+                        .cloned()
+                        .collect();
+                    details.len() as f64
+                }
+            };
+            Ok(CheckResult {
+                check: check.to_string(),
+                metric: check.metric.to_string(),
+                actual,
+                threshold: check.threshold,
+                failed: check.comparison.breached(actual, check.threshold),
+                details,
+            })
+        })
+        .collect::<Result<_, AnalyzerError>>()?;
+
+    let failed: Vec<String> = results
+        .iter()
+        .filter(|r| r.failed)
+        .map(|r| r.check.clone())
+        .collect();
+    Ok(GateReport {
+        passed: failed.is_empty(),
+        failed,
+        checks: results,
+    })
+}
+
+/// Print the check results in the same style as the main report.
+pub fn print_gate_report(report: &GateReport, layout: &TextLayout) {
+    println!("{}", layout.title("🚦", "CI CHECKS"));
+
+    for result in &report.checks {
+        let status = if result.failed {
+            "FAIL".red().bold()
+        } else {
+            "PASS".green().bold()
+        };
+        println!(
+            "  {}  {:<30} {}",
+            status,
+            result.check,
+            format!("actual {:.2}", result.actual).bright_black()
+        );
+        for detail in &result.details {
+            println!("        {}", truncate(detail, layout.width.saturating_sub(8), layout.ascii).yellow());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(lines: &[&str]) -> Vec<LogEntry> {
+        lines.iter().map(|line| LogEntry::parse_log(line).unwrap()).collect()
+    }
+
+    const LINES: [&str; 4] = [
+        "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/orders 500 900ms User 42 failed",
+        "2024-01-15 08:15:20.000 INFO 10.0.0.2 GET /api/users 200 40ms User 7 logged in",
+        "2024-01-15 08:15:40.000 WARNING 10.0.0.3 GET /api/users 200 60ms Slow query",
+        "2024-01-15 08:16:10.000 INFO 10.0.0.1 GET /api/users 200 100ms User 9 logged in",
+    ];
+
+    fn check(s: &str) -> Check {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn checks_parse() {
+        assert_eq!(
+            check("error_rate>5"),
+            Check { metric: Metric::Aggregate(Agg::ErrorRate), comparison: Comparison::Above, threshold: 5.0 }
+        );
+        assert_eq!(check("p95 >= 800").metric, Metric::Aggregate(Agg::Percentile(95.0, "response_time".to_string())));
+        assert_eq!(check("p95 >= 800").comparison, Comparison::AtLeast);
+        assert_eq!(check("avg<=100").metric, Metric::AvgResponseTime);
+        assert_eq!(check("avg(response_time)<=100").metric, Metric::Aggregate(Agg::Avg("response_time".to_string())));
+        assert_eq!(check("total<10").metric, Metric::Aggregate(Agg::Count));
+        assert_eq!(check("count(warn)>0").metric, Metric::LevelCount(LogLevel::Warning));
+        assert_eq!(check("max(response_time)>1").to_string(), "max(response_time)>1");
+        assert_eq!(
+            check("new_templates"),
+            Check { metric: Metric::NewTemplates, comparison: Comparison::Above, threshold: 0.0 }
+        );
+        for (s, message) in [
+            ("error_rate", "missing comparison"),
+            ("error_rate>lots", "invalid threshold"),
+            ("speed>5", "unknown metric"),
+            ("count(LOUD)>0", "LOUD"),
+        ] {
+            let err = s.parse::<Check>().unwrap_err().to_string();
+            assert!(err.contains(message), "{s}: {err}");
+        }
+    }
+
+    #[test]
+    fn evaluate_compares_each_metric() {
+        let entries = entries(&LINES);
+        let stats = LogStats::from_entries(&entries);
+        let checks = [check("error_rate>25"), check("count(WARNING)>=1"), check("max(response_time)<1000")];
+        let report = evaluate(&checks, &stats, &entries, None).unwrap();
+        assert!(!report.passed);
+        assert_eq!(report.failed, ["count(WARNING)>=1", "max(response_time)<1000"]);
+        assert_eq!(report.checks[0].actual, 25.0);
+        assert!(!report.checks[0].failed, "25 is not above 25");
+    }
+
+    #[test]
+    fn new_templates_are_checked_against_the_baseline() {
+        let entries = entries(&LINES);
+        let stats = LogStats::from_entries(&entries);
+        let checks = [check("new_templates")];
+        let err = evaluate(&checks, &stats, &entries, None).unwrap_err();
+        assert!(err.to_string().contains("--baseline"), "{err}");
+
+        let baseline = Baseline::from_entries(&entries[1..]);
+        let report = evaluate(&checks, &stats, &entries, Some(&baseline)).unwrap();
+        assert!(!report.passed);
+        assert_eq!(report.checks[0].details.len(), 1);
+        assert!(evaluate(&checks, &stats, &entries, Some(&Baseline::from_entries(&entries))).unwrap().passed);
+    }
+
+    #[test]
+    fn avg_is_the_reported_average() {
+        let mut entries = entries(&LINES);
+        entries.push(LogEntry::parse_log("2024-01-15 08:16:30.000 INFO Service started").unwrap());
+        let stats = LogStats::from_entries(&entries);
+        let report = evaluate(&[check("avg<=200")], &stats, &entries, None).unwrap();
+        assert_eq!(report.checks[0].actual, stats.avg_response_time);
+        assert_eq!(report.checks[0].check, "avg_response_time<=200");
+    }
+
+    #[test]
+    fn metrics_without_data_are_not_evaluable() {
+        let entries = entries(&["2024-01-15 08:15:01.000 INFO Service started"]);
+        let stats = LogStats::from_entries(&entries);
+        let err = evaluate(&[check("p95<800")], &stats, &entries, None).unwrap_err();
+        assert!(matches!(&err, AnalyzerError::NoMetricData(metric) if metric == "p95(response_time)"), "{err}");
+
+        let stats = LogStats::from_entries(&[]);
+        let err = evaluate(&[check("avg<800")], &stats, &[], None).unwrap_err();
+        assert!(matches!(err, AnalyzerError::NoMetricData(_)), "{err}");
+    }
+}
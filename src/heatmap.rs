@@ -0,0 +1,201 @@
+//! Hour-of-day by day-of-week heatmaps, for spotting weekly traffic and
+//! error patterns such as quiet maintenance windows.
+//!
+//! Timestamps are read as UTC (offsets are converted) and shifted into the
+//! chosen IANA timezone before bucketing, so daylight saving time moves the
+//! cells the way local clocks do.
+
+use crate::log_analyzer::{LogEntry, LogLevel, percentile};
+use chrono::{Datelike, Timelike};
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// What a heatmap cell shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatmapMetric {
+    /// Number of requests.
+    #[default]
+    Volume,
+    /// Share of error entries, 0 to 100.
+    ErrorRate,
+    /// 95th percentile response time in milliseconds.
+    P95,
+}
+
+impl HeatmapMetric {
+    /// What is measured, e.g. `error rate`.
+    pub fn label(self) -> &'static str {
+        match self {
+            HeatmapMetric::Volume => "requests",
+            HeatmapMetric::ErrorRate => "error rate",
+            HeatmapMetric::P95 => "p95 latency",
+        }
+    }
+
+    /// A cell value with its unit, e.g. `12 requests` or `3.5% errors`.
+    pub fn format(self, value: f64) -> String {
+        match self {
+            HeatmapMetric::Volume => format!("{value:.0} requests"),
+            HeatmapMetric::ErrorRate => format!("{value:.1}% errors"),
+            HeatmapMetric::P95 => format!("{value:.2}ms p95"),
+        }
+    }
+}
+
+/// A weekday by hour matrix; rows are Monday to Sunday, columns the hours
+/// 0 to 23.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Heatmap {
+    pub metric: HeatmapMetric,
+    /// IANA name of the timezone the hours are in.
+    pub timezone: String,
+    /// The metric per cell; none where it is undefined (no requests, or no
+    /// response times for `p95`).
+    pub values: Vec<Vec<Option<f64>>>,
+    /// Entries per cell.
+    pub requests: Vec<Vec<usize>>,
+    /// Largest value of any cell.
+    pub max: Option<f64>,
+}
+
+/// Parse an IANA timezone name such as `Europe/Berlin` or `UTC`.
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse()
+        .map_err(|_| format!("unknown timezone '{name}' (expected an IANA name such as Europe/Berlin)"))
+}
+
+impl Heatmap {
+    /// Bucket the entries with a timestamp; `None` when none has one.
+    pub fn new(entries: &[LogEntry], metric: HeatmapMetric, timezone: Tz) -> Option<Self> {
+        let mut requests = vec![vec![0usize; 24]; 7];
+        let mut errors = vec![vec![0usize; 24]; 7];
+        let mut times = vec![vec![Vec::new(); 24]; 7];
+        let mut any = false;
+        for entry in entries {
+            let Some(ts) = entry.parsed_timestamp() else { continue };
+            let local = ts.and_utc().with_timezone(&timezone);
+            let (day, hour) = (local.weekday().num_days_from_monday() as usize, local.hour() as usize);
+            any = true;
+            requests[day][hour] += 1;
+            if entry.level == Some(LogLevel::Error) {
+                errors[day][hour] += 1;
+            }
+            times[day][hour].extend(entry.response_time);
+        }
+        if !any {
+            return None;
+        }
+        let values: Vec<Vec<Option<f64>>> = (0..7)
+            .map(|day| {
+                (0..24)
+                    .map(|hour| {
+                        let count = requests[day][hour];
+                        match metric {
+                            HeatmapMetric::Volume => Some(count as f64),
+                            HeatmapMetric::ErrorRate => {
+                                (count > 0).then(|| errors[day][hour] as f64 / count as f64 * 100.0)
+                            }
+                            HeatmapMetric::P95 => percentile(&times[day][hour], 95.0),
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let max = values.iter().flatten().flatten().copied().reduce(f64::max);
+        Some(Self {
+            metric,
+            timezone: timezone.name().to_string(),
+            values,
+            requests,
+            max,
+        })
+    }
+
+    /// The cell with the largest value as (weekday, hour, value); the
+    /// earliest one on ties.
+    pub fn peak(&self) -> Option<(usize, usize, f64)> {
+        let mut peak: Option<(usize, usize, f64)> = None;
+        for (day, row) in self.values.iter().enumerate() {
+            for (hour, value) in row.iter().enumerate() {
+                if let Some(value) = *value
+                    && peak.is_none_or(|(_, _, best)| value > best)
+                {
+                    peak = Some((day, hour, value));
+                }
+            }
+        }
+        peak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: &str, level: &str, ms: u32) -> LogEntry {
+        LogEntry::parse_log(&format!("{timestamp} {level} 10.0.0.1 GET /api 200 {ms}ms")).unwrap()
+    }
+
+    #[test]
+    fn buckets_by_local_weekday_and_hour_across_a_dst_change() {
+        let entries = [
+            // Berlin switches to CEST at 01:00 UTC on Sunday 2024-03-31.
+            entry("2024-03-24 01:30:00.000", "INFO", 10),
+            entry("2024-03-31 00:30:00.000", "INFO", 10),
+            entry("2024-03-31 01:30:00.000", "INFO", 10),
+            entry("2024-03-31 23:30:00.000", "INFO", 10),
+        ];
+        let heatmap = Heatmap::new(&entries, HeatmapMetric::Volume, parse_timezone("Europe/Berlin").unwrap()).unwrap();
+        assert_eq!(heatmap.timezone, "Europe/Berlin");
+        let (sun, mon) = (6, 0);
+        assert_eq!(heatmap.requests[sun][1], 1);
+        assert_eq!(heatmap.requests[sun][2], 1);
+        assert_eq!(heatmap.requests[sun][3], 1);
+        assert_eq!(heatmap.requests[mon][1], 1);
+        assert_eq!(heatmap.requests.iter().flatten().sum::<usize>(), 4);
+        assert_eq!(heatmap.values[mon][1], Some(1.0));
+        assert_eq!(heatmap.values[mon][2], Some(0.0));
+    }
+
+    #[test]
+    fn error_rate_and_p95_are_undefined_without_data() {
+        let mut untimed = entry("2024-03-25 10:00:00.000", "ERROR", 0);
+        untimed.response_time = None;
+        let entries = [entry("2024-03-25 09:00:00.000", "INFO", 40), untimed];
+
+        let rate = Heatmap::new(&entries, HeatmapMetric::ErrorRate, Tz::UTC).unwrap();
+        assert_eq!(rate.values[0][9], Some(0.0));
+        assert_eq!(rate.values[0][10], Some(100.0));
+        assert_eq!(rate.values[0][11], None);
+        assert_eq!(rate.max, Some(100.0));
+
+        let p95 = Heatmap::new(&entries, HeatmapMetric::P95, Tz::UTC).unwrap();
+        assert_eq!(p95.values[0][9], Some(40.0));
+        assert_eq!(p95.values[0][10], None);
+        assert_eq!(p95.requests[0][10], 1);
+
+        let mut no_timestamp = entries[0].clone();
+        no_timestamp.timestamp = None;
+        assert!(Heatmap::new(&[no_timestamp], HeatmapMetric::Volume, Tz::UTC).is_none());
+    }
+
+    #[test]
+    fn peak_prefers_the_earliest_cell_on_ties() {
+        let entries = [
+            entry("2024-03-27 15:00:00.000", "INFO", 10),
+            entry("2024-03-26 18:00:00.000", "INFO", 10),
+            entry("2024-03-26 07:00:00.000", "INFO", 10),
+            entry("2024-03-28 01:00:00.000", "INFO", 10),
+        ];
+        let heatmap = Heatmap::new(&entries, HeatmapMetric::Volume, Tz::UTC).unwrap();
+        assert_eq!(heatmap.peak(), Some((1, 7, 1.0)));
+
+        let busier = [&entries[..], &[entry("2024-03-28 01:30:00.000", "INFO", 10)]].concat();
+        let heatmap = Heatmap::new(&busier, HeatmapMetric::Volume, Tz::UTC).unwrap();
+        assert_eq!(heatmap.peak(), Some((3, 1, 2.0)));
+    }
+}
@@ -0,0 +1,484 @@
+//! Self-contained HTML report.
+//!
+//! Everything — styles, charts and the table sorting script — is inlined, so
+//! the file can be mailed around or opened on an air-gapped machine. Charts
+//! are plain SVG generated here rather than drawn by a JavaScript library.
+
+use crate::log_analyzer::{LogEntry, LogLevel, LogStats, slowest_first};
+use crate::report::{Report, TimeSeries};
+use chrono::NaiveDateTime;
+use std::{collections::BTreeMap, fmt::Write};
+
+/// How many rows the endpoint, error and slowest-request tables show.
+const TABLE_ROWS: usize = 50;
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Roboto, Helvetica, Arial, sans-serif; margin: 0; background: #f4f6f8; color: #1f2933; }
+header { background: #0b7285; color: #fff; padding: 24px 32px; }
+header h1 { margin: 0 0 4px; font-size: 24px; }
+header p { margin: 0; opacity: .85; font-size: 14px; }
+main { padding: 24px 32px; max-width: 1200px; }
+section { background: #fff; border-radius: 8px; padding: 16px 20px; margin-bottom: 20px; box-shadow: 0 1px 3px rgba(0,0,0,.08); }
+h2 { font-size: 18px; margin: 0 0 12px; }
+.cards { display: grid; grid-template-columns: repeat(auto-fill, minmax(160px, 1fr)); gap: 12px; }
+.card { background: #fff; border-radius: 8px; padding: 14px 16px; box-shadow: 0 1px 3px rgba(0,0,0,.08); }
+.card .label { font-size: 12px; text-transform: uppercase; color: #616e7c; }
+.card .value { font-size: 26px; font-weight: 600; margin-top: 4px; }
+.error { color: #c92a2a; } .warning { color: #e67700; } .info { color: #2b8a3e; }
+table { border-collapse: collapse; width: 100%; font-size: 14px; }
+th, td { text-align: left; padding: 6px 8px; border-bottom: 1px solid #e4e7eb; }
+th { background: #f0f4f8; cursor: pointer; user-select: none; white-space: nowrap; }
+th.sorted-asc::after { content: " \25B2"; } th.sorted-desc::after { content: " \25BC"; }
+td.num, th.num { text-align: right; font-variant-numeric: tabular-nums; }
+td.mono { font-family: ui-monospace, Menlo, Consolas, monospace; word-break: break-all; }
+.bar { background: #74c0fc; height: 10px; border-radius: 2px; }
+.bar.error { background: #ff8787; }
+svg text { font-size: 11px; fill: #52606d; }
+.muted { color: #7b8794; font-size: 13px; }
+footer { padding: 0 32px 24px; color: #7b8794; font-size: 12px; }
+"#;
+
+/// Click a header to sort by that column; numeric columns sort numerically.
+const SORT_SCRIPT: &str = r#"
+document.querySelectorAll("table.sortable").forEach(function (table) {
+  table.querySelectorAll("th").forEach(function (th, col) {
+    th.addEventListener("click", function () {
+      var asc = !th.classList.contains("sorted-asc");
+      table.querySelectorAll("th").forEach(function (h) { h.classList.remove("sorted-asc", "sorted-desc"); });
+      th.classList.add(asc ? "sorted-asc" : "sorted-desc");
+      var body = table.tBodies[0];
+      var rows = Array.prototype.slice.call(body.rows);
+      rows.sort(function (a, b) {
+        var x = a.cells[col].dataset.sort || a.cells[col].textContent;
+        var y = b.cells[col].dataset.sort || b.cells[col].textContent;
+        var nx = parseFloat(x), ny = parseFloat(y);
+        var cmp = (!isNaN(nx) && !isNaN(ny)) ? nx - ny : x.localeCompare(y);
+        return asc ? cmp : -cmp;
+      });
+      rows.forEach(function (row) { body.appendChild(row); });
+    });
+  });
+});
+"#;
+
+/// Escape text for use in HTML content and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Render the whole report over `entries` as one HTML document.
+pub fn render(report: &Report, entries: &[LogEntry]) -> String {
+    let stats = report.stats;
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    let _ = writeln!(html, "<title>Log analysis — {}</title>", escape(&report.input.path));
+    let _ = writeln!(html, "<style>{STYLE}</style>\n</head>\n<body>");
+
+    let range = match (report.input.first_timestamp, report.input.last_timestamp) {
+        (Some(first), Some(last)) => format!(
+            " · {} → {}",
+            first.format("%Y-%m-%d %H:%M:%S"),
+            last.format("%Y-%m-%d %H:%M:%S")
+        ),
+        _ => String::new(),
+    };
+    let _ = writeln!(
+        html,
+        "<header><h1>Log Analysis Report</h1><p>{}{} · {} entries</p></header>\n<main>",
+        escape(&report.input.path),
+        range,
+        stats.total_requests
+    );
+
+    summary_cards(&mut html, stats);
+    latency_histogram(&mut html, report);
+    time_series(&mut html, entries);
+    top_endpoints(&mut html, stats);
+    error_analysis(&mut html, stats, entries);
+    slowest_requests(&mut html, entries);
+    if let Some(findings) = report.findings {
+        html.push_str("<section><h2>Security findings</h2>");
+        if findings.is_empty() {
+            html.push_str("<p class=\"muted\">No suspicious activity detected.</p>");
+        } else {
+            html.push_str("<table class=\"sortable\"><thead><tr><th>Severity</th><th>Kind</th><th>IP</th><th>Summary</th></tr></thead><tbody>");
+            for finding in findings {
+                let _ = write!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td class=\"mono\">{}</td><td>{}</td></tr>",
+                    finding.severity,
+                    finding.kind,
+                    finding.source_ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                    escape(&finding.summary)
+                );
+            }
+            html.push_str("</tbody></table>");
+        }
+        html.push_str("</section>\n");
+    }
+    if let Some(alerts) = report.alerts {
+        html.push_str("<section><h2>Alerts</h2>");
+        if alerts.is_empty() {
+            html.push_str("<p class=\"muted\">No rules triggered.</p>");
+        } else {
+            html.push_str("<table class=\"sortable\"><thead><tr><th>Severity</th><th>Rule</th><th>Group</th><th>Aggregate</th><th class=\"num\">Value</th><th class=\"num\">Threshold</th></tr></thead><tbody>");
+            for alert in alerts {
+                let _ = write!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"num\">{:.2}</td><td class=\"num\">{}</td></tr>",
+                    alert.severity,
+                    escape(&alert.rule),
+                    escape(alert.group.as_deref().unwrap_or("")),
+                    escape(&alert.aggregate),
+                    alert.value,
+                    alert.threshold
+                );
+            }
+            html.push_str("</tbody></table>");
+        }
+        html.push_str("</section>\n");
+    }
+    if let Some(checks) = report.checks {
+        html.push_str("<section><h2>CI checks</h2><table><thead><tr><th>Result</th><th>Check</th><th class=\"num\">Actual</th></tr></thead><tbody>");
+        for check in &checks.checks {
+            let (class, label) = if check.failed { ("error", "FAIL") } else { ("info", "PASS") };
+            let _ = write!(
+                html,
+                "<tr><td class=\"{class}\">{label}</td><td class=\"mono\">{}</td><td class=\"num\">{:.2}</td></tr>",
+                escape(&check.check),
+                check.actual
+            );
+        }
+        html.push_str("</tbody></table></section>\n");
+    }
+    if !report.parse_warnings.is_empty() {
+        let _ = write!(
+            html,
+            "<section><h2>Parse warnings</h2><p class=\"muted\">{} line(s) could not be parsed.</p><table><thead><tr><th class=\"num\">Line</th><th>Error</th><th>Content</th></tr></thead><tbody>",
+            report.parse_warnings.len()
+        );
+        for warning in report.parse_warnings.iter().take(TABLE_ROWS) {
+            let _ = write!(
+                html,
+                "<tr><td class=\"num\">{}</td><td>{}</td><td class=\"mono\">{}</td></tr>",
+                warning.line_number,
+                escape(&warning.error),
+                escape(&warning.line_content)
+            );
+        }
+        html.push_str("</tbody></table></section>\n");
+    }
+
+    let _ = writeln!(
+        html,
+        "</main>\n<footer>Generated by {} · report schema {}</footer>\n<script>{SORT_SCRIPT}</script>\n</body>\n</html>",
+        escape(&report.generator),
+        report.schema_version
+    );
+    html
+}
+
+fn card(html: &mut String, label: &str, value: &str, class: &str) {
+    let _ = write!(
+        html,
+        "<div class=\"card\"><div class=\"label\">{label}</div><div class=\"value {class}\">{}</div></div>",
+        escape(value)
+    );
+}
+
+fn summary_cards(html: &mut String, stats: &LogStats) {
+    let ms = |v: Option<f64>| v.map_or("–".to_string(), |v| format!("{v:.0} ms"));
+    let total = stats.total_requests.max(1) as f64;
+    html.push_str("<section class=\"cards\" style=\"background:none;box-shadow:none;padding:0\">");
+    card(html, "Total requests", &stats.total_requests.to_string(), "");
+    card(
+        html,
+        "Errors",
+        &format!("{} ({:.1}%)", stats.error_count, stats.error_rate()),
+        "error",
+    );
+    card(
+        html,
+        "Warnings",
+        &format!("{} ({:.1}%)", stats.warning_count, stats.warning_count as f64 / total * 100.0),
+        "warning",
+    );
+    card(html, "Info", &stats.info_count.to_string(), "info");
+    let avg = (stats.total_requests > 0).then_some(stats.avg_response_time);
+    card(html, "Avg response", &ms(avg), "");
+    card(html, "P50", &ms(stats.response_time_percentile(50.0)), "");
+    card(html, "P95", &ms(stats.response_time_percentile(95.0)), "warning");
+    card(html, "P99", &ms(stats.response_time_percentile(99.0)), "error");
+    html.push_str("</section>\n");
+}
+
+/// A vertical bar chart. Each bar has a label, a value and an optional
+/// highlighted part drawn over it (e.g. the errors within a time bucket).
+fn bar_chart(bars: &[(String, f64, f64)], height: u32, label_every: usize) -> String {
+    let width = 1000.0;
+    let chart_height = f64::from(height) - 30.0;
+    let max = bars.iter().map(|b| b.1).fold(0.0, f64::max).max(1.0);
+    let slot = width / bars.len().max(1) as f64;
+    let bar_width = (slot * 0.8).max(1.0);
+    let mut svg = format!(
+        "<svg viewBox=\"0 0 {width} {height}\" width=\"100%\" preserveAspectRatio=\"none\" role=\"img\">"
+    );
+    let _ = write!(
+        svg,
+        "<text x=\"2\" y=\"12\">{}</text><line x1=\"0\" y1=\"{chart_height}\" x2=\"{width}\" y2=\"{chart_height}\" stroke=\"#cbd2d9\"/>",
+        format_count(max)
+    );
+    for (i, (label, value, highlighted)) in bars.iter().enumerate() {
+        let x = i as f64 * slot + (slot - bar_width) / 2.0;
+        let h = value / max * (chart_height - 16.0);
+        let _ = write!(
+            svg,
+            "<rect x=\"{x:.1}\" y=\"{:.1}\" width=\"{bar_width:.1}\" height=\"{h:.1}\" fill=\"#74c0fc\"><title>{}: {}</title></rect>",
+            chart_height - h,
+            escape(label),
+            format_count(*value)
+        );
+        if *highlighted > 0.0 {
+            let eh = highlighted / max * (chart_height - 16.0);
+            let _ = write!(
+                svg,
+                "<rect x=\"{x:.1}\" y=\"{:.1}\" width=\"{bar_width:.1}\" height=\"{eh:.1}\" fill=\"#ff8787\"><title>{}: {} errors</title></rect>",
+                chart_height - eh,
+                escape(label),
+                format_count(*highlighted)
+            );
+        }
+        if i % label_every.max(1) == 0 {
+            let _ = write!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                x + bar_width / 2.0,
+                height - 8,
+                escape(label)
+            );
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn format_count(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{value}")
+    } else {
+        format!("{value:.1}")
+    }
+}
+
+fn latency_histogram(html: &mut String, report: &Report) {
+    html.push_str("<section><h2>Latency distribution</h2>");
+    let Some(histogram) = report.latency.first() else {
+        html.push_str("<p class=\"muted\">No response times in the input.</p></section>\n");
+        return;
+    };
+    let bars: Vec<(String, f64, f64)> = histogram
+        .buckets
+        .iter()
+        .map(|bucket| (bucket.label(false), bucket.count as f64, 0.0))
+        .collect();
+    html.push_str(&bar_chart(&bars, 220, 1));
+    let _ = writeln!(
+        html,
+        "<p class=\"muted\">{} requests with a response time. P50 {:.2}ms, P95 {:.2}ms, P99 {:.2}ms.</p></section>",
+        histogram.count, histogram.p50, histogram.p95, histogram.p99
+    );
+}
+
+fn time_series(html: &mut String, entries: &[LogEntry]) {
+    html.push_str("<section><h2>Requests over time</h2>");
+    let Some(series) = TimeSeries::new(entries) else {
+        html.push_str("<p class=\"muted\">No timestamps in the input.</p></section>\n");
+        return;
+    };
+    let label = |start: NaiveDateTime| {
+        if series.bucket_seconds >= 24 * 60 * 60 {
+            start.format("%m-%d").to_string()
+        } else if series.span_seconds >= 24 * 60 * 60 {
+            start.format("%m-%d %H:%M").to_string()
+        } else {
+            start.format("%H:%M").to_string()
+        }
+    };
+    let label_every = series.points.len().div_ceil(10);
+
+    let requests: Vec<(String, f64, f64)> = series
+        .points
+        .iter()
+        .map(|p| (label(p.start), p.requests as f64, p.errors as f64))
+        .collect();
+    let _ = write!(
+        html,
+        "<p class=\"muted\">Requests per {}; errors in red.</p>{}",
+        series.bucket_name,
+        bar_chart(&requests, 220, label_every)
+    );
+
+    let latency: Vec<(String, f64, f64)> = series
+        .points
+        .iter()
+        .map(|p| (label(p.start), p.avg_response_time.unwrap_or(0.0), 0.0))
+        .collect();
+    let _ = writeln!(
+        html,
+        "<h2 style=\"margin-top:16px\">Average response time (ms)</h2>{}</section>",
+        bar_chart(&latency, 180, label_every)
+    );
+}
+
+fn top_endpoints(html: &mut String, stats: &LogStats) {
+    let mut endpoints: Vec<_> = stats.endpoint_frequency.iter().collect();
+    endpoints.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let max = endpoints.first().map_or(1, |e| *e.1).max(1);
+    html.push_str("<section><h2>Top endpoints</h2><table class=\"sortable\"><thead><tr><th class=\"num\">#</th><th>Endpoint</th><th class=\"num\">Requests</th><th class=\"num\">Errors</th><th></th></tr></thead><tbody>");
+    for (i, (endpoint, count)) in endpoints.iter().take(TABLE_ROWS).enumerate() {
+        let errors = stats.errors_by_endpoint.get(*endpoint).copied().unwrap_or(0);
+        let _ = write!(
+            html,
+            "<tr><td class=\"num\">{}</td><td class=\"mono\">{}</td><td class=\"num\">{count}</td><td class=\"num\">{errors}</td><td style=\"width:30%\"><div class=\"bar\" style=\"width:{:.1}%\"></div></td></tr>",
+            i + 1,
+            escape(endpoint),
+            **count as f64 / max as f64 * 100.0
+        );
+    }
+    html.push_str("</tbody></table></section>\n");
+}
+
+fn error_analysis(html: &mut String, stats: &LogStats, entries: &[LogEntry]) {
+    html.push_str("<section><h2>Error analysis</h2>");
+    if stats.errors_by_endpoint.is_empty() {
+        html.push_str("<p class=\"muted\">No errors detected.</p></section>\n");
+        return;
+    }
+    let mut errors: Vec<_> = stats.errors_by_endpoint.iter().collect();
+    errors.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let max = errors.first().map_or(1, |e| *e.1).max(1);
+    html.push_str("<table class=\"sortable\"><thead><tr><th>Endpoint</th><th class=\"num\">Errors</th><th class=\"num\">Error rate</th><th></th></tr></thead><tbody>");
+    for (endpoint, count) in errors.iter().take(TABLE_ROWS) {
+        let total = stats.endpoint_frequency.get(*endpoint).copied().unwrap_or(0).max(1);
+        let rate = **count as f64 / total as f64 * 100.0;
+        let _ = write!(
+            html,
+            "<tr><td class=\"mono\">{}</td><td class=\"num\">{count}</td><td class=\"num\" data-sort=\"{rate}\">{rate:.1}%</td><td style=\"width:30%\"><div class=\"bar error\" style=\"width:{:.1}%\"></div></td></tr>",
+            escape(endpoint),
+            **count as f64 / max as f64 * 100.0
+        );
+    }
+    html.push_str("</tbody></table>");
+
+    let mut messages: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.level == Some(LogLevel::Error)) {
+        if let Some(template) = entry.message_template() {
+            *messages.entry(template).or_default() += 1;
+        }
+    }
+    if !messages.is_empty() {
+        let mut messages: Vec<_> = messages.into_iter().collect();
+        messages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        html.push_str("<h2 style=\"margin-top:16px\">Most common error messages</h2><table class=\"sortable\"><thead><tr><th>Message</th><th class=\"num\">Count</th></tr></thead><tbody>");
+        for (message, count) in messages.iter().take(TABLE_ROWS) {
+            let _ = write!(
+                html,
+                "<tr><td class=\"mono\">{}</td><td class=\"num\">{count}</td></tr>",
+                escape(message)
+            );
+        }
+        html.push_str("</tbody></table>");
+    }
+    html.push_str("</section>\n");
+}
+
+fn slowest_requests(html: &mut String, entries: &[LogEntry]) {
+    html.push_str("<section><h2>Slowest requests</h2><table class=\"sortable\"><thead><tr><th class=\"num\">Line</th><th>Timestamp</th><th>Level</th><th>Method</th><th>Endpoint</th><th class=\"num\">Status</th><th class=\"num\">Time (ms)</th><th>Message</th></tr></thead><tbody>");
+    for entry in slowest_first(entries).into_iter().take(TABLE_ROWS) {
+        let level = entry.level.as_ref().map(|l| l.to_string()).unwrap_or_default();
+        let _ = write!(
+            html,
+            "<tr><td class=\"num\">{}</td><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td class=\"mono\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td>{}</td></tr>",
+            entry.line_number,
+            escape(entry.timestamp.as_deref().unwrap_or("")),
+            level.to_lowercase(),
+            level,
+            entry.method.as_ref().map(|m| m.to_string()).unwrap_or_default(),
+            escape(entry.endpoint.as_deref().unwrap_or("")),
+            entry.status_code.map(|s| s.to_string()).unwrap_or_default(),
+            entry.response_time.map(format_count).unwrap_or_default(),
+            escape(entry.message.as_deref().unwrap_or(""))
+        );
+    }
+    html.push_str("</tbody></table></section>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_analyzer::ParseWarning;
+    use crate::report::{AppliedFilters, InputMetadata};
+    use std::path::Path;
+
+    const HOSTILE: &str = r#"<script>alert("x")</script>&amp;"#;
+    const ESCAPED: &str = "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt;&amp;amp;";
+
+    fn render_entries(entries: &[LogEntry], warnings: &[ParseWarning]) -> String {
+        let stats = LogStats::from_entries(entries);
+        let input = InputMetadata::new(Path::new("server.log"), entries.len(), entries, AppliedFilters::default());
+        render(&Report::new(input, &stats, entries, warnings), entries)
+    }
+
+    #[test]
+    fn escapes_markup_and_quotes() {
+        assert_eq!(escape(HOSTILE), ESCAPED);
+        assert_eq!(escape("it's"), "it&#39;s");
+        assert_eq!(escape("/api/users"), "/api/users");
+    }
+
+    #[test]
+    fn log_content_is_escaped() {
+        let line = serde_json::json!({
+            "timestamp": "2024-01-15T08:15:01",
+            "level": "ERROR",
+            "method": "GET",
+            "path": format!("/api/{HOSTILE}"),
+            "status": 500,
+            "response_time": 12,
+            "message": HOSTILE,
+        });
+        let entries = vec![LogEntry::parse_log(&line.to_string()).unwrap()];
+        let warnings = [ParseWarning {
+            line_number: 2,
+            line_content: HOSTILE.to_string(),
+            error: HOSTILE.to_string(),
+        }];
+        let html = render_entries(&entries, &warnings);
+        assert!(!html.contains("<script>alert"), "{html}");
+        assert!(html.contains(&format!("/api/{ESCAPED}")));
+        // Endpoint cells of three tables, the slowest request's message and
+        // both columns of the parse warning.
+        assert!(html.matches(ESCAPED).count() >= 6, "{html}");
+    }
+
+    #[test]
+    fn empty_input_renders_placeholders() {
+        let html = render_entries(&[], &[]);
+        assert!(html.contains("No response times in the input."));
+        assert!(html.contains("No timestamps in the input."));
+        assert!(html.contains("No errors detected."));
+        assert!(html.ends_with("</html>\n"), "{html}");
+    }
+}
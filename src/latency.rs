@@ -0,0 +1,179 @@
+//! Log-scaled latency histograms.
+//!
+//! Bucket bounds follow a 1-2-5 ladder (1ms, 2ms, 5ms, 10ms, …) so every
+//! decade gets the same number of buckets, and only the buckets between the
+//! fastest and the slowest response are kept. A bucket holds the responses
+//! above its lower bound and up to and including its upper bound.
+
+use crate::log_analyzer::{LogEntry, percentile};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Steps of the bucket ladder within one decade.
+const LADDER: [f64; 3] = [1.0, 2.0, 5.0];
+/// Upper bound of the last bucket; slower responses go to an open bucket.
+const MAX_BOUND_MS: f64 = 1_000_000.0;
+
+/// One bucket of a [`LatencyHistogram`].
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct HistogramBucket {
+    /// Exclusive lower bound in milliseconds.
+    pub lower_ms: f64,
+    /// Inclusive upper bound in milliseconds; none for the open last bucket.
+    pub upper_ms: Option<f64>,
+    pub count: usize,
+    /// Share of responses in this or a faster bucket, 0 to 100.
+    pub cumulative_percent: f64,
+}
+
+/// Response-time distribution of all entries or of one endpoint.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LatencyHistogram {
+    /// The endpoint, or none for all entries.
+    pub endpoint: Option<String>,
+    /// Entries with a response time.
+    pub count: usize,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub buckets: Vec<HistogramBucket>,
+}
+
+fn bounds(max: f64) -> Vec<f64> {
+    let mut bounds = Vec::new();
+    let mut decade = 1.0;
+    while decade <= MAX_BOUND_MS {
+        for step in LADDER {
+            let bound = step * decade;
+            if bound > MAX_BOUND_MS {
+                break;
+            }
+            bounds.push(bound);
+            if bound >= max {
+                return bounds;
+            }
+        }
+        decade *= 10.0;
+    }
+    bounds
+}
+
+impl LatencyHistogram {
+    /// Histogram of `times` in milliseconds; `None` when there are none.
+    pub fn new(endpoint: Option<String>, times: &[f64]) -> Option<Self> {
+        let fastest = times.iter().copied().reduce(f64::min)?;
+        let slowest = times.iter().copied().reduce(f64::max)?;
+        let bounds = bounds(slowest);
+        let mut counts = vec![0usize; bounds.len() + 1];
+        for t in times {
+            counts[bounds.iter().position(|b| t <= b).unwrap_or(bounds.len())] += 1;
+        }
+        let first = bounds.iter().position(|b| fastest <= *b).unwrap_or(bounds.len());
+        let last = bounds.iter().position(|b| slowest <= *b).unwrap_or(bounds.len());
+        let mut cumulative = 0;
+        let buckets = (first..=last)
+            .map(|i| {
+                cumulative += counts[i];
+                HistogramBucket {
+                    lower_ms: if i == 0 { 0.0 } else { bounds[i - 1] },
+                    upper_ms: bounds.get(i).copied(),
+                    count: counts[i],
+                    cumulative_percent: cumulative as f64 / times.len() as f64 * 100.0,
+                }
+            })
+            .collect();
+        Some(Self {
+            endpoint,
+            count: times.len(),
+            p50: percentile(times, 50.0)?,
+            p95: percentile(times, 95.0)?,
+            p99: percentile(times, 99.0)?,
+            buckets,
+        })
+    }
+
+    /// Index of the bucket holding `ms`.
+    pub fn bucket_of(&self, ms: f64) -> Option<usize> {
+        self.buckets.iter().position(|b| b.upper_ms.is_none_or(|upper| ms <= upper))
+    }
+}
+
+/// Histogram over all `entries`, followed by one for each of `endpoints`
+/// that has response times.
+pub fn histograms(entries: &[LogEntry], endpoints: &[String]) -> Vec<LatencyHistogram> {
+    let times = |endpoint: Option<&str>| -> Vec<f64> {
+        entries
+            .iter()
+            .filter(|e| endpoint.is_none_or(|endpoint| e.endpoint.as_deref() == Some(endpoint)))
+            .filter_map(|e| e.response_time)
+            .collect()
+    };
+    let overall = LatencyHistogram::new(None, &times(None));
+    let per_endpoint = endpoints
+        .iter()
+        .filter_map(|endpoint| LatencyHistogram::new(Some(endpoint.clone()), &times(Some(endpoint))));
+    overall.into_iter().chain(per_endpoint).collect()
+}
+
+/// `5ms`, `2.5s`: a bucket bound for labels.
+pub fn format_bound(ms: f64) -> String {
+    if ms >= 1000.0 { format!("{}s", ms / 1000.0) } else { format!("{ms}ms") }
+}
+
+impl HistogramBucket {
+    /// `≤5ms` (`<=5ms` in ASCII), or `>10s` for the open bucket.
+    pub fn label(&self, ascii: bool) -> String {
+        match self.upper_ms {
+            Some(upper) => format!("{}{}", if ascii { "<=" } else { "≤" }, format_bound(upper)),
+            None => format!(">{}", format_bound(self.lower_ms)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_climb_the_ladder_up_to_the_slowest_response() {
+        assert_eq!(bounds(0.5), vec![1.0]);
+        assert_eq!(bounds(5.0), vec![1.0, 2.0, 5.0]);
+        assert_eq!(bounds(7.0), vec![1.0, 2.0, 5.0, 10.0]);
+        let all = bounds(f64::INFINITY);
+        assert_eq!(all.len(), 19);
+        assert_eq!(all.last(), Some(&MAX_BOUND_MS));
+        assert_eq!(bounds(MAX_BOUND_MS), all);
+    }
+
+    #[test]
+    fn upper_bounds_are_inclusive() {
+        let histogram = LatencyHistogram::new(None, &[2.0, 5.0, 5.1]).unwrap();
+        let shape: Vec<_> = histogram.buckets.iter().map(|b| (b.lower_ms, b.upper_ms, b.count)).collect();
+        assert_eq!(shape, vec![(1.0, Some(2.0), 1), (2.0, Some(5.0), 1), (5.0, Some(10.0), 1)]);
+        assert_eq!(histogram.buckets[2].cumulative_percent, 100.0);
+        assert_eq!(histogram.buckets[0].label(false), "≤2ms");
+        assert_eq!(histogram.buckets[0].label(true), "<=2ms");
+    }
+
+    #[test]
+    fn responses_above_the_last_bound_go_to_an_open_bucket() {
+        let histogram = LatencyHistogram::new(None, &[10.0, MAX_BOUND_MS, 2.0 * MAX_BOUND_MS]).unwrap();
+        assert_eq!(histogram.buckets[0].upper_ms, Some(10.0));
+        let [.., closed, open] = histogram.buckets.as_slice() else { panic!("too few buckets") };
+        assert_eq!((closed.upper_ms, closed.count), (Some(MAX_BOUND_MS), 1));
+        assert_eq!((open.lower_ms, open.upper_ms, open.count), (MAX_BOUND_MS, None, 1));
+        assert_eq!(open.label(false), ">1000s");
+        assert_eq!(histogram.bucket_of(f64::MAX), Some(histogram.buckets.len() - 1));
+    }
+
+    #[test]
+    fn percentile_markers_land_in_their_bucket() {
+        let histogram = LatencyHistogram::new(None, &[2.0, 5.0, 5.1]).unwrap();
+        assert_eq!((histogram.p50, histogram.p95, histogram.p99), (5.0, 5.1, 5.1));
+        assert_eq!(histogram.bucket_of(histogram.p50), Some(1));
+        assert_eq!(histogram.bucket_of(histogram.p95), Some(2));
+        assert_eq!(histogram.bucket_of(2.0), Some(0));
+        assert_eq!(histogram.bucket_of(11.0), None);
+        assert!(LatencyHistogram::new(None, &[]).is_none());
+    }
+}
@@ -1 +1,26 @@
-pub mod log_analyzer;
\ No newline at end of file
+pub mod log_analyzer;
+pub mod security;
+pub mod rules;
+pub mod gate;
+pub mod query;
+pub mod filter;
+pub mod time_range;
+pub mod sql;
+pub mod aggregate;
+pub mod search;
+pub mod show;
+pub mod report;
+pub mod html;
+pub mod markdown;
+pub mod export;
+pub mod columnar;
+pub mod sqlite;
+pub mod metrics;
+pub mod tui;
+pub mod text_report;
+pub mod template;
+pub mod latency;
+pub mod heatmap;
+pub mod convert;
+pub mod logfmt;
+pub mod syslog;
@@ -1,14 +1,20 @@
-use anyhow::Context;
-use chrono::{NaiveDate, NaiveDateTime};
-use colored::*;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use lazy_static::lazy_static;
 use regex::Regex;
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
 use std::{
-    collections::HashMap, fmt::{self, Display, Formatter}, fs::File, io::{BufRead, BufReader}, net::IpAddr, path::PathBuf, str::FromStr
+    collections::{BTreeMap, HashMap}, fmt::{self, Display, Formatter}, fs::File, io::{BufRead, BufReader, Seek, SeekFrom}, net::IpAddr, path::{Path, PathBuf}, str::FromStr
 };
 use thiserror::Error;
 
+use crate::filter::Filter;
+use crate::logfmt::{self, LogfmtKeys};
+use crate::syslog;
+use crate::query::QueryError;
+use crate::text_report::{self, TextLayout};
+use crate::time_range::{self, TimeWindow};
+
 lazy_static! {
   static ref TIMESTAMP_PATTERN: Regex = Regex::new(
       r"(?:\[(\d{2}/[A-Za-z]{3}/\d{4}:\d{2}:\d{2}:\d{2} [+\-]\d{4})\])|(\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}(?:\.\d{3})?Z?)"
@@ -21,7 +27,10 @@ lazy_static! {
   static ref STATUS_PATTERN: Regex = Regex::new(r"\s+(\d{3})\s+").unwrap();
   static ref RESPONSE_TIME_PATTERN: Regex = Regex::new(r"(\d+(?:\.\d+)?)\s*(?:ms|s)").unwrap();
   static ref MESSAGE_PATTERN: Regex = Regex::new(r"\d+(?:\.\d+)?\s*(?:ms|s)\s+(.+)$").unwrap();
-  static ref DATE_EXTRACT_PATTERN: Regex = Regex::new(r"(\d{4}-\d{2}-\d{2})").unwrap();
+  static ref DEFAULT_PARSE_OPTIONS: ParseOptions = ParseOptions::default();
+  static ref TEMPLATE_VARIABLE_PATTERN: Regex = Regex::new(
+      r#"(?i)"[^"]*"|'[^']*'|\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b|\b0x[0-9a-f]+\b|\b[0-9a-f]*\d[0-9a-f]*\b|\b\d+(?:\.\d+)*[a-z]*\b"#
+  ).unwrap();
 }
 
 #[derive(Error, Debug)]
@@ -46,9 +55,176 @@ pub enum AnalyzerError {
 
     #[error("No log entries found in file")]
     EmptyLogFile,
+
+    #[error("No timestamp, log level or status code found")]
+    UnrecognizedLine,
+
+    #[error("Invalid duration: {0}")]
+    DurationParseError(String),
+
+    #[error("Failed to parse rules file: {0}")]
+    RulesFileError(#[from] toml::de::Error),
+
+    #[error("Rules file defines no [[rule]] tables")]
+    NoRules,
+
+    #[error("Invalid rule '{rule}': {message}")]
+    RuleError { rule: String, message: String },
+
+    #[error("Invalid check: {0}")]
+    CheckParseError(String),
+
+    #[error("No data for metric {0} in this input")]
+    NoMetricData(String),
+
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(String),
+
+    #[error("Invalid query: {0}")]
+    QueryError(#[from] QueryError),
+
+    #[error("Invalid aggregation: {0}")]
+    AggregationError(String),
+
+    #[error("Invalid search index: {0}")]
+    IndexError(String),
+
+    #[error("Invalid output options: {0}")]
+    OutputError(String),
+
+    #[error("Export failed: {0}")]
+    ExportError(String),
+
+    #[error("Template error: {0}")]
+    TemplateError(String),
+
+    #[error("Invalid JSON: {0}")]
+    JsonLineError(String),
+
+    #[error("Invalid logfmt: {0}")]
+    LogfmtError(String),
+
+    #[error("Invalid syslog line: {0}")]
+    SyslogError(String),
+}
+
+/// Nearest-rank percentile (`pct` in 0..=100), matching the indexing used by
+/// the text report.
+pub fn percentile(values: &[f64], pct: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let idx = ((sorted.len() as f64 * pct / 100.0) as usize).min(sorted.len() - 1);
+    Some(sorted[idx])
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+/// The entries with a response time, slowest first; ties keep their order.
+pub fn slowest_first(entries: &[LogEntry]) -> Vec<&LogEntry> {
+    slowest_of(entries.iter())
+}
+
+fn slowest_of<'e>(entries: impl Iterator<Item = &'e LogEntry>) -> Vec<&'e LogEntry> {
+    let mut slowest: Vec<&LogEntry> = entries.filter(|e| e.response_time.is_some()).collect();
+    slowest.sort_by(|a, b| b.response_time.partial_cmp(&a.response_time).unwrap_or(std::cmp::Ordering::Equal));
+    slowest
+}
+
+/// Requests, errors and response times of one endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EndpointStats {
+    pub endpoint: String,
+    pub requests: usize,
+    pub errors: usize,
+    /// Share of requests logged at `ERROR`, 0 to 100.
+    pub error_rate: f64,
+    /// None when no request has a response time, as for the percentiles.
+    pub avg_response_time: Option<f64>,
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    pub p99: Option<f64>,
+}
+
+/// Statistics of every endpoint in `entries`, ordered by endpoint.
+pub fn endpoint_stats(entries: &[LogEntry]) -> Vec<EndpointStats> {
+    let mut by_endpoint: BTreeMap<&str, (usize, usize, Vec<f64>)> = BTreeMap::new();
+    for entry in entries {
+        let Some(endpoint) = &entry.endpoint else { continue };
+        let (requests, errors, times) = by_endpoint.entry(endpoint).or_default();
+        *requests += 1;
+        if entry.level == Some(LogLevel::Error) {
+            *errors += 1;
+        }
+        times.extend(entry.response_time);
+    }
+    by_endpoint
+        .into_iter()
+        .map(|(endpoint, (requests, errors, times))| EndpointStats {
+            endpoint: endpoint.to_string(),
+            requests,
+            errors,
+            error_rate: errors as f64 / requests as f64 * 100.0,
+            avg_response_time: (!times.is_empty()).then(|| times.iter().sum::<f64>() / times.len() as f64),
+            p50: percentile(&times, 50.0),
+            p95: percentile(&times, 95.0),
+            p99: percentile(&times, 99.0),
+        })
+        .collect()
+}
+
+/// Parse a compact duration such as `500ms`, `30s`, `5m`, `2h` or `1d`.
+pub fn parse_duration(s: &str) -> Result<Duration, AnalyzerError> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| AnalyzerError::DurationParseError(s.to_string()))?;
+    let (amount, unit) = s.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| AnalyzerError::DurationParseError(s.to_string()))?;
+    let duration = match unit.trim() {
+        "ms" => Duration::try_milliseconds(amount),
+        "s" | "sec" | "secs" => Duration::try_seconds(amount),
+        "m" | "min" | "mins" => Duration::try_minutes(amount),
+        "h" | "hour" | "hours" => Duration::try_hours(amount),
+        "d" | "day" | "days" => Duration::try_days(amount),
+        _ => None,
+    };
+    duration.ok_or_else(|| AnalyzerError::DurationParseError(s.to_string()))
+}
+
+/// Milliseconds in a duration with units such as `45ms`, `1.5s`, `250µs` or
+/// Go's `1m30.5s`.
+pub fn duration_millis(s: &str) -> Option<f64> {
+    let mut rest = s.trim();
+    let mut total = 0.0;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let (amount, tail) = rest.split_at(split);
+        let amount: f64 = amount.parse().ok()?;
+        let unit_len = tail.find(|c: char| c.is_ascii_digit()).unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        total += amount
+            * match unit {
+                "ns" => 1e-6,
+                "us" | "µs" | "μs" => 1e-3,
+                "ms" => 1.0,
+                "s" => 1e3,
+                "m" => 60e3,
+                "h" => 3600e3,
+                _ => return None,
+            };
+        rest = tail;
+    }
+    Some(total)
+}
+
+/// Log severity, ordered from least to most severe.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum LogLevel {
     Info,
     Warning,
@@ -66,6 +242,19 @@ impl Display for LogLevel {
     }
 }
 
+impl LogLevel {
+    /// Case-insensitive parse that also accepts common aliases (`warn`,
+    /// `fatal`, `critical`) found in structured logs.
+    pub fn parse_lenient(s: &str) -> Option<Self> {
+        match s.trim().to_uppercase().as_str() {
+            "INFO" | "NOTICE" => Some(LogLevel::Info),
+            "WARN" | "WARNING" => Some(LogLevel::Warning),
+            "ERROR" | "ERR" | "FATAL" | "CRITICAL" | "CRIT" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
 impl FromStr for LogLevel {
     type Err = AnalyzerError;
 
@@ -79,7 +268,7 @@ impl FromStr for LogLevel {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum LogMethod {
     Get,
     Post,
@@ -125,7 +314,8 @@ impl Display for ParseWarning {
         )
     }
 }
-#[derive(Debug, Clone)]
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 pub struct ParseWarning {
     pub line_number: usize,
     pub line_content: String,
@@ -148,7 +338,7 @@ impl ParseResult {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct LogEntry {
     pub timestamp: Option<String>,
     pub level: Option<LogLevel>,
@@ -158,11 +348,42 @@ pub struct LogEntry {
     pub status_code: Option<u16>,
     pub response_time: Option<f64>,
     pub message: Option<String>,
+    pub line_number: usize,
+    #[serde(skip)]
+    pub raw: String,
+    /// Fields of structured (JSON) lines that don't map onto a typed field.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, serde_json::Value>,
 }
 
 impl LogEntry {
     pub fn parse_log(log_line: &str) -> Result<Self, AnalyzerError> {
-        Ok(Self {
+        Self::parse_log_with(log_line, &DEFAULT_PARSE_OPTIONS)
+    }
+
+    /// Parse a JSON, syslog, logfmt or plain text line.
+    pub fn parse_log_with(log_line: &str, options: &ParseOptions) -> Result<Self, AnalyzerError> {
+        if log_line.trim_start().starts_with('{') {
+            let fields = serde_json::from_str(log_line).map_err(|e| AnalyzerError::JsonLineError(e.to_string()))?;
+            return Ok(Self::from_json_fields(log_line, fields));
+        }
+        if syslog::looks_like_syslog(log_line) {
+            let reference = options.syslog_reference.unwrap_or_else(|| Utc::now().naive_utc());
+            return syslog::parse(log_line, reference).map_err(AnalyzerError::SyslogError);
+        }
+        if logfmt::looks_like_logfmt(log_line) {
+            let (fields, bare) = logfmt::fields(log_line, &options.logfmt_keys).map_err(AnalyzerError::LogfmtError)?;
+            let mut entry = Self::from_json_fields(log_line, fields);
+            for key in bare {
+                if let Some(value) = entry.extra.get_mut(&key)
+                    && let serde_json::Value::String(s) = value
+                {
+                    *value = logfmt::typed(s);
+                }
+            }
+            return Ok(entry);
+        }
+        let entry = Self {
             timestamp: TIMESTAMP_PATTERN
                 .find(log_line)
                 .map(|m| m.as_str().to_string()),
@@ -193,22 +414,147 @@ impl LogEntry {
             message: MESSAGE_PATTERN
                 .captures(log_line)
                 .and_then(|c| c.get(1).map(|m| m.as_str().to_string())),
-        })
+            line_number: 0,
+            raw: log_line.to_string(),
+            extra: BTreeMap::new(),
+        };
+        if entry.timestamp.is_none() && entry.level.is_none() && entry.status_code.is_none() {
+            return Err(AnalyzerError::UnrecognizedLine);
+        }
+        Ok(entry)
     }
 
-    // Extract date from timestamp for filtering
-    fn extract_date(&self) -> Option<NaiveDate> {
-        self.timestamp.as_ref().and_then(|ts| {
-            DATE_EXTRACT_PATTERN
-                .find(ts)
-                .and_then(|m| NaiveDate::parse_from_str(m.as_str(), "%Y-%m-%d").ok())
-        })
+    /// Build an entry from a JSON log line. Well-known keys (`timestamp`,
+    /// `level`, `path`, `status`, ...) fill the typed fields; everything else,
+    /// and any known key whose value doesn't fit its field, goes to `extra`.
+    fn from_json_fields(log_line: &str, mut fields: serde_json::Map<String, serde_json::Value>) -> Self {
+        fn take<T>(
+            fields: &mut serde_json::Map<String, serde_json::Value>,
+            keys: &[&str],
+            convert: impl Fn(&serde_json::Value) -> Option<T>,
+        ) -> Option<T> {
+            let key = keys.iter().find(|k| fields.get(**k).and_then(&convert).is_some())?;
+            fields.remove(*key).as_ref().and_then(convert)
+        }
+        let text = |v: &serde_json::Value| v.as_str().map(str::to_string);
+        let number = |v: &serde_json::Value| v.as_f64().or_else(|| v.as_str()?.parse().ok());
+
+        Self {
+            timestamp: take(&mut fields, &["timestamp", "time", "ts", "@timestamp"], text),
+            level: take(&mut fields, &["level", "severity", "lvl"], |v| {
+                LogLevel::parse_lenient(v.as_str()?)
+            }),
+            ip_address: take(&mut fields, &["ip", "ip_address", "remote_addr", "client_ip"], |v| {
+                v.as_str()?.parse().ok()
+            }),
+            method: take(&mut fields, &["method"], |v| v.as_str()?.to_uppercase().parse().ok()),
+            endpoint: take(&mut fields, &["endpoint", "path", "url"], text),
+            status_code: take(&mut fields, &["status", "status_code", "statusCode"], |v| {
+                let n: u64 = v.as_u64().or_else(|| v.as_str()?.parse().ok())?;
+                u16::try_from(n).ok()
+            }),
+            response_time: take(
+                &mut fields,
+                &["response_time", "responseTime", "duration_ms", "latency_ms"],
+                |v| number(v).or_else(|| duration_millis(v.as_str()?)).filter(|t| t.is_finite() && *t >= 0.0),
+            ),
+            message: take(&mut fields, &["message", "msg"], text),
+            line_number: 0,
+            raw: log_line.to_string(),
+            extra: fields.into_iter().collect(),
+        }
+    }
+
+    /// Look up an extra field by key or dotted path (`error.code`).
+    pub fn extra_value(&self, path: &str) -> Option<&serde_json::Value> {
+        if let Some(value) = self.extra.get(path) {
+            return Some(value);
+        }
+        let mut parts = path.split('.');
+        let mut value = self.extra.get(parts.next()?)?;
+        for part in parts {
+            value = match value {
+                serde_json::Value::Object(map) => map.get(part)?,
+                serde_json::Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Look up a field by name as a string, for grouping and display.
+    ///
+    /// Accepts `timestamp`, `level`, `ip`, `method`, `endpoint`, `status`,
+    /// `response_time` and `message`; any other name is looked up in `extra`.
+    pub fn field_value(&self, field: &str) -> Option<String> {
+        match field {
+            "timestamp" => self.timestamp.clone(),
+            "level" => self.level.as_ref().map(|l| l.to_string()),
+            "ip" | "ip_address" => self.ip_address.map(|ip| ip.to_string()),
+            "method" => self.method.as_ref().map(|m| m.to_string()),
+            "endpoint" | "path" => self.endpoint.clone(),
+            "status" | "status_code" => self.status_code.map(|s| s.to_string()),
+            "response_time" => self.response_time.map(|t| t.to_string()),
+            "message" => self.message.clone(),
+            _ => self.extra_value(field).map(|v| match v {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            }),
+        }
+    }
+
+    /// The message with variable parts (numbers, ids, quoted values) replaced
+    /// by `<*>`, so messages emitted by the same log statement compare equal.
+    pub fn message_template(&self) -> Option<String> {
+        self.message
+            .as_deref()
+            .map(|m| TEMPLATE_VARIABLE_PATTERN.replace_all(m, "<*>").into_owned())
+    }
+
+    /// Parse the raw timestamp string into a typed datetime.
+    ///
+    /// Handles `2024-01-15 08:15:23.145` style and RFC 3339 timestamps as well
+    /// as the bracketed Common Log Format (`10/Oct/2000:13:55:36 -0700`);
+    /// timestamps with an offset are normalised to UTC.
+    pub fn parsed_timestamp(&self) -> Option<NaiveDateTime> {
+        let ts = self.timestamp.as_deref()?.trim_matches(&['[', ']'][..]);
+        if let Ok(dt) = DateTime::parse_from_str(ts, "%d/%b/%Y:%H:%M:%S %z") {
+            return Some(dt.naive_utc());
+        }
+        time_range::parse_datetime(ts)
+    }
+}
+
+/// Settings for parsing lines beyond format detection.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// How keys of logfmt lines map onto fields.
+    pub logfmt_keys: LogfmtKeys,
+    /// Reference time for the missing year of RFC 3164 syslog timestamps;
+    /// the current time when unset.
+    pub syslog_reference: Option<NaiveDateTime>,
+}
+
+impl ParseOptions {
+    /// These options for reading `file`: syslog years are inferred from its
+    /// modification time unless a reference was set.
+    pub fn for_file(&self, file: &File) -> Self {
+        let mut options = self.clone();
+        if options.syslog_reference.is_none() {
+            options.syslog_reference = file
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .map(|t| DateTime::<Utc>::from(t).naive_utc());
+        }
+        options
     }
 }
 
 #[derive(Debug)]
 pub struct Logs {
     pub entries: Vec<LogEntry>,
+    pub options: ParseOptions,
 }
 impl Default for Logs {
     fn default() -> Self {
@@ -219,12 +565,170 @@ impl Logs {
     fn new() -> Self {
         Self {
             entries: Vec::new(),
+            options: ParseOptions::default(),
         }
     }
 
     pub fn read_and_parse_log(&mut self, file_path: PathBuf) -> Result<ParseResult, AnalyzerError> {
         let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
+        self.default_syslog_reference(&file);
+        let result = self.parse_lines(BufReader::new(file), None)?;
+        if result.entries_parsed == 0 {
+            return Err(nothing_parsed(result.warnings.first().map(|w| (w.line_number, w.error.clone()))));
+        }
+        Ok(result)
+    }
+
+    /// Read only the entries inside `window` from a file sorted by time.
+    ///
+    /// Instead of parsing everything, this binary-searches the file for the
+    /// start of the window and stops reading at its end. Relative bounds are
+    /// anchored to the last timestamp in the file. Line numbers count from
+    /// the start of the window, since numbering them from the start of the
+    /// file would mean reading everything before it. Like filtering a full
+    /// read, an empty window is no error; a file without entries is.
+    pub fn read_and_parse_log_window(
+        &mut self,
+        file_path: PathBuf,
+        window: &TimeWindow,
+    ) -> Result<ParseResult, AnalyzerError> {
+        let mut file = File::open(file_path)?;
+        self.default_syslog_reference(&file);
+        let anchor = if window.needs_anchor() {
+            time_range::last_timestamp_in_file(&mut file, &self.options)?
+        } else {
+            None
+        };
+        let range = window.resolve(anchor)?;
+        let offset = match range.start {
+            Some(start) => time_range::seek_to_time(&mut file, start, &self.options)?,
+            None => 0,
+        };
+        file.seek(SeekFrom::Start(offset))?;
+
+        let initial_count = self.entries.len();
+        let result = self.parse_lines(BufReader::new(&mut file), range.end)?;
+        let mut kept = self.entries.split_off(initial_count);
+        let any_parsed = !kept.is_empty();
+        kept.retain(|e| range.matches(e));
+        self.entries.extend(kept);
+        if !any_parsed {
+            // Nothing was read from the offset on; fail only if a full read
+            // would, so an untimed file gives an empty window too.
+            file.seek(SeekFrom::Start(0))?;
+            let mut probe = EntryStream::new(BufReader::new(&mut file), &self.options);
+            if probe.find_map(Result::ok).is_none() {
+                return Err(nothing_parsed(probe.first_failure));
+            }
+        }
+        Ok(ParseResult {
+            entries_parsed: self.entries.len() - initial_count,
+            ..result
+        })
+    }
+
+    /// Stream the entries of `file_path` inside `window` without keeping
+    /// them, as [`Logs::read_and_parse_log`] or, when `sorted`,
+    /// [`Logs::read_and_parse_log_window`] would read them. Each call reads
+    /// the file again, so callers can make several passes. Lines that do not
+    /// parse are skipped.
+    pub fn stream(
+        &self,
+        file_path: &Path,
+        window: &TimeWindow,
+        sorted: bool,
+    ) -> Result<impl Iterator<Item = Result<LogEntry, AnalyzerError>> + use<>, AnalyzerError> {
+        let mut file = File::open(file_path)?;
+        let options = self.options.for_file(&file);
+        let mut probe = EntryStream::new(BufReader::new(&mut file), &options);
+        if probe.find_map(Result::ok).is_none() {
+            return Err(nothing_parsed(probe.first_failure));
+        }
+        let (range, offset) = if !window.is_empty() && sorted {
+            let anchor = if window.needs_anchor() {
+                time_range::last_timestamp_in_file(&mut file, &options)?
+            } else {
+                None
+            };
+            let range = window.resolve(anchor)?;
+            let offset = match range.start {
+                Some(start) => time_range::seek_to_time(&mut file, start, &options)?,
+                None => 0,
+            };
+            (Some(range), offset)
+        } else if !window.is_empty() {
+            let anchor = if window.needs_anchor() {
+                file.seek(SeekFrom::Start(0))?;
+                EntryStream::new(BufReader::new(&mut file), &options)
+                    .filter_map(|e| e.ok()?.parsed_timestamp())
+                    .max()
+            } else {
+                None
+            };
+            (Some(window.resolve(anchor)?), 0)
+        } else {
+            (None, 0)
+        };
+        file.seek(SeekFrom::Start(offset))?;
+        let mut entries = EntryStream::new(BufReader::new(file), &options);
+        entries.stop_at = range.as_ref().filter(|_| sorted).and_then(|r| r.end);
+        Ok(entries.filter(move |entry| match (entry, &range) {
+            (Ok(entry), Some(range)) => range.matches(entry),
+            _ => true,
+        }))
+    }
+
+    /// Follow a growing file like `tail -f`: parse every line appended after
+    /// its current end and pass the entries to `on_entry`, checking for new
+    /// data every `interval`. A truncated file is read again from the start.
+    /// Unparseable lines are skipped and line numbers count from where
+    /// following began. Only returns on an I/O error.
+    pub fn follow(
+        &self,
+        file_path: PathBuf,
+        interval: std::time::Duration,
+        mut on_entry: impl FnMut(LogEntry),
+    ) -> Result<(), AnalyzerError> {
+        let mut reader = BufReader::new(File::open(file_path)?);
+        let mut position = reader.seek(SeekFrom::End(0))?;
+        let mut line = String::new();
+        let mut line_number = 0;
+        loop {
+            let read = reader.read_line(&mut line)?;
+            if read == 0 || !line.ends_with('\n') {
+                // Nothing new, or a line still being written.
+                std::thread::sleep(interval);
+                if reader.get_ref().metadata()?.len() < position {
+                    position = reader.seek(SeekFrom::Start(0))?;
+                    line.clear();
+                }
+                continue;
+            }
+            position += line.len() as u64;
+            line_number += 1;
+            if !line.trim().is_empty()
+                && let Ok(mut entry) = LogEntry::parse_log_with(line.trim_end_matches(['\r', '\n']), &self.options)
+            {
+                entry.line_number = line_number;
+                on_entry(entry);
+            }
+            line.clear();
+        }
+    }
+
+    /// Infer syslog years from the file's modification time unless a
+    /// reference was set.
+    fn default_syslog_reference(&mut self, file: &File) {
+        self.options = self.options.for_file(file);
+    }
+
+    /// Parse lines from `reader`, numbering them from 1. When `stop_at` is
+    /// set, reading ends at the first entry at or after it.
+    fn parse_lines<R: BufRead>(
+        &mut self,
+        reader: R,
+        stop_at: Option<NaiveDateTime>,
+    ) -> Result<ParseResult, AnalyzerError> {
         let mut warnings = Vec::new();
         let initial_count = self.entries.len();
         for (line_number, line_result) in reader.lines().enumerate() {
@@ -234,9 +738,17 @@ impl Logs {
                     if line.trim().is_empty() {
                         continue; // Skip empty lines
                     }
-                    let parse_result = LogEntry::parse_log(&line);
+                    let parse_result = LogEntry::parse_log_with(&line, &self.options);
                     match parse_result {
-                        Ok(entry) => self.entries.push(entry),
+                        Ok(mut entry) => {
+                            if let Some(stop_at) = stop_at
+                                && entry.parsed_timestamp().is_some_and(|ts| ts >= stop_at)
+                            {
+                                break;
+                            }
+                            entry.line_number = line_number;
+                            self.entries.push(entry)
+                        }
                         Err(e) => {
                             warnings.push(ParseWarning {
                                 line_number,
@@ -255,69 +767,125 @@ impl Logs {
                 }
             }
         }
-        let entries_parsed = self.entries.len() - initial_count;
-        if entries_parsed == 0 {
-            return Err(AnalyzerError::EmptyLogFile);
-        }
         Ok(ParseResult {
             warnings,
-            entries_parsed,
+            entries_parsed: self.entries.len() - initial_count,
         })
     }
 
-    pub fn filter_by_level(
-        &self,
-        log_level: &str,
-    ) -> Result<impl Iterator<Item = &LogEntry>, AnalyzerError> {
-        Ok(self.entries.iter().filter(|&e| {
-            e.level
-                .as_ref()
-                .map(|level| {
-                    level.to_string() == log_level.parse::<LogLevel>().unwrap().to_string()
-                })
-                .unwrap_or(false)
-        }))
+    /// Iterate over the entries matching `filter`.
+    pub fn filter<'a, F: Filter + ?Sized>(
+        &'a self,
+        filter: &'a F,
+    ) -> impl Iterator<Item = &'a LogEntry> + 'a {
+        self.entries.iter().filter(move |e| filter.matches(e))
     }
 
-    pub fn filter_by_date_range(
-        &self,
-        start: &str,
-        end: &str,
-    ) -> Result<impl Iterator<Item = &LogEntry>, AnalyzerError> {
-        let start = NaiveDate::parse_from_str(start, "%Y-%m-%d")?;
-        let end = NaiveDate::parse_from_str(end, "%Y-%m-%d")?;
-        Ok(self.entries.iter().filter(move |&e| {
-            e.extract_date()
-                .as_ref()
-                .map(|d| d.ge(&start) && d.le(&end))
-                .unwrap_or(false)
-        }))
+    /// Drop every entry that does not match `filter`.
+    pub fn retain<F: Filter + ?Sized>(&mut self, filter: &F) {
+        self.entries.retain(|e| filter.matches(e));
     }
+}
 
-    pub fn filter_by_endpoint(
-        &self,
-        pattern: &str,
-    ) -> Result<impl Iterator<Item = &LogEntry>, AnalyzerError> {
-        let endpoint_pattern = Regex::new(&regex::escape(pattern))?;
-        Ok(self.entries.iter().filter(move |&e| {
-            e.endpoint
-                .as_ref()
-                .map(|endpoint| endpoint_pattern.is_match(endpoint))
-                .unwrap_or(false)
-        }))
+/// The error for a file without entries: a parse error naming the first
+/// line that failed, or [`AnalyzerError::EmptyLogFile`] when every line was
+/// blank.
+fn nothing_parsed(first_failure: Option<(usize, String)>) -> AnalyzerError {
+    match first_failure {
+        Some((line_number, message)) => AnalyzerError::ParseError {
+            line_number,
+            message: format!("{message} (no line of the file could be parsed)"),
+        },
+        None => AnalyzerError::EmptyLogFile,
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Entries parsed one line at a time, numbered from the reader's start.
+/// Lines that do not parse are skipped; reading ends at the first entry at
+/// or after `stop_at`.
+struct EntryStream<R> {
+    lines: std::io::Lines<R>,
+    line_number: usize,
+    options: ParseOptions,
+    stop_at: Option<NaiveDateTime>,
+    /// Line number and error of the first line that did not parse.
+    first_failure: Option<(usize, String)>,
+}
+
+impl<R: BufRead> EntryStream<R> {
+    fn new(reader: R, options: &ParseOptions) -> Self {
+        Self {
+            lines: reader.lines(),
+            line_number: 0,
+            options: options.clone(),
+            stop_at: None,
+            first_failure: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for EntryStream<R> {
+    type Item = Result<LogEntry, AnalyzerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut entry = match LogEntry::parse_log_with(&line, &self.options) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.first_failure.get_or_insert((self.line_number, e.to_string()));
+                    continue;
+                }
+            };
+            if let Some(stop_at) = self.stop_at
+                && entry.parsed_timestamp().is_some_and(|ts| ts >= stop_at)
+            {
+                return None;
+            }
+            entry.line_number = self.line_number;
+            return Some(Ok(entry));
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct LogStats {
     pub total_requests: usize,
     pub error_count: usize,
     pub warning_count: usize,
     pub info_count: usize,
     pub avg_response_time: f64,
+    #[serde(serialize_with = "serialize_sorted")]
     pub endpoint_frequency: HashMap<String, usize>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub errors_by_endpoint: HashMap<String, usize>,
-    pub slowest_requests: Vec<LogEntry>, // top 10 slowest
+    /// The slowest requests, at most `--top` (10 by default).
+    pub slowest_requests: Vec<LogEntry>,
+    /// Every response time, for percentiles.
+    #[serde(skip)]
+    response_times: Vec<f64>,
+}
+
+/// Serialize a `HashMap` with its keys in sorted order, so the JSON output is
+/// the same from run to run.
+fn serialize_sorted<S: Serializer, V: Serialize>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+impl Default for LogStats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LogStats {
@@ -331,21 +899,35 @@ impl LogStats {
             endpoint_frequency: HashMap::new(),
             errors_by_endpoint: HashMap::new(),
             slowest_requests: Vec::new(),
+            response_times: Vec::new(),
         }
     }
 
+    /// Statistics of `entries`, keeping the 10 slowest requests.
     pub fn from_entries(entries: &[LogEntry]) -> Self {
+        Self::from_entries_top(entries, text_report::DEFAULT_TOP)
+    }
+
+    /// Statistics of `entries`, keeping the `top` slowest requests.
+    pub fn from_entries_top(entries: &[LogEntry], top: usize) -> Self {
+        Self::from_refs_top(&entries.iter().collect::<Vec<_>>(), top)
+    }
+
+    /// Statistics of a selection of entries, keeping the 10 slowest requests.
+    pub fn from_refs(entries: &[&LogEntry]) -> Self {
+        Self::from_refs_top(entries, text_report::DEFAULT_TOP)
+    }
+
+    fn from_refs_top(entries: &[&LogEntry], top: usize) -> Self {
         let total_requests = entries.len();
         let mut error_count: usize = 0;
         let mut warning_count: usize = 0;
         let mut info_count: usize = 0;
         let mut sum_response_time: f64 = 0.0;
-        let mut requests = Vec::with_capacity(total_requests);
-        requests.resize(total_requests, LogEntry::default());
-        requests.clone_from_slice(entries);
+        let mut response_times = Vec::new();
         let mut endpoint_frequency = HashMap::new();
         let mut errors_by_endpoint = HashMap::new();
-        for entry in entries {
+        for &entry in entries {
             if let Some(level) = &entry.level {
                 match level {
                     LogLevel::Info =>info_count += 1,
@@ -363,11 +945,15 @@ impl LogStats {
             }
             if let Some(response_time) = entry.response_time {
                 sum_response_time += response_time;
+                response_times.push(response_time);
             }
         }
-        let avg_response_time = sum_response_time / total_requests as f64;
-        requests.sort_by(|a, b| b.response_time.partial_cmp(&a.response_time).unwrap_or(std::cmp::Ordering::Equal));
-        let slowest_requests = requests.to_vec();
+        let avg_response_time = if total_requests == 0 {
+            0.0
+        } else {
+            sum_response_time / total_requests as f64
+        };
+        let slowest_requests = slowest_of(entries.iter().copied()).into_iter().take(top).cloned().collect();
 
         Self {
             total_requests,
@@ -378,212 +964,41 @@ impl LogStats {
             endpoint_frequency,
             errors_by_endpoint,
             slowest_requests,
+            response_times,
         }
 
     }
 
-    /// Print a comprehensive formatted report to stdout
+    /// Print the colored report with the default [`TextLayout`], whose
+    /// sections need no entries beyond the statistics.
     pub fn print_report(&self) {
-        self.print_header();
-        self.print_summary();
-        self.print_performance();
-        self.print_top_endpoints();
-        self.print_error_analysis();
-        self.print_slowest_requests();
-        self.print_footer();
-    }
-
-    /// Export stats to JSON format
-    pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string_pretty(self)
-    }
-
-    fn print_header(&self) {
-        println!("\n{}", "╔═══════════════════════════════════════════════════════════════╗".bright_cyan());
-        println!("{}", "║          LOG ANALYSIS REPORT                                  ║".bright_cyan().bold());
-        println!("{}", "╚═══════════════════════════════════════════════════════════════╝".bright_cyan());
-    }
-
-    fn print_footer(&self) {
-        println!("{}", "═".repeat(65).bright_cyan());
-        println!();
-    }
-
-    fn print_summary(&self) {
-        println!("\n{}", "📊 SUMMARY STATISTICS".bold().bright_white());
-        println!("{}", "─".repeat(65).bright_black());
-        
-        println!("{:<30} {:>10}", "Total Requests:", format!("{}", self.total_requests).bright_white().bold());
-        
-        // Status breakdown with percentages and color coding
-        let info_pct = (self.info_count as f64 / self.total_requests as f64) * 100.0;
-        let warning_pct = (self.warning_count as f64 / self.total_requests as f64) * 100.0;
-        let error_pct = (self.error_count as f64 / self.total_requests as f64) * 100.0;
-        
-        println!("\n{}", "Status Breakdown:".bright_white());
-        println!("  {:<26} {:>8}  {:>6}", 
-            "INFO".green(), 
-            format!("{}", self.info_count).green(),
-            format!("({:.1}%)", info_pct).bright_black()
-        );
-        println!("  {:<26} {:>8}  {:>6}", 
-            "WARNING".yellow(), 
-            format!("{}", self.warning_count).yellow(),
-            format!("({:.1}%)", warning_pct).bright_black()
-        );
-        println!("  {:<26} {:>8}  {:>6}", 
-            "ERROR".red(), 
-            format!("{}", self.error_count).red().bold(),
-            format!("({:.1}%)", error_pct).bright_black()
-        );
-        
-        // Error rate indicator
-        if error_pct > 5.0 {
-            println!("\n  {} {}", "⚠".yellow(), format!("High error rate detected: {:.1}%", error_pct).yellow().bold());
-        } else if error_pct > 1.0 {
-            println!("\n  {} {}", "ℹ".bright_blue(), format!("Moderate error rate: {:.1}%", error_pct).bright_blue());
-        }
+        print!("{}", text_report::render(self, &[], &TextLayout::default()));
     }
 
-    fn print_performance(&self) {
-        println!("\n{}", "⚡ PERFORMANCE METRICS".bold().bright_white());
-        println!("{}", "─".repeat(65).bright_black());
-        
-        println!("{:<30} {:>10}", 
-            "Average Response Time:", 
-            format!("{:.2}ms", self.avg_response_time).bright_cyan()
-        );
-        
-        // Calculate percentiles if we have response times
-        let mut response_times: Vec<f64> = self.slowest_requests
-            .iter()
-            .filter_map(|e| e.response_time)
-            .collect();
-        
-        if !response_times.is_empty() {
-            response_times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-            
-            let p50_idx = (response_times.len() as f64 * 0.50) as usize;
-            let p95_idx = (response_times.len() as f64 * 0.95) as usize;
-            let p99_idx = (response_times.len() as f64 * 0.99) as usize;
-            
-            if p50_idx < response_times.len() {
-                println!("{:<30} {:>10}", 
-                    "P50 Response Time:", 
-                    format!("{:.2}ms", response_times[p50_idx]).bright_green()
-                );
-            }
-            if p95_idx < response_times.len() {
-                println!("{:<30} {:>10}", 
-                    "P95 Response Time:", 
-                    format!("{:.2}ms", response_times[p95_idx]).yellow()
-                );
-            }
-            if p99_idx < response_times.len() {
-                println!("{:<30} {:>10}", 
-                    "P99 Response Time:", 
-                    format!("{:.2}ms", response_times[p99_idx]).red()
-                );
-            }
-        }
+    /// Response time percentile (`pct` in 0..=100) across all requests.
+    pub fn response_time_percentile(&self, pct: f64) -> Option<f64> {
+        percentile(&self.response_times, pct)
     }
 
-    fn print_top_endpoints(&self) {
-        println!("\n{}", "🔝 TOP 10 ENDPOINTS BY REQUEST COUNT".bold().bright_white());
-        println!("{}", "─".repeat(65).bright_black());
-        
-        let mut endpoints: Vec<_> = self.endpoint_frequency.iter().collect();
-        endpoints.sort_by(|a, b| b.1.cmp(a.1));
-        
-        println!("{:<4} {:<40} {:>10}", 
-            "#".bright_black(), 
-            "Endpoint".bright_black(), 
-            "Count".bright_black()
-        );
-        println!("{}", "─".repeat(65).bright_black());
-        
-        for (i, (endpoint, count)) in endpoints.into_iter().take(10).enumerate() {
-            let bar_width = (*count as f64 / self.total_requests as f64 * 30.0) as usize;
-            let bar = "█".repeat(bar_width);
-            
-            println!("{:<4} {:<40} {:>10} {}", 
-                format!("{}", i + 1).bright_cyan(),
-                Self::truncate_endpoint(endpoint, 40),
-                format!("{}", count).bright_white().bold(),
-                bar.bright_blue()
-            );
+    pub fn level_count(&self, level: &LogLevel) -> usize {
+        match level {
+            LogLevel::Info => self.info_count,
+            LogLevel::Warning => self.warning_count,
+            LogLevel::Error => self.error_count,
         }
     }
 
-    fn print_error_analysis(&self) {
-        if self.errors_by_endpoint.is_empty() {
-            println!("\n{}", "✅ ERROR ANALYSIS: No errors detected".bold().green());
-            return;
-        }
-        
-        println!("\n{}", "🚨 ERROR ANALYSIS".bold().bright_white());
-        println!("{}", "─".repeat(65).bright_black());
-        
-        let mut errors: Vec<_> = self.errors_by_endpoint.iter().collect();
-        errors.sort_by(|a, b| b.1.cmp(a.1));
-        
-        println!("{:<4} {:<40} {:>10}", 
-            "#".bright_black(), 
-            "Endpoint".bright_black(), 
-            "Errors".bright_black()
-        );
-        println!("{}", "─".repeat(65).bright_black());
-        
-        for (i, (endpoint, count)) in errors.iter().take(10).enumerate() {
-            println!("{:<4} {:<40} {:>10}", 
-                format!("{}", i + 1).bright_cyan(),
-                Self::truncate_endpoint(endpoint, 40),
-                format!("{}", count).red().bold()
-            );
-        }
-    }
-
-    fn print_slowest_requests(&self) {
-        println!("\n{}", "🐌 TOP 10 SLOWEST REQUESTS".bold().bright_white());
-        println!("{}", "─".repeat(65).bright_black());
-        
-        println!("{:<4} {:<35} {:<10} {:>10}", 
-            "#".bright_black(), 
-            "Endpoint".bright_black(),
-            "Method".bright_black(),
-            "Time".bright_black()
-        );
-        println!("{}", "─".repeat(65).bright_black());
-        
-        for (i, entry) in self.slowest_requests.iter().take(10).enumerate() {
-            if let Some(response_time) = entry.response_time {
-                let endpoint = entry.endpoint.as_deref().unwrap_or("N/A");
-                let method = entry.method.as_ref().map(|m| m.to_string()).unwrap_or_else(|| "N/A".to_string());
-                
-                let time_color = if response_time > 1000.0 {
-                    format!("{:.2}ms", response_time).red().bold()
-                } else if response_time > 500.0 {
-                    format!("{:.2}ms", response_time).yellow()
-                } else {
-                    format!("{:.2}ms", response_time).bright_white()
-                };
-                
-                println!("{:<4} {:<35} {:<10} {:>10}", 
-                    format!("{}", i + 1).bright_cyan(),
-                    Self::truncate_endpoint(endpoint, 35),
-                    method,
-                    time_color
-                );
-            }
+    /// Share of requests logged at `ERROR`, as a percentage.
+    pub fn error_rate(&self) -> f64 {
+        if self.total_requests == 0 {
+            return 0.0;
         }
+        self.error_count as f64 / self.total_requests as f64 * 100.0
     }
 
-    fn truncate_endpoint(endpoint: &str, max_len: usize) -> String {
-        if endpoint.len() > max_len {
-            format!("{}...", &endpoint[..max_len - 3])
-        } else {
-            endpoint.to_string()
-        }
+    /// Export stats to JSON format
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
     }
 }
 
@@ -609,4 +1024,119 @@ impl Display for LogStats {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(line: &str) -> LogEntry {
+        LogEntry::parse_log(line).unwrap()
+    }
+
+    #[test]
+    fn json_lines_fill_typed_fields() {
+        let entry = parse(
+            r#"{"ts":"2024-01-15T08:15:23Z","lvl":"warn","client_ip":"10.0.0.1","method":"get","path":"/api","statusCode":"404","latency_ms":"1.5s","msg":"gone"}"#,
+        );
+        assert_eq!(entry.timestamp.as_deref(), Some("2024-01-15T08:15:23Z"));
+        assert_eq!(entry.level, Some(LogLevel::Warning));
+        assert_eq!(entry.ip_address, Some("10.0.0.1".parse().unwrap()));
+        assert!(matches!(entry.method, Some(LogMethod::Get)));
+        assert_eq!(entry.endpoint.as_deref(), Some("/api"));
+        assert_eq!(entry.status_code, Some(404));
+        assert_eq!(entry.response_time, Some(1500.0));
+        assert_eq!(entry.message.as_deref(), Some("gone"));
+        assert!(entry.extra.is_empty());
+    }
+
+    #[test]
+    fn out_of_range_statuses_stay_in_extra() {
+        for status in [json!(70000), json!(-1), json!("70000")] {
+            let entry = parse(&json!({ "status": status, "message": "x" }).to_string());
+            assert_eq!(entry.status_code, None, "{status}");
+            assert_eq!(entry.extra.get("status"), Some(&status));
+        }
+    }
+
+    #[test]
+    fn non_finite_and_negative_response_times_stay_in_extra() {
+        for time in [json!("NaN"), json!("inf"), json!(-5), json!("-5")] {
+            let entry = parse(&json!({ "response_time": time, "message": "x" }).to_string());
+            assert_eq!(entry.response_time, None, "{time}");
+            assert_eq!(entry.extra.get("response_time"), Some(&time));
+        }
+        assert_eq!(parse(r#"{"response_time": 0}"#).response_time, Some(0.0));
+    }
+
+    #[test]
+    fn first_usable_alias_wins() {
+        let entry = parse(r#"{"level":"warn","severity":"error","lvl":"info"}"#);
+        assert_eq!(entry.level, Some(LogLevel::Warning));
+        assert_eq!(entry.extra.get("severity"), Some(&json!("error")));
+        assert_eq!(entry.extra.get("lvl"), Some(&json!("info")));
+
+        // An alias whose value doesn't parse is skipped and kept as extra.
+        let entry = parse(r#"{"level":"verbose","severity":"error","lvl":"info"}"#);
+        assert_eq!(entry.level, Some(LogLevel::Error));
+        assert_eq!(entry.extra.get("level"), Some(&json!("verbose")));
+        assert!(!entry.extra.contains_key("severity"));
+    }
+
+    #[test]
+    fn unknown_fields_are_kept_as_extra() {
+        let entry = parse(r#"{"message":"paid","user":"alice","order":{"id":7,"items":[1,2]},"retry":true}"#);
+        assert_eq!(entry.message.as_deref(), Some("paid"));
+        assert_eq!(entry.extra.len(), 3);
+        assert_eq!(entry.extra_value("user"), Some(&json!("alice")));
+        assert_eq!(entry.extra_value("order.id"), Some(&json!(7)));
+        assert_eq!(entry.extra_value("order.items.1"), Some(&json!(2)));
+        assert_eq!(entry.field_value("retry").as_deref(), Some("true"));
+        assert_eq!(entry.raw, r#"{"message":"paid","user":"alice","order":{"id":7,"items":[1,2]},"retry":true}"#);
+    }
+
+    #[test]
+    fn malformed_json_is_a_parse_warning() {
+        let error = LogEntry::parse_log("{not json} 2024-01-15 08:15:23.145 ERROR 10.0.0.1 GET /api 500 12ms");
+        assert!(matches!(error, Err(AnalyzerError::JsonLineError(_))));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("truncated.log");
+        let lines = [
+            r#"{"timestamp":"2024-01-15T08:15:23","level":"error","status":500}"#,
+            r#"{"timestamp":"2024-01-15T08:15:24","level":"inf"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let mut logs = Logs::new();
+        let result = logs.read_and_parse_log(path).unwrap();
+        assert_eq!(result.entries_parsed, 1);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line_number, 2);
+        assert_eq!(result.warnings[0].error, "Invalid JSON: EOF while parsing a string at line 1 column 47");
+    }
+
+    #[test]
+    fn lines_without_timestamp_level_or_status_are_rejected() {
+        assert!(matches!(LogEntry::parse_log("hello world"), Err(AnalyzerError::UnrecognizedLine)));
+        assert!(LogEntry::parse_log("GET /api 12ms ERROR").is_ok());
+        assert!(LogEntry::parse_log("GET /api 404 12ms").is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mixed.log");
+        std::fs::write(&path, "hello world\n2024-01-15 08:15:01.000 INFO 10.0.0.1 GET /api 200 5ms\n").unwrap();
+        let mut logs = Logs::new();
+        let result = logs.read_and_parse_log(path.clone()).unwrap();
+        assert_eq!(result.entries_parsed, 1);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line_number, 1);
+
+        std::fs::write(&path, "\nhello world\n").unwrap();
+        let error = Logs::new().read_and_parse_log(path.clone()).unwrap_err();
+        assert!(matches!(error, AnalyzerError::ParseError { line_number: 2, .. }), "{error}");
+        let error = Logs::new().stream(&path, &TimeWindow::default(), false).err().unwrap();
+        assert!(matches!(error, AnalyzerError::ParseError { line_number: 2, .. }), "{error}");
+
+        std::fs::write(&path, "\n\n").unwrap();
+        assert!(matches!(Logs::new().read_and_parse_log(path).unwrap_err(), AnalyzerError::EmptyLogFile));
+    }
+}
@@ -0,0 +1,274 @@
+//! logfmt input: `level=info ts=… method=GET path=/api/users status=200
+//! dur=45ms msg="user \"bob\" logged in"`.
+//!
+//! Values may be bare or double-quoted; quoted values understand the escapes
+//! `\"`, `\\`, `\n`, `\r` and `\t`, and a key without `=` is a boolean flag.
+//! Keys are mapped onto the typed [`LogEntry`](crate::log_analyzer::LogEntry)
+//! fields through [`LogfmtKeys`]; all other keys become extra attributes, with
+//! bare numbers and booleans kept typed.
+
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Typed fields a key can be mapped onto, by their JSON names.
+pub const FIELDS: [&str; 8] = ["timestamp", "level", "ip", "method", "endpoint", "status", "response_time", "message"];
+
+/// Keys understood without configuration, beyond the field names and the
+/// aliases of JSON lines.
+const DEFAULT_KEYS: [(&str, &str); 5] = [
+    ("dur", "response_time"),
+    ("duration", "response_time"),
+    ("elapsed", "response_time"),
+    ("took", "response_time"),
+    ("uri", "endpoint"),
+];
+
+/// Mapping of logfmt keys onto typed fields.
+#[derive(Debug, Clone)]
+pub struct LogfmtKeys {
+    keys: BTreeMap<String, &'static str>,
+}
+
+impl Default for LogfmtKeys {
+    fn default() -> Self {
+        Self {
+            keys: DEFAULT_KEYS.iter().map(|(k, f)| (k.to_string(), *f)).collect(),
+        }
+    }
+}
+
+impl LogfmtKeys {
+    /// The defaults plus `mappings`, which win over them.
+    pub fn with(mappings: &[KeyMapping]) -> Self {
+        let mut keys = Self::default();
+        for mapping in mappings {
+            keys.keys.insert(mapping.key.clone(), mapping.field);
+        }
+        keys
+    }
+
+    fn field(&self, key: &str) -> Option<&'static str> {
+        self.keys.get(key).copied()
+    }
+}
+
+/// One `KEY=FIELD` mapping from the command line, e.g. `dur=response_time`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMapping {
+    pub key: String,
+    pub field: &'static str,
+}
+
+impl std::str::FromStr for KeyMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, field) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected KEY=FIELD, e.g. dur=response_time, got '{s}'"))?;
+        let field = FIELDS
+            .iter()
+            .find(|f| **f == field.trim())
+            .ok_or_else(|| format!("unknown field '{field}' (expected one of {})", FIELDS.join(", ")))?;
+        Ok(Self { key: key.trim().to_string(), field })
+    }
+}
+
+fn is_key_char(c: char) -> bool {
+    c > ' ' && c != '=' && c != '"'
+}
+
+/// Whether `line` starts with a `key=` pair, the sign of a logfmt line.
+pub fn looks_like_logfmt(line: &str) -> bool {
+    let line = line.trim_start();
+    let key_len = line.find(|c: char| !is_key_char(c)).unwrap_or(line.len());
+    key_len > 0 && line[key_len..].starts_with('=')
+}
+
+/// The value of a logfmt pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairValue {
+    /// `key=value`
+    Bare(String),
+    /// `key="value"`, unescaped.
+    Quoted(String),
+    /// A key without `=`.
+    Flag,
+}
+
+/// Split a line into its pairs. Fails on an unterminated quote or a
+/// dangling escape.
+pub fn parse_pairs(line: &str) -> Result<Vec<(String, PairValue)>, String> {
+    let mut pairs = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(pairs);
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| is_key_char(*c)) {
+            key.push(c);
+        }
+        if key.is_empty() {
+            return Err(format!("expected a key, found '{}'", chars.next().unwrap_or_default()));
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            pairs.push((key, PairValue::Flag));
+            continue;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some('r') => value.push('\r'),
+                        Some('t') => value.push('\t'),
+                        Some(c) => value.push(c),
+                        None => return Err(format!("dangling escape in the value of '{key}'")),
+                    },
+                    Some(c) => value.push(c),
+                    None => return Err(format!("unterminated quote in the value of '{key}'")),
+                }
+            }
+            pairs.push((key, PairValue::Quoted(value)));
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+            pairs.push((key, PairValue::Bare(value)));
+        }
+    }
+}
+
+/// A bare value as a number or boolean when it reads as one.
+pub fn typed(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => value
+            .parse::<i64>()
+            .map(Value::from)
+            .ok()
+            .or_else(|| value.parse::<f64>().ok().and_then(serde_json::Number::from_f64).map(Value::Number))
+            .unwrap_or_else(|| Value::String(value.to_string())),
+    }
+}
+
+/// The pairs of a logfmt line as string fields, with mapped keys renamed to
+/// their typed field, plus the keys whose value was bare (and may be read as
+/// a number or boolean with [`typed`]). The first key mapped onto a field
+/// wins; later ones are kept under their own name.
+pub fn fields(line: &str, keys: &LogfmtKeys) -> Result<(Map<String, Value>, Vec<String>), String> {
+    let mut fields = Map::new();
+    let mut bare = Vec::new();
+    for (key, value) in parse_pairs(line)? {
+        let key = match keys.field(&key).filter(|field| !fields.contains_key(*field)) {
+            Some(field) => field.to_string(),
+            None => key,
+        };
+        let value = match value {
+            PairValue::Bare(value) => {
+                bare.push(key.clone());
+                Value::String(value)
+            }
+            PairValue::Quoted(value) => Value::String(value),
+            PairValue::Flag => Value::Bool(true),
+        };
+        fields.insert(key, value);
+    }
+    Ok((fields, bare))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_analyzer::{LogEntry, LogLevel, LogMethod};
+
+    fn bare(s: &str) -> PairValue {
+        PairValue::Bare(s.to_string())
+    }
+
+    fn quoted(s: &str) -> PairValue {
+        PairValue::Quoted(s.to_string())
+    }
+
+    #[test]
+    fn pairs_are_split_and_unescaped() {
+        let pairs = parse_pairs(r#"  a=1 msg="say \"hi\"\n\tback\\slash" debug b= c="" d=x=y"#).unwrap();
+        let pairs: Vec<(&str, PairValue)> = pairs.iter().map(|(k, v)| (k.as_str(), v.clone())).collect();
+        assert_eq!(
+            pairs,
+            [
+                ("a", bare("1")),
+                ("msg", quoted("say \"hi\"\n\tback\\slash")),
+                ("debug", PairValue::Flag),
+                ("b", bare("")),
+                ("c", quoted("")),
+                ("d", bare("x=y")),
+            ]
+        );
+        assert_eq!(parse_pairs("").unwrap(), []);
+    }
+
+    #[test]
+    fn broken_quotes_are_errors() {
+        assert_eq!(parse_pairs(r#"a=1 msg="never closed"#).unwrap_err(), "unterminated quote in the value of 'msg'");
+        assert_eq!(parse_pairs(r#"msg="ends in \"#).unwrap_err(), "dangling escape in the value of 'msg'");
+        assert_eq!(parse_pairs(r#"a=1 "oops""#).unwrap_err(), "expected a key, found '\"'");
+    }
+
+    #[test]
+    fn key_mappings_parse() {
+        let mapping: KeyMapping = " lat = response_time ".parse().unwrap();
+        assert_eq!(mapping, KeyMapping { key: "lat".to_string(), field: "response_time" });
+        assert!("lat".parse::<KeyMapping>().unwrap_err().contains("expected KEY=FIELD"));
+        assert!("lat=latency".parse::<KeyMapping>().unwrap_err().contains("unknown field 'latency'"));
+    }
+
+    #[test]
+    fn first_mapped_key_wins() {
+        let keys = LogfmtKeys::with(&["rt=response_time".parse().unwrap()]);
+        let (map, bare) = fields("dur=45ms rt=50 took=60 flag", &keys).unwrap();
+        assert_eq!(map["response_time"], "45ms");
+        assert_eq!(map["rt"], "50");
+        assert_eq!(map["took"], "60");
+        assert_eq!(map["flag"], true);
+        assert_eq!(bare, ["response_time", "rt", "took"]);
+
+        // Command-line mappings win over the defaults.
+        let keys = LogfmtKeys::with(&["dur=message".parse().unwrap()]);
+        assert_eq!(fields("dur=45ms", &keys).unwrap().0["message"], "45ms");
+    }
+
+    #[test]
+    fn bare_values_are_typed() {
+        assert_eq!(typed("true"), Value::Bool(true));
+        assert_eq!(typed("false"), Value::Bool(false));
+        assert_eq!(typed("42"), Value::from(42));
+        assert_eq!(typed("-1.5"), Value::from(-1.5));
+        assert_eq!(typed("NaN"), Value::String("NaN".to_string()));
+        assert_eq!(typed("45ms"), Value::String("45ms".to_string()));
+        assert_eq!(typed("True"), Value::String("True".to_string()));
+    }
+
+    #[test]
+    fn parses_go_service_lines() {
+        let line = r#"level=info ts=2024-01-15T08:15:01Z method=GET path=/api/users status=200 dur=45ms msg="user \"bob\" logged in" region=eu retries=2 cached"#;
+        assert!(looks_like_logfmt(line));
+        let entry = LogEntry::parse_log(line).unwrap();
+        assert_eq!(entry.level, Some(LogLevel::Info));
+        assert!(matches!(entry.method, Some(LogMethod::Get)));
+        assert_eq!(entry.endpoint.as_deref(), Some("/api/users"));
+        assert_eq!(entry.status_code, Some(200));
+        assert_eq!(entry.response_time, Some(45.0));
+        assert_eq!(entry.message.as_deref(), Some("user \"bob\" logged in"));
+        assert!(entry.parsed_timestamp().is_some());
+        assert_eq!(entry.extra["region"], "eu");
+        assert_eq!(entry.extra["retries"], 2);
+        assert_eq!(entry.extra["cached"], true);
+        assert!(!looks_like_logfmt("2024-01-15 08:15:01.000 INFO started"));
+    }
+}
@@ -1,30 +1,729 @@
 #![allow(unused)]
 use std::{
     fs::File,
-    io::{BufRead, BufReader}, path::PathBuf,
+    io::{BufRead, BufReader, IsTerminal}, path::{Path, PathBuf}, process::ExitCode,
 };
-use clap::Parser;
+use chrono::Duration;
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Serialize, Deserialize};
 use anyhow::Result;
-use loggaliza::log_analyzer::{AnalyzerError, LogEntry, LogStats, Logs};
+use loggaliza::aggregate::{self, Agg, GroupBy, SortBy};
+use loggaliza::columnar::{self, ExtraFields};
+use loggaliza::convert::{self, ConvertFormat};
+use loggaliza::export::{self, ExportFormat, ExportTable};
+use loggaliza::gate::{self, Baseline, Check, GateReport};
+use loggaliza::heatmap::{self, Heatmap, HeatmapMetric};
+use loggaliza::html;
+use loggaliza::latency;
+use loggaliza::log_analyzer::{AnalyzerError, LogEntry, LogStats, Logs, parse_duration};
+use loggaliza::logfmt::{KeyMapping, LogfmtKeys};
+use loggaliza::markdown;
+use loggaliza::metrics::{self, Exposition};
+use loggaliza::query::Expr;
+use loggaliza::report::{self, AppliedFilters, InputMetadata, OutputFormat, Report};
+use loggaliza::rules::{self, Alert, RuleEngine, RuleSet};
+use loggaliza::template;
+use loggaliza::text_report::{self, ColorMode, Section, TextLayout};
+use loggaliza::time_range::{self, TimeSpec, TimeWindow};
+use loggaliza::search::{self, SearchIndex, SearchQuery};
+use loggaliza::security::{self, Finding, SecurityAnalyzer};
+use loggaliza::show::{self, ContextSpan, JsonStyle, ShowOptions};
+use loggaliza::sql::{Select, SqlFormat};
+use loggaliza::sqlite;
+use loggaliza::tui;
 
 #[derive(Parser)]
 #[command(name="Loggaliza", version, about("Server logs file analyzer"), long_about = None)]
 struct Opts {
-    #[arg(short = 'i', long)]
+    /// Log file with plain text, JSON, logfmt or syslog lines
+    #[arg(short = 'i', long, required_unless_present_any = ["json_schema", "print_template"])]
+    input_file: Option<PathBuf>,
+
+    /// Map logfmt keys onto fields, e.g. `dur=response_time,path=endpoint`.
+    /// Fields: timestamp, level, ip, method, endpoint, status, response_time,
+    /// message; unmapped keys are kept as extra fields
+    #[arg(long, value_name = "KEY=FIELD", value_delimiter = ',')]
+    logfmt_key: Vec<KeyMapping>,
+
+    /// Only analyze entries matching a query, e.g.
+    /// `level >= WARNING and status in 500..599 and not ip in 10.0.0.0/8`
+    #[arg(short = 'w', long = "where", value_name = "EXPR")]
+    query: Option<String>,
+
+    /// Only analyze entries at or after this time: a datetime (offsets such as
+    /// `+02:00` are converted to UTC), `08:16`, `2h ago`, `yesterday 14:00` or
+    /// `last 15m`. Relative times are anchored to the newest entry.
+    #[arg(long, value_name = "TIME")]
+    since: Option<String>,
+
+    /// Only analyze entries before this time (same syntax as --since)
+    #[arg(long, value_name = "TIME")]
+    until: Option<String>,
+
+    /// The input is sorted by time: binary-search to the --since/--until
+    /// window instead of parsing the whole file. Line numbers then count from
+    /// the start of the window
+    #[arg(long)]
+    sorted: bool,
+
+    /// Run security detections (brute force, scanning, forbidden bursts, payloads)
+    #[arg(long)]
+    security: bool,
+
+    /// TOML file with alerting rules to evaluate
+    #[arg(short = 'r', long)]
+    rules: Option<PathBuf>,
+
+    /// Keep watching the input for new lines and print rule alerts as their
+    /// windows close, as text or (with --output json) one JSON object per line
+    #[arg(short = 'f', long, requires = "rules")]
+    follow: bool,
+
+    /// Break entries down by fields instead of printing the report, e.g.
+    /// `endpoint,method`. Any field, extra JSON path (`error.code`),
+    /// `status_class`, `template`, `minute`, `hour` or `day`
+    #[arg(long = "group-by", value_name = "KEYS", value_delimiter = ',')]
+    group_by: Vec<String>,
+
+    /// Aggregates per group (default `count`): count, error_rate, share,
+    /// count_distinct(F), sum(F), avg(F), min(F), max(F), median(F), p95(F)
+    #[arg(long = "agg", value_name = "AGGS", value_delimiter = ',', requires = "group_by")]
+    aggregates: Vec<Agg>,
+
+    /// Sort groups by a key or aggregate, e.g. `p95(response_time)` or
+    /// `endpoint:asc` (default: first aggregate, descending)
+    #[arg(long, value_name = "COLUMN", requires = "group_by")]
+    sort: Option<SortBy>,
+
+    /// Only show the first N groups, or N rows per table of the text report
+    /// and N slowest requests in the JSON report (default 10)
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Sections of the text report, in order
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "SECTIONS", default_values_t = Section::DEFAULT)]
+    sections: Vec<Section>,
+
+    /// Also show the latency histogram of these endpoints, in the text
+    /// report's latency section (added when missing) and the JSON report
+    #[arg(long, value_name = "ENDPOINTS", value_delimiter = ',')]
+    latency_endpoint: Vec<String>,
+
+    /// What the heatmap section and the JSON heatmap show per hour and
+    /// weekday
+    #[arg(long, value_enum, default_value_t, value_name = "METRIC")]
+    heatmap_metric: HeatmapMetric,
+
+    /// IANA timezone of the heatmap's hours and weekdays, e.g. Europe/Berlin
+    #[arg(long, value_name = "TZ", default_value = "UTC", value_parser = heatmap::parse_timezone)]
+    timezone: Tz,
+
+    /// Width of the text report (default: the terminal's)
+    #[arg(long, value_name = "COLUMNS")]
+    width: Option<usize>,
+
+    /// Only ASCII in the text report: no box drawing, bars or emoji
+    #[arg(long)]
+    ascii: bool,
+
+    /// When to use colors; `auto` honors NO_COLOR and disables them when
+    /// stdout is not a terminal
+    #[arg(long, value_enum, default_value_t, value_name = "WHEN")]
+    color: ColorMode,
+
+    /// Report format
+    #[arg(short = 'o', long, value_enum, default_value_t)]
+    output: OutputFormat,
+
+    /// Write the report, or the output of `sql`, `export`, `convert` and
+    /// --group-by, to FILE instead of stdout
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+
+    /// Latency histogram buckets of the metrics formats, e.g.
+    /// `10ms,50ms,250ms,1s` (default: 5ms to 10s)
+    #[arg(long, value_name = "DURATIONS", value_delimiter = ',', value_parser = parse_duration)]
+    buckets: Vec<Duration>,
+
+    /// Shorthand for `--output json`
+    #[arg(long, hide = true)]
+    json: bool,
+
+    /// Print the JSON Schema of the JSON report and exit
+    #[arg(long)]
+    json_schema: bool,
+
+    /// Render the text report with a MiniJinja template instead. The template
+    /// picks its own sections; --latency-endpoint, --heatmap-metric and
+    /// --timezone shape the `latency` and `heatmap` it sees
+    #[arg(long, value_name = "FILE", conflicts_with = "sections")]
+    template: Option<PathBuf>,
+
+    /// Print the built-in report template, a starting point for --template,
+    /// and exit
+    #[arg(long)]
+    print_template: bool,
+
+    /// Fail when a threshold is breached, e.g. `error_rate>5`, `p95>800`,
+    /// `avg<200` (the report's average response time), `count(ERROR)>0`,
+    /// `new_templates` or any --agg aggregate such as
+    /// `max(response_time)>5000` (repeatable)
+    #[arg(long = "fail-on", value_name = "CHECK")]
+    fail_on: Vec<Check>,
+
+    /// Known message templates, one per line, for the `new_templates` check
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+
+    /// Write the message templates seen in this run to FILE
+    #[arg(long, value_name = "FILE")]
+    save_baseline: Option<PathBuf>,
+
+    /// Write the check results as JSON to FILE
+    #[arg(long, value_name = "FILE")]
+    checks_output: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Query the parsed entries with SQL, e.g.
+    /// `SELECT endpoint, count(*) AS hits FROM logs GROUP BY endpoint ORDER BY hits DESC`.
+    /// --where and --since/--until are applied first.
+    Sql {
+        query: String,
+
+        #[arg(long, value_enum, default_value_t)]
+        format: SqlFormat,
+    },
+    /// Build (or rebuild) the full-text index next to the input file
+    Index,
+    /// Search the input through its index, building it first if it is
+    /// missing or the file changed. Terms, "quoted phrases", field:value
+    /// qualifiers (level:error, status:5xx, endpoint:/api/*) and -exclusions
+    Search {
+        query: String,
+
+        /// Lines of context to show around each match
+        #[arg(short = 'C', long, default_value_t = 0)]
+        context: usize,
+
+        /// Stop after this many matches
+        #[arg(short = 'm', long = "max-count", value_name = "N")]
+        max_count: Option<usize>,
+    },
+    /// Export the filtered entries or a statistics table as CSV/TSV to
+    /// stdout or --out
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        table: ExportTable,
+
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+
+        /// Entry columns: any field or extra JSON path, `line_number`, `raw`
+        /// or `template` (default: the standard fields). CSV/TSV only
+        #[arg(long, value_delimiter = ',', value_name = "COLUMNS")]
+        columns: Vec<String>,
+
+        /// Parquet/Arrow: store extra JSON fields as one map column or as a
+        /// column per key
+        #[arg(long, value_enum, default_value_t)]
+        extra: ExtraFields,
+
+        /// Parquet/Arrow: rows per row group
+        #[arg(long, value_name = "ROWS", default_value_t = columnar::DEFAULT_ROW_GROUP_SIZE)]
+        row_group_size: usize,
+
+        /// SQLite: add this run to an existing database instead of replacing it
+        #[arg(long)]
+        append: bool,
+    },
+    /// Rewrite the filtered entries in another log format to stdout or
+    /// --out, keeping extra fields where the format allows. Fields that
+    /// could not be written are reported on stderr
+    Convert {
+        #[arg(long, value_enum, default_value_t)]
+        to: ConvertFormat,
+    },
+    /// Explore the entries interactively: summary, top endpoints and errors
+    /// (Enter drills down), entry list with details, and a filter bar that
+    /// takes --where expressions. --since/--until narrow the entries first
+    Tui,
+    /// Print the raw lines of the entries selected by --where/--since/--until
+    /// with context, colored by level and with matched values highlighted.
+    /// Context is a line count (`3`) or a time span (`5s`)
+    Show {
+        /// Context after each match
+        #[arg(short = 'A', long = "after-context", value_name = "N|SPAN")]
+        after: Option<ContextSpan>,
+
+        /// Context before each match
+        #[arg(short = 'B', long = "before-context", value_name = "N|SPAN")]
+        before: Option<ContextSpan>,
+
+        /// Context on both sides of each match
+        #[arg(short = 'C', long, value_name = "N|SPAN")]
+        context: Option<ContextSpan>,
+
+        /// Additionally highlight this regex in matched lines (repeatable)
+        #[arg(long, value_name = "REGEX")]
+        highlight: Vec<String>,
+
+        /// How to print JSON entries
+        #[arg(long, value_enum, default_value_t)]
+        json_style: JsonStyle,
+    },
+}
+
+/// Exit code for I/O and configuration errors. Clap uses 2 for usage errors.
+const EXIT_ERROR: u8 = 1;
+/// Exit code when a `--fail-on` check failed or a rule raised an alert.
+const EXIT_THRESHOLD_BREACH: u8 = 3;
+/// Exit code when no line of the input could be parsed into an entry. Lines
+/// that fail to parse among others that do are reported as warnings and the
+/// run still exits 0.
+const EXIT_PARSE_FAILURE: u8 = 4;
+
+fn main() -> ExitCode {
+    match run(Opts::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn exit_code(error: &AnalyzerError) -> u8 {
+    match error {
+        AnalyzerError::EmptyLogFile | AnalyzerError::ParseError { .. } => EXIT_PARSE_FAILURE,
+        _ => EXIT_ERROR,
+    }
+}
+
+/// Write `contents` with [`export::write_atomically`], so readers such as the
+/// textfile collector never see a partial file.
+fn write_atomically(path: &Path, contents: &str) -> Result<(), AnalyzerError> {
+    export::write_atomically(path, |mut file| Ok(std::io::Write::write_all(&mut file, contents.as_bytes())?))
+}
+
+/// The filters as given on the command line, for report metadata.
+fn applied_filters(args: &Opts) -> AppliedFilters {
+    AppliedFilters {
+        query: args.query.clone(),
+        since: args.since.clone(),
+        until: args.until.clone(),
+    }
+}
+
+/// Command-line spelling of an export format.
+fn format_name(format: impl ValueEnum) -> String {
+    format.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+}
+
+/// How often `--follow` checks the input for new lines.
+const FOLLOW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Layout of the text report and the sections printed after it.
+fn text_layout(args: &Opts) -> TextLayout {
+    let mut layout = TextLayout {
+        width: args.width.unwrap_or_else(|| TextLayout::detect_width(std::io::stdout().is_terminal())),
+        top: args.top.unwrap_or(text_report::DEFAULT_TOP),
+        sections: args.sections.clone(),
+        ascii: args.ascii,
+        latency_endpoints: args.latency_endpoint.clone(),
+        heatmap_metric: args.heatmap_metric,
+        timezone: args.timezone,
+    };
+    if !layout.latency_endpoints.is_empty() && !layout.sections.contains(&Section::Latency) {
+        layout.sections.push(Section::Latency);
+    }
+    layout
+}
+
+/// Evaluate the rules over lines appended to the input until interrupted.
+fn follow_rules(
+    args: &Opts,
+    logs: &Logs,
     input_file: PathBuf,
+    output: OutputFormat,
+    query: Option<&Expr>,
+) -> Result<ExitCode, AnalyzerError> {
+    if args.command.is_some() || args.since.is_some() || args.until.is_some() {
+        return Err(AnalyzerError::OutputError(
+            "--follow watches new lines for rules; drop the subcommand and --since/--until".to_string(),
+        ));
+    }
+    if !matches!(output, OutputFormat::Text | OutputFormat::Json) || args.template.is_some() || args.out.is_some() {
+        return Err(AnalyzerError::OutputError("--follow prints alerts as text or JSON lines".to_string()));
+    }
+    if args.security || !args.fail_on.is_empty() || !args.group_by.is_empty() {
+        return Err(AnalyzerError::OutputError(
+            "--follow only evaluates rules; drop --security, --fail-on and --group-by".to_string(),
+        ));
+    }
+    let rule_set = RuleSet::from_file(args.rules.as_deref().expect("clap requires --rules"))?;
+    let mut engine = RuleEngine::new(&rule_set)?;
+    if let Some(rule) = engine.rules().iter().find(|rule| rule.window.is_none()) {
+        return Err(AnalyzerError::RuleError {
+            rule: rule.name.clone(),
+            message: "--follow needs a window to know when to alert".to_string(),
+        });
+    }
+    logs.follow(input_file, FOLLOW_INTERVAL, |entry| {
+        if query.is_some_and(|q| !q.matches(&entry)) {
+            return;
+        }
+        engine.observe(&entry);
+        let Some(now) = entry.parsed_timestamp() else {
+            return;
+        };
+        for alert in engine.close_windows(now) {
+            if output == OutputFormat::Json {
+                println!("{}", serde_json::to_string(&alert).expect("alerts are serializable"));
+            } else {
+                rules::print_alert(&alert);
+            }
+        }
+    })?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run(args: Opts) -> Result<ExitCode, AnalyzerError> {
+    args.color.apply(std::io::stdout().is_terminal());
+    if args.print_template {
+        print!("{}", template::DEFAULT_TEMPLATE);
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.json_schema {
+        println!("{}", report::json_schema());
+        return Ok(ExitCode::SUCCESS);
+    }
+    let input_file = args.input_file.clone().expect("clap requires --input-file");
+    let output = if args.json { OutputFormat::Json } else { args.output };
+    if args.template.is_some() && output != OutputFormat::Text {
+        return Err(AnalyzerError::OutputError("--template renders text; drop --output".to_string()));
+    }
+    if let Some(out) = &args.out
+        && out.canonicalize().ok().is_some_and(|out| input_file.canonicalize().is_ok_and(|input| input == out))
+    {
+        return Err(AnalyzerError::OutputError("--out is the input file".to_string()));
+    }
+    if args.out.is_some() && output == OutputFormat::Text && args.template.is_none() && args.command.is_none() {
+        return Err(AnalyzerError::OutputError(
+            "--out needs a file format such as --output json".to_string(),
+        ));
+    }
+    if args.out.is_some() {
+        let terminal_only = match &args.command {
+            Some(Command::Index) => Some("index writes the index next to the input"),
+            Some(Command::Search { .. }) => Some("search prints to the terminal"),
+            Some(Command::Tui) => Some("tui runs in the terminal"),
+            Some(Command::Show { .. }) => Some("show prints to the terminal"),
+            _ => None,
+        };
+        if let Some(reason) = terminal_only {
+            return Err(AnalyzerError::OutputError(format!("{reason}; drop --out")));
+        }
+    }
+    let query = args.query.as_deref().map(Expr::parse).transpose()?;
+
+    let mut logs = Logs::default();
+    logs.options.logfmt_keys = LogfmtKeys::with(&args.logfmt_key);
+
+    // The index commands work on the raw file and never parse it as a whole.
+    match &args.command {
+        Some(Command::Index) => {
+            let index = SearchIndex::build(&input_file, &logs.options)?;
+            eprintln!(
+                "indexed {} lines, {} terms into {}",
+                index.line_count(),
+                index.term_count(),
+                search::index_path(&input_file).display()
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Search { query, context, max_count }) => {
+            let query = SearchQuery::parse(query)?;
+            let (index, rebuilt) = SearchIndex::open(&input_file, &logs.options)?;
+            if rebuilt {
+                eprintln!("index was missing or out of date; rebuilt it");
+            }
+            let mut hits = index.search(&input_file, &query)?;
+            if let Some(max) = max_count {
+                hits.truncate(*max);
+            }
+            search::print_hits(&index, &input_file, &query, &hits, *context)?;
+            if hits.is_empty() {
+                eprintln!("no matches");
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+
+    if args.follow {
+        return follow_rules(&args, &logs, input_file, output, query.as_ref());
+    }
+    let window = TimeWindow {
+        since: args.since.as_deref().map(str::parse).transpose()?,
+        until: args.until.as_deref().map(str::parse).transpose()?,
+    };
+    if let Some(Command::Export { table, format, columns, .. }) = &args.command
+        && format.delimited().is_none()
+    {
+        if args.out.is_none() {
+            return Err(AnalyzerError::OutputError(format!("--format {} needs --out FILE", format_name(*format))));
+        }
+        if *table != ExportTable::Entries || !columns.is_empty() {
+            return Err(AnalyzerError::OutputError(format!(
+                "--format {} exports the entries with all columns; drop --table/--columns",
+                format_name(*format)
+            )));
+        }
+    }
+    if let (Some(Command::Export { format, extra, row_group_size, .. }), Some(path)) = (&args.command, &args.out)
+        && let Some(columnar_format) = format.columnar()
+    {
+        // Parquet and Arrow stream the entries instead of reading them all.
+        let entries = || {
+            let entries = logs.stream(&input_file, &window, args.sorted)?;
+            Ok(entries.filter(|entry| match (entry, &query) {
+                (Ok(entry), Some(query)) => query.matches(entry),
+                _ => true,
+            }))
+        };
+        let rows = columnar::write(path, columnar_format, entries, *extra, *row_group_size)?;
+        eprintln!("Wrote {rows} entries to {}", path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let parse_result = if !window.is_empty() && args.sorted {
+        logs.read_and_parse_log_window(input_file.clone(), &window)?
+    } else {
+        logs.read_and_parse_log(input_file.clone())?
+    };
+    let entries_parsed = logs.entries.len();
+    // `show` needs the unfiltered entries for context.
+    let all_entries = matches!(args.command, Some(Command::Show { .. })).then(|| logs.entries.clone());
+    if !window.is_empty() && !args.sorted {
+        let range = window.resolve(time_range::newest_timestamp(&logs.entries))?;
+        logs.retain(&range);
+    }
+    if let Some(Command::Tui) = &args.command {
+        if !std::io::stdout().is_terminal() {
+            return Err(AnalyzerError::OutputError("tui needs a terminal".to_string()));
+        }
+        tui::run(&logs.entries, args.query.as_deref())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(query) = &query {
+        logs.retain(query);
+    }
+
+    if let (Some(Command::Show { after, before, context, highlight, json_style }), Some(all_entries)) =
+        (&args.command, &all_entries)
+    {
+        let mut highlights = query.as_ref().map(Expr::highlight_patterns).unwrap_or_default();
+        for pattern in highlight {
+            highlights.push(regex::Regex::new(pattern)?);
+        }
+        let options = ShowOptions {
+            before: before.or(*context),
+            after: after.or(*context),
+            highlights,
+            json_style: *json_style,
+        };
+        let matched = logs.entries.iter().map(|e| e.line_number).collect();
+        if show::print_matches(all_entries, &matched, &options) == 0 {
+            eprintln!("no matches");
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::Sql { query, format }) = &args.command {
+        let select = Select::parse(query)?;
+        let mut rendered = select.execute(&logs.entries).render(*format);
+        if *format == SqlFormat::Json {
+            rendered.push('\n');
+        }
+        match &args.out {
+            Some(path) => write_atomically(path, &rendered)?,
+            None => print!("{rendered}"),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if !args.group_by.is_empty() {
+        let table = GroupBy {
+            keys: args.group_by.clone(),
+            aggregates: args.aggregates.clone(),
+            sort: args.sort.clone(),
+            top: args.top,
+        }
+        .run(&logs.entries)?;
+        if args.out.is_some() && output != OutputFormat::Json {
+            return Err(AnalyzerError::OutputError("--group-by writes --out only with --output json".to_string()));
+        }
+        if output == OutputFormat::Json {
+            let json = serde_json::to_string_pretty(&table).expect("group table is serializable");
+            match &args.out {
+                Some(path) => write_atomically(path, &(json + "\n"))?,
+                None => println!("{json}"),
+            }
+        } else {
+            aggregate::print_group_table(&table, &text_layout(&args));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(Command::Convert { to }) = &args.command {
+        let conversion = convert::convert(&logs.entries, *to);
+        match &args.out {
+            Some(path) => write_atomically(path, &conversion.output)?,
+            None => print!("{}", conversion.output),
+        }
+        if !conversion.dropped.is_empty() {
+            let fields: Vec<String> = conversion
+                .dropped
+                .iter()
+                .map(|(field, count)| format!("{field} ({count} {})", if *count == 1 { "entry" } else { "entries" }))
+                .collect();
+            eprintln!("Not representable in {}: {}", format_name(*to), fields.join(", "));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let top = args.top.unwrap_or(text_report::DEFAULT_TOP);
+    let stats = LogStats::from_entries_top(&logs.entries, top);
+
+    if let Some(Command::Export { table, format, columns, append, .. }) = &args.command {
+        if let Some(delimited) = format.delimited() {
+            let exported = export::export(*table, delimited, columns, &logs.entries, &stats)?;
+            match &args.out {
+                Some(path) => write_atomically(path, &exported)?,
+                None => print!("{exported}"),
+            }
+        } else if let Some(path) = &args.out {
+            let input = InputMetadata::new(&input_file, entries_parsed, &logs.entries, applied_filters(&args));
+            let source_id = sqlite::write(path, *append, &input, &logs.entries, &stats)?;
+            eprintln!("Wrote {} entries to {} as source {source_id}", logs.entries.len(), path.display());
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let alerts = match &args.rules {
+        Some(path) => {
+            let rule_set = RuleSet::from_file(path)?;
+            Some(RuleEngine::new(&rule_set)?.evaluate(&logs.entries))
+        }
+        None => None,
+    };
+    let findings = args
+        .security
+        .then(|| SecurityAnalyzer::default().analyze(&logs.entries));
+
+    let baseline = args.baseline.as_deref().map(Baseline::from_file).transpose()?;
+    let gate_report = (!args.fail_on.is_empty())
+        .then(|| gate::evaluate(&args.fail_on, &stats, &logs.entries, baseline.as_ref()))
+        .transpose()?;
+    if let Some(path) = &args.save_baseline {
+        Baseline::from_entries(&logs.entries).write(path)?;
+    }
+    if let (Some(path), Some(report)) = (&args.checks_output, &gate_report) {
+        let json = serde_json::to_string_pretty(report).expect("check report is serializable");
+        write_atomically(path, &json)?;
+    }
+
+    let layout = text_layout(&args);
+    let rendered = match output {
+        OutputFormat::Text if args.template.is_none() => {
+            print!("{}", text_report::render(&stats, &logs.entries, &layout));
+            if let Some(findings) = &findings {
+                security::print_findings(findings, &layout);
+            }
+            if let Some(alerts) = &alerts {
+                rules::print_alerts(alerts, &layout);
+            }
+            if let Some(report) = &gate_report {
+                gate::print_gate_report(report, &layout);
+            }
+            None
+        }
+        format => {
+            let input = InputMetadata::new(&input_file, entries_parsed, &logs.entries, applied_filters(&args));
+            let mut report = Report::new(input, &stats, &logs.entries, &parse_result.warnings);
+            report.alerts = alerts.as_deref();
+            report.findings = findings.as_deref();
+            report.checks = gate_report.as_ref();
+            if !args.latency_endpoint.is_empty() {
+                report.latency = latency::histograms(&logs.entries, &args.latency_endpoint);
+            }
+            if args.heatmap_metric != HeatmapMetric::default() || args.timezone != Tz::UTC {
+                report.heatmap = Heatmap::new(&logs.entries, args.heatmap_metric, args.timezone);
+            }
+            Some(match format {
+                OutputFormat::Text => {
+                    let path = args.template.as_ref().expect("text output only gets here with a template");
+                    let source = std::fs::read_to_string(path)?;
+                    template::render(&source, &path.display().to_string(), &report, &logs.entries, &layout)?
+                }
+                OutputFormat::Html => html::render(&report, &logs.entries),
+                OutputFormat::Markdown => markdown::render(&report, &logs.entries),
+                OutputFormat::Prometheus | OutputFormat::OpenMetrics => {
+                    let exposition = if format == OutputFormat::Prometheus {
+                        Exposition::Prometheus
+                    } else {
+                        Exposition::OpenMetrics
+                    };
+                    let rendered = metrics::render(&report, &logs.entries, &metrics::buckets(&args.buckets)?, exposition);
+                    rendered.trim_end().to_string()
+                }
+                OutputFormat::Json => report.to_json(),
+            })
+        }
+    };
+    match (rendered, &args.out) {
+        (Some(rendered), Some(path)) => write_atomically(path, &(rendered + "\n"))?,
+        (Some(rendered), None) => println!("{rendered}"),
+        (None, _) => {}
+    }
+
+    if let Some(report) = &gate_report
+        && !report.passed
+    {
+        eprintln!("checks failed: {}", report.failed.join(", "));
+        return Ok(ExitCode::from(EXIT_THRESHOLD_BREACH));
+    }
+    if alerts.is_some_and(|alerts| !alerts.is_empty()) {
+        return Ok(ExitCode::from(EXIT_THRESHOLD_BREACH));
+    }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn main() -> Result<(), AnalyzerError> {
-    let args = Opts::parse();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_map_to_exit_codes() {
+        assert_eq!(exit_code(&AnalyzerError::EmptyLogFile), EXIT_PARSE_FAILURE);
+        let not_evaluable = AnalyzerError::NoMetricData("p95(response_time)".to_string());
+        assert_eq!(exit_code(&not_evaluable), EXIT_ERROR);
+        assert_eq!(exit_code(&AnalyzerError::InvalidFilter("x".to_string())), EXIT_ERROR);
+    }
 
-    let file_exists = args.input_file.try_exists()?;
-    if !file_exists {
-        panic!("File does not exist")
+    #[test]
+    fn a_file_without_parseable_lines_fails_with_the_parse_exit_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("garbage.log");
+        std::fs::write(&path, "hello world\n\n%%% not a log line %%%\n").unwrap();
+        let args = Opts::try_parse_from(["loggaliza", "-i", path.to_str().unwrap(), "--json"]).unwrap();
+        let error = run(args).expect_err("garbage must not parse");
+        assert!(matches!(error, AnalyzerError::ParseError { line_number: 1, .. }), "{error}");
+        assert_eq!(exit_code(&error), EXIT_PARSE_FAILURE);
     }
-    let mut logs: Logs = Logs::default();
-    logs.read_and_parse_log(args.input_file)?;
-    let stats = LogStats::from_entries(&logs.entries);
-    stats.print_report();
-    Ok(())
 }
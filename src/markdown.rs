@@ -0,0 +1,382 @@
+//! GitHub-flavored Markdown report, for pasting into pull requests and
+//! postmortems. Mirrors the sections of [`LogStats::print_report`]; longer
+//! lists go into collapsible `<details>` blocks.
+
+use crate::log_analyzer::{LogEntry, LogStats, slowest_first};
+use crate::report::Report;
+use std::fmt::Write;
+
+/// Rows shown before a list is folded into `<details>`.
+const VISIBLE_ROWS: usize = 10;
+/// Rows inside a `<details>` block.
+const FOLDED_ROWS: usize = 100;
+
+/// Escape text for a table cell: pipes would end the cell and newlines the row.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+/// Escape text in running Markdown: punctuation that could start emphasis,
+/// code, links, HTML or a table cell is backslash-escaped, and newlines
+/// would end the list item.
+fn inline(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.replace(['\r', '\n'], " ").chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' | '!' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Length of the longest run of backticks in `text`.
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+/// Inline code span that survives backticks in `text`: the fence is longer
+/// than any backtick run inside, and a space separates it from a leading or
+/// trailing backtick (CommonMark strips one such space on each side).
+fn code(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let text = cell(text);
+    let fence = "`".repeat(longest_backtick_run(&text) + 1);
+    let space = if text.starts_with('`') || text.ends_with('`') || (text.starts_with(' ') && text.ends_with(' ')) {
+        " "
+    } else {
+        ""
+    };
+    format!("{fence}{space}{text}{space}{fence}")
+}
+
+fn table(md: &mut String, headers: &[&str], align: &[&str], rows: &[Vec<String>]) {
+    let _ = writeln!(md, "| {} |", headers.join(" | "));
+    let _ = writeln!(md, "|{}|", align.iter().map(|a| format!(" {a} ")).collect::<Vec<_>>().join("|"));
+    for row in rows {
+        let _ = writeln!(md, "| {} |", row.join(" | "));
+    }
+}
+
+/// A table whose first rows are visible and the rest folded away.
+fn folded_table(md: &mut String, summary: &str, headers: &[&str], align: &[&str], rows: &[Vec<String>]) {
+    let visible = rows.len().min(VISIBLE_ROWS);
+    table(md, headers, align, &rows[..visible]);
+    if rows.len() > visible {
+        let rest = &rows[visible..rows.len().min(VISIBLE_ROWS + FOLDED_ROWS)];
+        let _ = writeln!(md, "\n<details>\n<summary>{summary} ({} more)</summary>\n", rest.len());
+        table(md, headers, align, rest);
+        md.push_str("\n</details>\n");
+    }
+}
+
+fn ms(value: Option<f64>) -> String {
+    value.map_or("–".to_string(), |v| format!("{v:.2}ms"))
+}
+
+/// Render the report over `entries` as Markdown.
+pub fn render(report: &Report, entries: &[LogEntry]) -> String {
+    let stats = report.stats;
+    let mut md = String::new();
+    md.push_str("# Log Analysis Report\n\n");
+    let _ = write!(md, "**Input:** {}", code(&report.input.path));
+    if let (Some(first), Some(last)) = (report.input.first_timestamp, report.input.last_timestamp) {
+        let _ = write!(
+            md,
+            " · {} → {}",
+            first.format("%Y-%m-%d %H:%M:%S"),
+            last.format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    let filters = &report.input.filters;
+    for (name, value) in [("where", &filters.query), ("since", &filters.since), ("until", &filters.until)] {
+        if let Some(value) = value {
+            let _ = write!(md, " · {name} {}", code(value));
+        }
+    }
+    md.push_str("\n\n");
+
+    summary(&mut md, stats);
+    performance(&mut md, stats);
+    top_endpoints(&mut md, stats);
+    error_analysis(&mut md, stats);
+    slowest_requests(&mut md, entries);
+
+    if let Some(findings) = report.findings {
+        md.push_str("## Security Findings\n\n");
+        if findings.is_empty() {
+            md.push_str("No suspicious activity detected.\n\n");
+        }
+        for finding in findings {
+            let ip = finding.source_ip.map(|ip| ip.to_string()).unwrap_or_else(|| "unknown".to_string());
+            let _ = writeln!(md, "- **{}** {} from {}: {}", finding.severity, finding.kind, code(&ip), inline(&finding.summary));
+            if !finding.evidence.is_empty() {
+                // The lines come from the log, so no backtick run in them may
+                // close the fence.
+                let lines: Vec<String> = finding
+                    .evidence
+                    .iter()
+                    .map(|e| format!("{:>6}: {}", e.line_number, e.line.replace(['\r', '\n'], " ")))
+                    .collect();
+                let longest = lines.iter().map(|l| longest_backtick_run(l)).max().unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                let _ = writeln!(md, "  <details>\n  <summary>Evidence</summary>\n\n  {fence}text");
+                for line in &lines {
+                    let _ = writeln!(md, "  {line}");
+                }
+                let _ = writeln!(md, "  {fence}\n  </details>");
+            }
+        }
+        md.push('\n');
+    }
+    if let Some(alerts) = report.alerts {
+        md.push_str("## Alerts\n\n");
+        if alerts.is_empty() {
+            md.push_str("No rules triggered.\n\n");
+        } else {
+            let rows: Vec<Vec<String>> = alerts
+                .iter()
+                .map(|a| {
+                    vec![
+                        a.severity.to_string(),
+                        cell(&a.rule),
+                        cell(a.group.as_deref().unwrap_or("")),
+                        code(&a.aggregate),
+                        format!("{:.2}", a.value),
+                        format!("{} {}", a.comparison, a.threshold),
+                    ]
+                })
+                .collect();
+            table(&mut md, &["Severity", "Rule", "Group", "Aggregate", "Value", "Threshold"], &[":--", ":--", ":--", ":--", "--:", "--:"], &rows);
+            md.push('\n');
+        }
+    }
+    if let Some(checks) = report.checks {
+        md.push_str("## CI Checks\n\n");
+        let rows: Vec<Vec<String>> = checks
+            .checks
+            .iter()
+            .map(|c| {
+                vec![
+                    if c.failed { "❌ FAIL" } else { "✅ PASS" }.to_string(),
+                    code(&c.check),
+                    format!("{:.2}", c.actual),
+                ]
+            })
+            .collect();
+        table(&mut md, &["Result", "Check", "Actual"], &[":--", ":--", "--:"], &rows);
+        md.push('\n');
+    }
+    if !report.parse_warnings.is_empty() {
+        let _ = writeln!(
+            md,
+            "<details>\n<summary>{} parse warning(s)</summary>\n",
+            report.parse_warnings.len()
+        );
+        let rows: Vec<Vec<String>> = report
+            .parse_warnings
+            .iter()
+            .take(FOLDED_ROWS)
+            .map(|w| vec![w.line_number.to_string(), cell(&w.error), code(&w.line_content)])
+            .collect();
+        table(&mut md, &["Line", "Error", "Content"], &["--:", ":--", ":--"], &rows);
+        md.push_str("\n</details>\n\n");
+    }
+
+    let _ = writeln!(md, "---\n_Generated by {} · report schema {}_", report.generator, report.schema_version);
+    md
+}
+
+fn summary(md: &mut String, stats: &LogStats) {
+    md.push_str("## Summary\n\n");
+    let pct = |count: usize| {
+        if stats.total_requests == 0 {
+            0.0
+        } else {
+            count as f64 / stats.total_requests as f64 * 100.0
+        }
+    };
+    let rows = vec![
+        vec!["**Total**".to_string(), stats.total_requests.to_string(), String::new()],
+        vec!["INFO".to_string(), stats.info_count.to_string(), format!("{:.1}%", pct(stats.info_count))],
+        vec!["WARNING".to_string(), stats.warning_count.to_string(), format!("{:.1}%", pct(stats.warning_count))],
+        vec!["ERROR".to_string(), stats.error_count.to_string(), format!("{:.1}%", pct(stats.error_count))],
+    ];
+    table(md, &["Level", "Count", "Share"], &[":--", "--:", "--:"], &rows);
+    let error_pct = pct(stats.error_count);
+    if error_pct > 5.0 {
+        let _ = write!(md, "\n> [!WARNING]\n> High error rate detected: {error_pct:.1}%\n");
+    } else if error_pct > 1.0 {
+        let _ = write!(md, "\n> [!NOTE]\n> Moderate error rate: {error_pct:.1}%\n");
+    }
+    md.push('\n');
+}
+
+fn performance(md: &mut String, stats: &LogStats) {
+    md.push_str("## Performance\n\n");
+    let avg = (stats.total_requests > 0).then_some(stats.avg_response_time);
+    let rows = vec![
+        vec!["Average".to_string(), ms(avg)],
+        vec!["P50".to_string(), ms(stats.response_time_percentile(50.0))],
+        vec!["P95".to_string(), ms(stats.response_time_percentile(95.0))],
+        vec!["P99".to_string(), ms(stats.response_time_percentile(99.0))],
+    ];
+    table(md, &["Metric", "Response time"], &[":--", "--:"], &rows);
+    md.push('\n');
+}
+
+fn sorted_counts<'a>(map: impl Iterator<Item = (&'a String, &'a usize)>) -> Vec<(&'a String, &'a usize)> {
+    let mut counts: Vec<_> = map.collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    counts
+}
+
+fn top_endpoints(md: &mut String, stats: &LogStats) {
+    md.push_str("## Top Endpoints by Request Count\n\n");
+    if stats.endpoint_frequency.is_empty() {
+        md.push_str("No endpoints found.\n\n");
+        return;
+    }
+    let rows: Vec<Vec<String>> = sorted_counts(stats.endpoint_frequency.iter())
+        .into_iter()
+        .enumerate()
+        .map(|(i, (endpoint, count))| vec![(i + 1).to_string(), code(endpoint), count.to_string()])
+        .collect();
+    folded_table(md, "All endpoints", &["#", "Endpoint", "Count"], &["--:", ":--", "--:"], &rows);
+    md.push('\n');
+}
+
+fn error_analysis(md: &mut String, stats: &LogStats) {
+    md.push_str("## Error Analysis\n\n");
+    if stats.errors_by_endpoint.is_empty() {
+        md.push_str("✅ No errors detected.\n\n");
+        return;
+    }
+    let rows: Vec<Vec<String>> = sorted_counts(stats.errors_by_endpoint.iter())
+        .into_iter()
+        .enumerate()
+        .map(|(i, (endpoint, count))| vec![(i + 1).to_string(), code(endpoint), count.to_string()])
+        .collect();
+    folded_table(md, "All endpoints with errors", &["#", "Endpoint", "Errors"], &["--:", ":--", "--:"], &rows);
+    md.push('\n');
+}
+
+fn slowest_requests(md: &mut String, entries: &[LogEntry]) {
+    md.push_str("## Slowest Requests\n\n");
+    let slowest = slowest_first(entries);
+    if slowest.is_empty() {
+        md.push_str("No response times found.\n\n");
+        return;
+    }
+    let rows: Vec<Vec<String>> = slowest
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            vec![
+                (i + 1).to_string(),
+                code(e.endpoint.as_deref().unwrap_or("")),
+                e.method.as_ref().map(|m| m.to_string()).unwrap_or_default(),
+                ms(e.response_time),
+                e.status_code.map(|s| s.to_string()).unwrap_or_default(),
+                e.line_number.to_string(),
+            ]
+        })
+        .collect();
+    folded_table(
+        md,
+        "More slow requests",
+        &["#", "Endpoint", "Method", "Time", "Status", "Line"],
+        &["--:", ":--", ":--", "--:", "--:", "--:"],
+        &rows,
+    );
+    md.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_escape_pipes_and_newlines() {
+        assert_eq!(cell("a|b"), "a\\|b");
+        assert_eq!(cell("line one\r\nline two"), "line one  line two");
+    }
+
+    #[test]
+    fn code_spans_outlast_backticks_in_the_text() {
+        assert_eq!(code(""), "");
+        assert_eq!(code("/api/users"), "`/api/users`");
+        assert_eq!(code("a`b"), "``a`b``");
+        assert_eq!(code("a``b"), "```a``b```");
+        assert_eq!(code("`x`"), "`` `x` ``");
+        assert_eq!(code("x`"), "`` x` ``");
+        assert_eq!(code(" x "), "`  x  `");
+        assert_eq!(code("a|b"), "`a\\|b`");
+    }
+
+    #[test]
+    fn evidence_fences_outlast_backticks_in_the_log() {
+        use crate::log_analyzer::{LogStats, ParseWarning};
+        use crate::report::{AppliedFilters, InputMetadata};
+        use crate::security::SecurityAnalyzer;
+        use std::path::Path;
+
+        let line = "2024-01-15 08:15:01.000 INFO 10.0.0.1 GET /a/../../etc/passwd?x=````<b>hi</b> 404 5ms";
+        let entries = vec![LogEntry::parse_log(line).unwrap()];
+        let stats = LogStats::from_entries(&entries);
+        let findings = SecurityAnalyzer::default().analyze(&entries);
+        assert_eq!(findings.len(), 1);
+        let warnings = [ParseWarning { line_number: 2, line_content: "```".to_string(), error: "bad | line".to_string() }];
+        let input = InputMetadata::new(Path::new("server.log"), 1, &entries, AppliedFilters::default());
+        let mut report = Report::new(input, &stats, &entries, &warnings);
+        report.findings = Some(&findings);
+        let md = render(&report, &entries);
+        assert!(md.contains("  `````text\n"), "{md}");
+        assert!(md.contains("````<b>hi</b> 404 5ms\n  `````\n  </details>\n"), "{md}");
+        assert!(md.contains("| 2 | bad \\| line | ```` ``` ```` |"), "{md}");
+    }
+
+    #[test]
+    fn finding_summaries_are_escaped() {
+        assert_eq!(inline("a|b *c* <i>_d_</i>\n[e](f) \\"), "a\\|b \\*c\\* \\<i\\>\\_d\\_\\</i\\> \\[e\\](f) \\\\");
+
+        use crate::log_analyzer::LogStats;
+        use crate::report::{AppliedFilters, InputMetadata};
+        use crate::security::{Finding, FindingKind, Severity};
+        use std::path::Path;
+
+        let entries = vec![LogEntry::parse_log("2024-01-15 08:15:01.000 INFO 10.0.0.1 GET /a 200 5ms").unwrap()];
+        let stats = LogStats::from_entries(&entries);
+        let findings = [Finding {
+            kind: FindingKind::SuspiciousPayload,
+            severity: Severity::High,
+            source_ip: None,
+            summary: "1 request(s) with <script> | markers".to_string(),
+            evidence: Vec::new(),
+        }];
+        let input = InputMetadata::new(Path::new("server.log"), 1, &entries, AppliedFilters::default());
+        let mut report = Report::new(input, &stats, &entries, &[]);
+        report.findings = Some(&findings);
+        let md = render(&report, &entries);
+        assert!(md.contains(": 1 request(s) with \\<script\\> \\| markers\n"), "{md}");
+    }
+
+    #[test]
+    fn long_tables_fold_into_details() {
+        let rows: Vec<Vec<String>> = (0..VISIBLE_ROWS + FOLDED_ROWS + 5).map(|i| vec![i.to_string()]).collect();
+        let mut md = String::new();
+        folded_table(&mut md, "All rows", &["#"], &["--:"], &rows);
+        let (visible, folded) = md.split_once("<details>").unwrap();
+        assert_eq!(visible.lines().filter(|l| l.starts_with("| ")).count(), VISIBLE_ROWS + 2);
+        assert!(folded.starts_with("\n<summary>All rows (100 more)</summary>\n"), "{folded}");
+        assert_eq!(folded.lines().filter(|l| l.starts_with("| ")).count(), FOLDED_ROWS + 2);
+        assert!(folded.ends_with("</details>\n"));
+
+        let mut md = String::new();
+        folded_table(&mut md, "All rows", &["#"], &["--:"], &rows[..VISIBLE_ROWS]);
+        assert!(!md.contains("<details>"));
+    }
+}
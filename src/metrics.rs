@@ -0,0 +1,289 @@
+//! Prometheus text and OpenMetrics exposition of the report, for feeding
+//! batch analysis into a metrics stack (e.g. node_exporter's textfile
+//! collector).
+//!
+//! Metric names share the `loggaliza_` prefix; durations are in seconds, as
+//! Prometheus conventions require, even though the logs record milliseconds.
+
+use crate::log_analyzer::{AnalyzerError, LogEntry};
+use crate::report::Report;
+use chrono::Duration;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const PREFIX: &str = "loggaliza";
+
+/// Upper bounds of the latency histogram when none are given, in seconds
+/// (the Prometheus client defaults).
+pub const DEFAULT_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// The two text formats differ in how counters are declared, in `# UNIT`
+/// metadata and in the closing `# EOF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exposition {
+    Prometheus,
+    OpenMetrics,
+}
+
+/// Histogram upper bounds in seconds, sorted and deduplicated; `durations`
+/// empty means [`DEFAULT_BUCKETS`]. Response times are logged in
+/// milliseconds, so bounds must be whole positive milliseconds.
+pub fn buckets(durations: &[Duration]) -> Result<Vec<f64>, AnalyzerError> {
+    if durations.is_empty() {
+        return Ok(DEFAULT_BUCKETS.to_vec());
+    }
+    let mut bounds = Vec::with_capacity(durations.len());
+    for duration in durations {
+        let millis = duration.num_milliseconds();
+        if millis <= 0 || Duration::try_milliseconds(millis) != Some(*duration) {
+            let shown = duration.num_microseconds().map_or(millis as f64, |us| us as f64 / 1000.0);
+            return Err(AnalyzerError::OutputError(format!(
+                "histogram buckets must be whole milliseconds of at least 1ms, got {shown}ms"
+            )));
+        }
+        bounds.push(millis as f64 / 1000.0);
+    }
+    bounds.sort_by(|a, b| a.total_cmp(b));
+    bounds.dedup();
+    Ok(bounds)
+}
+
+/// Escape a label value: backslash, double quote and line feed.
+fn label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    if pairs.is_empty() {
+        return String::new();
+    }
+    let inner: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| format!("{name}=\"{}\"", label_value(value)))
+        .collect();
+    format!("{{{}}}", inner.join(","))
+}
+
+/// Shortest float representation, with `+Inf` for infinity.
+fn number(value: f64) -> String {
+    if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        format!("{value}")
+    }
+}
+
+struct Writer {
+    out: String,
+    exposition: Exposition,
+}
+
+impl Writer {
+    /// `# HELP`/`# TYPE` (and `# UNIT`) lines of a metric family. Returns the
+    /// sample name for counters, which gains `_total`.
+    fn family(&mut self, name: &str, kind: &str, unit: Option<&str>, help: &str) -> String {
+        let counter = kind == "counter";
+        let declared = match self.exposition {
+            Exposition::Prometheus if counter => format!("{name}_total"),
+            _ => name.to_string(),
+        };
+        let _ = writeln!(self.out, "# HELP {declared} {help}");
+        let _ = writeln!(self.out, "# TYPE {declared} {kind}");
+        if let (Exposition::OpenMetrics, Some(unit)) = (self.exposition, unit) {
+            let _ = writeln!(self.out, "# UNIT {declared} {unit}");
+        }
+        if counter { format!("{name}_total") } else { name.to_string() }
+    }
+
+    fn sample(&mut self, name: &str, labels: &str, value: f64) {
+        let _ = writeln!(self.out, "{name}{labels} {}", number(value));
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, bounds: &[f64], seconds: f64) {
+        if self.counts.is_empty() {
+            self.counts = vec![0; bounds.len()];
+        }
+        for (bound, count) in bounds.iter().zip(&mut self.counts) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+fn text(value: Option<String>) -> String {
+    value.unwrap_or_default()
+}
+
+/// Render the metrics of the report over `entries`. Histogram `bounds` are
+/// in seconds.
+pub fn render(report: &Report, entries: &[LogEntry], bounds: &[f64], exposition: Exposition) -> String {
+    let mut w = Writer { out: String::new(), exposition };
+
+    let mut requests: BTreeMap<[String; 4], u64> = BTreeMap::new();
+    let mut latency: BTreeMap<String, Histogram> = BTreeMap::new();
+    for entry in entries {
+        let key = [
+            text(entry.endpoint.clone()),
+            text(entry.method.as_ref().map(|m| m.to_string())),
+            text(entry.status_code.map(|s| s.to_string())),
+            text(entry.level.as_ref().map(|l| l.to_string())),
+        ];
+        *requests.entry(key).or_default() += 1;
+        if let Some(ms) = entry.response_time {
+            latency
+                .entry(text(entry.endpoint.clone()))
+                .or_default()
+                .observe(bounds, ms / 1000.0);
+        }
+    }
+
+    let name = w.family(
+        &format!("{PREFIX}_requests"),
+        "counter",
+        None,
+        "Log entries by endpoint, method, status and level.",
+    );
+    for ([endpoint, method, status, level], count) in &requests {
+        let labels = labels(&[("endpoint", endpoint), ("method", method), ("status", status), ("level", level)]);
+        w.sample(&name, &labels, *count as f64);
+    }
+
+    let name = w.family(
+        &format!("{PREFIX}_request_duration_seconds"),
+        "histogram",
+        Some("seconds"),
+        "Response times by endpoint.",
+    );
+    for (endpoint, histogram) in &latency {
+        for (bound, count) in bounds.iter().zip(&histogram.counts) {
+            let labels = labels(&[("endpoint", endpoint), ("le", &number(*bound))]);
+            w.sample(&format!("{name}_bucket"), &labels, *count as f64);
+        }
+        let labels_inf = labels(&[("endpoint", endpoint), ("le", "+Inf")]);
+        w.sample(&format!("{name}_bucket"), &labels_inf, histogram.count as f64);
+        let endpoint_labels = labels(&[("endpoint", endpoint)]);
+        w.sample(&format!("{name}_sum"), &endpoint_labels, histogram.sum);
+        w.sample(&format!("{name}_count"), &endpoint_labels, histogram.count as f64);
+    }
+
+    let name = w.family(
+        &format!("{PREFIX}_entries_parsed"),
+        "counter",
+        None,
+        "Entries parsed from the input, before filtering.",
+    );
+    w.sample(&name, "", report.input.entries_parsed as f64);
+
+    let name = w.family(
+        &format!("{PREFIX}_parse_warnings"),
+        "counter",
+        None,
+        "Lines that could not be parsed.",
+    );
+    w.sample(&name, "", report.parse_warnings.len() as f64);
+
+    if let Some(last) = report.input.last_timestamp {
+        let name = w.family(
+            &format!("{PREFIX}_last_entry_timestamp_seconds"),
+            "gauge",
+            Some("seconds"),
+            "Unix time of the newest analyzed entry.",
+        );
+        w.sample(&name, "", last.and_utc().timestamp_millis() as f64 / 1000.0);
+    }
+
+    if exposition == Exposition::OpenMetrics {
+        w.out.push_str("# EOF\n");
+    }
+    w.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_analyzer::LogStats;
+    use crate::report::{AppliedFilters, InputMetadata};
+
+    #[test]
+    fn buckets_are_sorted_whole_milliseconds() {
+        assert_eq!(buckets(&[]).unwrap(), DEFAULT_BUCKETS);
+        let durations = [Duration::seconds(1), Duration::milliseconds(50), Duration::milliseconds(1000)];
+        assert_eq!(buckets(&durations).unwrap(), [0.05, 1.0]);
+        let invalid = [
+            (Duration::zero(), "0ms"),
+            (Duration::microseconds(500), "0.5ms"),
+            (Duration::microseconds(1500), "1.5ms"),
+        ];
+        for (duration, shown) in invalid {
+            let err = buckets(&[Duration::seconds(1), duration]).unwrap_err().to_string();
+            assert!(err.ends_with(&format!("got {shown}")), "{err}");
+        }
+        assert!(buckets(&[Duration::milliseconds(-5)]).is_err());
+    }
+
+    fn render_lines(lines: &[&str], exposition: Exposition) -> String {
+        let entries: Vec<LogEntry> = lines.iter().map(|line| LogEntry::parse_log(line).unwrap()).collect();
+        let stats = LogStats::from_entries(&entries);
+        let input = InputMetadata::new(std::path::Path::new("server.log"), entries.len(), &entries, AppliedFilters::default());
+        let report = Report::new(input, &stats, &entries, &[]);
+        render(&report, &entries, &[0.05, 0.5], exposition)
+    }
+
+    const LINES: [&str; 3] = [
+        "2024-01-15 08:15:01.000 INFO 10.0.0.1 GET /api/users 200 40ms",
+        "2024-01-15 08:15:02.000 INFO 10.0.0.1 GET /api/users 200 50ms",
+        "2024-01-15 08:15:03.000 ERROR 10.0.0.1 GET /api/users 500 900ms",
+    ];
+
+    #[test]
+    fn counters_are_declared_per_exposition() {
+        let prometheus = render_lines(&LINES, Exposition::Prometheus);
+        assert!(prometheus.contains("# TYPE loggaliza_requests_total counter\n"));
+        assert!(prometheus.contains("\nloggaliza_requests_total{endpoint=\"/api/users\",method=\"GET\",status=\"200\",level=\"INFO\"} 2\n"));
+        assert!(prometheus.contains("# TYPE loggaliza_entries_parsed_total counter\nloggaliza_entries_parsed_total 3\n"));
+        assert!(!prometheus.contains("# UNIT"));
+        assert!(!prometheus.contains("# EOF"));
+
+        let openmetrics = render_lines(&LINES, Exposition::OpenMetrics);
+        assert!(openmetrics.contains("# TYPE loggaliza_requests counter\n"));
+        assert!(openmetrics.contains("\nloggaliza_requests_total{endpoint=\"/api/users\""));
+        assert!(openmetrics.contains("# TYPE loggaliza_request_duration_seconds histogram\n# UNIT loggaliza_request_duration_seconds seconds\n"));
+        assert!(openmetrics.contains("# UNIT loggaliza_last_entry_timestamp_seconds seconds\nloggaliza_last_entry_timestamp_seconds 1705306503\n"));
+        assert!(openmetrics.ends_with("\n# EOF\n"));
+        assert_eq!(openmetrics.matches("# EOF").count(), 1);
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let rendered = render_lines(&LINES, Exposition::Prometheus);
+        let samples: Vec<&str> = rendered.lines().filter(|l| l.starts_with("loggaliza_request_duration_seconds")).collect();
+        assert_eq!(samples, [
+            "loggaliza_request_duration_seconds_bucket{endpoint=\"/api/users\",le=\"0.05\"} 2",
+            "loggaliza_request_duration_seconds_bucket{endpoint=\"/api/users\",le=\"0.5\"} 2",
+            "loggaliza_request_duration_seconds_bucket{endpoint=\"/api/users\",le=\"+Inf\"} 3",
+            "loggaliza_request_duration_seconds_sum{endpoint=\"/api/users\"} 0.99",
+            "loggaliza_request_duration_seconds_count{endpoint=\"/api/users\"} 3",
+        ]);
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(label_value("a\\b\"c\nd"), "a\\\\b\\\"c\\nd");
+        let rendered = render_lines(&[r#"{"path":"/a\\b\"c\nd","response_time":5}"#], Exposition::Prometheus);
+        assert!(rendered.contains("{endpoint=\"/a\\\\b\\\"c\\nd\",method=\"\",status=\"\",level=\"\"} 1\n"), "{rendered}");
+        assert!(rendered.contains("_bucket{endpoint=\"/a\\\\b\\\"c\\nd\",le=\"0.05\"} 1\n"), "{rendered}");
+        assert!(rendered.lines().all(|line| !line.ends_with('\\')));
+    }
+}
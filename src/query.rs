@@ -0,0 +1,714 @@
+//! A small expression language for selecting log entries, e.g.
+//!
+//! ```text
+//! level >= WARNING and status in 500..599 and endpoint ~ "^/api/orders"
+//!     and not ip in 10.0.0.0/8
+//! ```
+//!
+//! Queries are parsed into a typed [`Expr`] up front, so type errors such as
+//! `status > WARNING` are reported with the offending token before any entry is
+//! evaluated.
+
+use crate::filter::{Cidr, Filter, parse_level};
+use crate::log_analyzer::{LogEntry, LogLevel, LogMethod};
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::{
+    fmt::{self, Display, Formatter},
+    net::IpAddr,
+    str::FromStr,
+};
+
+/// A parse error pointing at the offending part of the query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    pub query: String,
+    /// Byte offset of the offending token.
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let column = self.query[..self.position].chars().count();
+        writeln!(f, "{} at column {}", self.message, column + 1)?;
+        writeln!(f, "  {}", self.query)?;
+        write!(f, "  {}^", " ".repeat(column))
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Timestamp,
+    Level,
+    Ip,
+    Method,
+    Endpoint,
+    Status,
+    ResponseTime,
+    Message,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "timestamp" | "time" | "ts" => Some(Field::Timestamp),
+            "level" => Some(Field::Level),
+            "ip" | "ip_address" => Some(Field::Ip),
+            "method" => Some(Field::Method),
+            "endpoint" | "path" => Some(Field::Endpoint),
+            "status" | "status_code" => Some(Field::Status),
+            "response_time" | "duration" | "latency" => Some(Field::ResponseTime),
+            "message" | "msg" => Some(Field::Message),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Field::Timestamp => "timestamp",
+            Field::Level => "level",
+            Field::Ip => "ip",
+            Field::Method => "method",
+            Field::Endpoint => "endpoint",
+            Field::Status => "status",
+            Field::ResponseTime => "response_time",
+            Field::Message => "message",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Regex match (`~`).
+    Match,
+    /// Negated regex match (`!~`).
+    NotMatch,
+    /// Range or CIDR membership.
+    In,
+}
+
+impl Op {
+    fn compare<T: PartialOrd>(&self, actual: &T, expected: &T) -> bool {
+        match self {
+            Op::Eq => actual == expected,
+            Op::Ne => actual != expected,
+            Op::Lt => actual < expected,
+            Op::Le => actual <= expected,
+            Op::Gt => actual > expected,
+            Op::Ge => actual >= expected,
+            Op::Match | Op::NotMatch | Op::In => false,
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+            Op::In => "in",
+        };
+        write!(f, "{op}")
+    }
+}
+
+/// A comparison value, already converted to the type of its field.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Level(LogLevel),
+    Method(LogMethod),
+    Number(f64),
+    Range(f64, f64),
+    Text(String),
+    Regex(Regex),
+    Ip(IpAddr),
+    Cidr(Cidr),
+    Timestamp(NaiveDateTime),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare { field: Field, op: Op, value: Value },
+}
+
+impl Expr {
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            query,
+            tokens,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(parser.error_at(token.start, format!("unexpected '{}'", token.text))),
+        }
+    }
+
+    /// Evaluate the expression. Comparisons on a field the entry does not
+    /// have are false.
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        match self {
+            Expr::And(lhs, rhs) => lhs.matches(entry) && rhs.matches(entry),
+            Expr::Or(lhs, rhs) => lhs.matches(entry) || rhs.matches(entry),
+            Expr::Not(inner) => !inner.matches(entry),
+            Expr::Compare { field, op, value } => compare(entry, *field, *op, value),
+        }
+    }
+
+    /// Patterns for the values this query positively matches on, for
+    /// highlighting matched fields in raw lines. Negated comparisons and
+    /// ranges contribute nothing.
+    pub fn highlight_patterns(&self) -> Vec<Regex> {
+        let mut patterns = Vec::new();
+        self.collect_highlights(false, &mut patterns);
+        patterns
+    }
+
+    fn collect_highlights(&self, negated: bool, patterns: &mut Vec<Regex>) {
+        match self {
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+                lhs.collect_highlights(negated, patterns);
+                rhs.collect_highlights(negated, patterns);
+            }
+            Expr::Not(inner) => inner.collect_highlights(!negated, patterns),
+            Expr::Compare { op, value, .. } if !negated && matches!(op, Op::Eq | Op::Match | Op::In) => {
+                let literal = match value {
+                    Value::Regex(re) => {
+                        patterns.push(re.clone());
+                        return;
+                    }
+                    Value::Level(level) => level.to_string(),
+                    Value::Method(method) => method.to_string(),
+                    Value::Text(text) => text.clone(),
+                    Value::Ip(ip) => ip.to_string(),
+                    Value::Number(n) if n.fract() == 0.0 => (*n as i64).to_string(),
+                    _ => return,
+                };
+                // Only anchor at word boundaries where the literal has word characters.
+                let boundary = |c: Option<char>| if c.is_some_and(|c| c.is_alphanumeric()) { r"\b" } else { "" };
+                let pattern = format!(
+                    "{}{}{}",
+                    boundary(literal.chars().next()),
+                    regex::escape(&literal),
+                    boundary(literal.chars().last())
+                );
+                if let Ok(re) = Regex::new(&pattern) {
+                    patterns.push(re);
+                }
+            }
+            Expr::Compare { .. } => {}
+        }
+    }
+}
+
+impl Filter for Expr {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        Expr::matches(self, entry)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Expr::parse(s)
+    }
+}
+
+fn compare(entry: &LogEntry, field: Field, op: Op, value: &Value) -> bool {
+    match (field, value) {
+        (Field::Level, Value::Level(expected)) => {
+            entry.level.as_ref().is_some_and(|l| op.compare(l, expected))
+        }
+        (Field::Method, Value::Method(expected)) => entry
+            .method
+            .as_ref()
+            .is_some_and(|m| op.compare(&m.to_string(), &expected.to_string())),
+        (Field::Status, _) => entry
+            .status_code
+            .is_some_and(|s| compare_number(f64::from(s), op, value)),
+        (Field::ResponseTime, _) => entry
+            .response_time
+            .is_some_and(|t| compare_number(t, op, value)),
+        (Field::Endpoint, _) => entry
+            .endpoint
+            .as_deref()
+            .is_some_and(|e| compare_text(e, op, value)),
+        (Field::Message, _) => entry
+            .message
+            .as_deref()
+            .is_some_and(|m| compare_text(m, op, value)),
+        (Field::Ip, Value::Ip(expected)) => entry.ip_address.is_some_and(|ip| op.compare(&ip, expected)),
+        (Field::Ip, Value::Cidr(cidr)) => entry.ip_address.is_some_and(|ip| cidr.contains(&ip)),
+        (Field::Timestamp, Value::Timestamp(expected)) => entry
+            .parsed_timestamp()
+            .is_some_and(|ts| op.compare(&ts, expected)),
+        _ => false,
+    }
+}
+
+fn compare_number(actual: f64, op: Op, value: &Value) -> bool {
+    match value {
+        Value::Number(expected) => op.compare(&actual, expected),
+        Value::Range(low, high) => (*low..=*high).contains(&actual),
+        _ => false,
+    }
+}
+
+fn compare_text(actual: &str, op: Op, value: &Value) -> bool {
+    match (op, value) {
+        (Op::Match, Value::Regex(re)) => re.is_match(actual),
+        (Op::NotMatch, Value::Regex(re)) => !re.is_match(actual),
+        (_, Value::Text(expected)) => op.compare(&actual, &expected.as_str()),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word,
+    Str,
+    Op,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    start: usize,
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let error = |position: usize, message: &str| QueryError {
+        query: query.to_string(),
+        position,
+        message: message.to_string(),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(Token {
+                    kind: if c == '(' { TokenKind::LParen } else { TokenKind::RParen },
+                    text: c.to_string(),
+                    start,
+                });
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                let mut closed = false;
+                while let Some((_, ch)) = chars.next() {
+                    match ch {
+                        '\\' => match chars.next() {
+                            Some((_, escaped)) if escaped == c || escaped == '\\' => text.push(escaped),
+                            Some((_, escaped)) => {
+                                text.push('\\');
+                                text.push(escaped);
+                            }
+                            None => break,
+                        },
+                        ch if ch == c => {
+                            closed = true;
+                            break;
+                        }
+                        ch => text.push(ch),
+                    }
+                }
+                if !closed {
+                    return Err(error(start, "unterminated string"));
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Str,
+                    text,
+                    start,
+                });
+            }
+            '=' | '!' | '<' | '>' | '~' => {
+                chars.next();
+                let mut op = c.to_string();
+                if let Some(&(_, next)) = chars.peek()
+                    && matches!((c, next), ('=', '=') | ('!', '=') | ('!', '~') | ('<', '=') | ('>', '='))
+                {
+                    op.push(next);
+                    chars.next();
+                }
+                if op == "!" {
+                    return Err(error(start, "expected '!=' or '!~'"));
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Op,
+                    text: op,
+                    start,
+                });
+            }
+            _ => {
+                let mut text = String::new();
+                while let Some(&(_, ch)) = chars.peek() {
+                    if ch.is_whitespace() || "()\"'=!<>~".contains(ch) {
+                        break;
+                    }
+                    text.push(ch);
+                    chars.next();
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Word,
+                    text,
+                    start,
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    query: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error_at(&self, position: usize, message: String) -> QueryError {
+        QueryError {
+            query: self.query.to_string(),
+            position,
+            message,
+        }
+    }
+
+    fn error_at_end(&self, message: String) -> QueryError {
+        self.error_at(self.query.len(), message)
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| t.kind == TokenKind::Word && t.text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut lhs = self.parse_and()?;
+        while self.at_keyword("or") {
+            self.next();
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut lhs = self.parse_unary()?;
+        while self.at_keyword("and") {
+            self.next();
+            let rhs = self.parse_unary()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        if self.at_keyword("not") {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        let token = self
+            .next()
+            .ok_or_else(|| self.error_at_end("expected a field name".to_string()))?;
+        match token.kind {
+            TokenKind::LParen => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(t) if t.kind == TokenKind::RParen => Ok(expr),
+                    Some(t) => Err(self.error_at(t.start, format!("expected ')', found '{}'", t.text))),
+                    None => Err(self.error_at_end("missing closing ')'".to_string())),
+                }
+            }
+            TokenKind::Word => self.parse_comparison(token),
+            _ => Err(self.error_at(token.start, format!("expected a field name, found '{}'", token.text))),
+        }
+    }
+
+    fn parse_comparison(&mut self, field_token: Token) -> Result<Expr, QueryError> {
+        let field = Field::parse(&field_token.text).ok_or_else(|| {
+            self.error_at(field_token.start, format!("unknown field '{}'", field_token.text))
+        })?;
+
+        let op_token = self
+            .next()
+            .ok_or_else(|| self.error_at_end(format!("expected an operator after '{field}'")))?;
+        let op = match (op_token.kind.clone(), op_token.text.to_lowercase().as_str()) {
+            (TokenKind::Op, "=" | "==") => Op::Eq,
+            (TokenKind::Op, "!=") => Op::Ne,
+            (TokenKind::Op, "<") => Op::Lt,
+            (TokenKind::Op, "<=") => Op::Le,
+            (TokenKind::Op, ">") => Op::Gt,
+            (TokenKind::Op, ">=") => Op::Ge,
+            (TokenKind::Op, "~") => Op::Match,
+            (TokenKind::Op, "!~") => Op::NotMatch,
+            (TokenKind::Word, "in") => Op::In,
+            _ => {
+                return Err(self.error_at(
+                    op_token.start,
+                    format!("expected an operator, found '{}'", op_token.text),
+                ));
+            }
+        };
+
+        let value_token = match self.next() {
+            Some(t) if t.kind == TokenKind::Str => t,
+            Some(t)
+                if t.kind == TokenKind::Word
+                    && !["and", "or", "not"].iter().any(|k| t.text.eq_ignore_ascii_case(k)) =>
+            {
+                t
+            }
+            Some(t) => {
+                return Err(self.error_at(
+                    t.start,
+                    format!("expected a value after '{}', found '{}'", op_token.text, t.text),
+                ));
+            }
+            None => return Err(self.error_at_end(format!("expected a value after '{}'", op_token.text))),
+        };
+        let value = self.parse_value(field, op, &value_token)?;
+        Ok(Expr::Compare { field, op, value })
+    }
+
+    fn parse_value(&self, field: Field, op: Op, token: &Token) -> Result<Value, QueryError> {
+        let text = token.text.as_str();
+        let invalid = |message: String| self.error_at(token.start, message);
+        let unsupported = || {
+            self.error_at(
+                token.start,
+                format!("operator '{op}' is not supported for field '{field}'"),
+            )
+        };
+
+        match field {
+            Field::Level => {
+                if matches!(op, Op::Match | Op::NotMatch | Op::In) {
+                    return Err(unsupported());
+                }
+                parse_level(text)
+                    .map(Value::Level)
+                    .map_err(|_| invalid(format!("unknown level '{text}' (expected INFO, WARNING or ERROR)")))
+            }
+            Field::Method => {
+                if !matches!(op, Op::Eq | Op::Ne) {
+                    return Err(unsupported());
+                }
+                text.to_uppercase()
+                    .parse::<LogMethod>()
+                    .map(Value::Method)
+                    .map_err(|_| invalid(format!("unknown HTTP method '{text}'")))
+            }
+            Field::Status | Field::ResponseTime => match op {
+                Op::Match | Op::NotMatch => Err(unsupported()),
+                Op::In => parse_range(text)
+                    .map(|(low, high)| Value::Range(low, high))
+                    .ok_or_else(|| invalid(format!("expected a range like 500..599, found '{text}'"))),
+                _ if field == Field::Status && text.len() == 3 && text.to_lowercase().ends_with("xx") => {
+                    let class: f64 = text[..1]
+                        .parse()
+                        .ok()
+                        .filter(|class| (1.0..=9.0).contains(class))
+                        .ok_or_else(|| invalid(format!("invalid status class '{text}' (expected 1xx to 9xx)")))?;
+                    match op {
+                        Op::Eq => Ok(Value::Range(class * 100.0, class * 100.0 + 99.0)),
+                        _ => Err(invalid(format!("status class '{text}' only supports '='"))),
+                    }
+                }
+                _ => parse_number(text)
+                    .map(Value::Number)
+                    .ok_or_else(|| invalid(format!("expected a number, found '{text}'"))),
+            },
+            Field::Endpoint | Field::Message => match op {
+                Op::Match | Op::NotMatch => Regex::new(text)
+                    .map(Value::Regex)
+                    .map_err(|e| invalid(format!("invalid regex: {e}"))),
+                Op::In => Err(unsupported()),
+                _ => Ok(Value::Text(text.to_string())),
+            },
+            Field::Ip => match op {
+                Op::In => text.parse::<Cidr>().map(Value::Cidr).map_err(invalid),
+                Op::Eq | Op::Ne => text
+                    .parse::<IpAddr>()
+                    .map(Value::Ip)
+                    .map_err(|_| invalid(format!("invalid IP address '{text}'"))),
+                _ => Err(unsupported()),
+            },
+            Field::Timestamp => {
+                if matches!(op, Op::Match | Op::NotMatch | Op::In) {
+                    return Err(unsupported());
+                }
+                parse_timestamp(text)
+                    .map(Value::Timestamp)
+                    .ok_or_else(|| invalid(format!("invalid timestamp '{text}'")))
+            }
+        }
+    }
+}
+
+fn parse_range(text: &str) -> Option<(f64, f64)> {
+    let (low, high) = text.split_once("..")?;
+    let low = parse_number(low)?;
+    let high = parse_number(high.strip_prefix('=').unwrap_or(high))?;
+    (low <= high).then_some((low, high))
+}
+
+/// Numbers may carry a `ms` or `s` suffix; seconds are converted to
+/// milliseconds to match `response_time`.
+fn parse_number(text: &str) -> Option<f64> {
+    if let Some(ms) = text.strip_suffix("ms") {
+        return ms.parse().ok();
+    }
+    if let Some(s) = text.strip_suffix('s') {
+        return s.parse::<f64>().ok().map(|s| s * 1000.0);
+    }
+    text.parse().ok()
+}
+
+fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim_end_matches('Z').replace('T', " ");
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(&text, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: &str) -> LogEntry {
+        LogEntry::parse_log(line).unwrap()
+    }
+
+    fn matches(query: &str, line: &str) -> bool {
+        Expr::parse(query).unwrap().matches(&entry(line))
+    }
+
+    const ORDER_FAILED: &str = "2024-01-15 08:15:25.891 ERROR 10.1.2.3 POST /api/orders 503 1200ms";
+    const USERS_OK: &str = "2024-01-15 08:15:23.145 INFO 192.168.1.105 GET /api/users 200 45ms";
+    const NO_REQUEST: &str = r#"{"level": "warn", "message": "Redis Client Reconnecting"}"#;
+
+    #[test]
+    fn level_comparisons_accept_warn() {
+        assert!(matches("level >= warn", ORDER_FAILED));
+        assert!(!matches("level >= WARNING", USERS_OK));
+        assert!(matches("level = WARN", NO_REQUEST));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let query = "status = 200 or status = 503 and method = GET";
+        assert!(matches(query, USERS_OK));
+        assert!(!matches(query, ORDER_FAILED));
+        assert!(matches("(status = 200 or status = 503) and method = POST", ORDER_FAILED));
+        assert!(matches("not status = 200 and not level = INFO", ORDER_FAILED));
+    }
+
+    #[test]
+    fn ranges_classes_and_units() {
+        assert!(matches("status in 500..599", ORDER_FAILED));
+        assert!(matches("status = 5xx", ORDER_FAILED));
+        assert!(!matches("status = 5xx", USERS_OK));
+        assert!(matches("response_time > 1s", ORDER_FAILED));
+        assert!(!matches("response_time > 1.5s", ORDER_FAILED));
+        assert!(matches("response_time <= 45ms", USERS_OK));
+    }
+
+    #[test]
+    fn text_regex_ip_and_timestamp() {
+        assert!(matches(r#"endpoint ~ "^/api/ord""#, ORDER_FAILED));
+        assert!(matches("endpoint !~ orders", USERS_OK));
+        assert!(matches("ip in 10.0.0.0/8", ORDER_FAILED));
+        assert!(!matches("not ip in 10.0.0.0/8", ORDER_FAILED));
+        assert!(matches("ip = 192.168.1.105", USERS_OK));
+        assert!(matches(r#"timestamp > "2024-01-15 08:15:24""#, ORDER_FAILED));
+        assert!(!matches("timestamp >= 2024-01-16", ORDER_FAILED));
+    }
+
+    #[test]
+    fn missing_fields_never_match() {
+        assert!(!matches("status != 200", NO_REQUEST));
+        assert!(!matches("response_time < 1000", NO_REQUEST));
+        assert!(matches("not status = 200", NO_REQUEST));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        let cases = [
+            ("stauts = 200", 0, "unknown field 'stauts'"),
+            ("status > WARNING", 9, "expected a number"),
+            ("level ~ ERR", 8, "operator '~' is not supported for field 'level'"),
+            ("status in 599..500", 10, "expected a range"),
+            ("status > 5xx", 9, "only supports '='"),
+            ("status = 0xx", 9, "invalid status class '0xx'"),
+            ("status = Axx", 9, "invalid status class 'Axx'"),
+            ("status = 200 and", 16, "expected a field name"),
+            ("(status = 200", 13, "missing closing ')'"),
+            ("status = 200 ip = 1.2.3.4", 13, "unexpected 'ip'"),
+        ];
+        for (query, position, message) in cases {
+            let err = Expr::parse(query).unwrap_err();
+            assert_eq!(err.position, position, "{query}: {err}");
+            assert!(err.message.contains(message), "{query}: {err}");
+        }
+    }
+
+    #[test]
+    fn highlights_skip_negated_comparisons() {
+        let expr = Expr::parse("method = POST and not endpoint = /health and status in 500..599").unwrap();
+        let patterns: Vec<String> = expr.highlight_patterns().iter().map(|re| re.to_string()).collect();
+        assert_eq!(patterns, vec![r"\bPOST\b"]);
+    }
+}
@@ -0,0 +1,289 @@
+//! The machine-readable report.
+//!
+//! The JSON layout is versioned by [`SCHEMA_VERSION`]: fields may be added in
+//! a minor version, while renaming or removing one bumps the major version.
+//! `--json-schema` prints the JSON Schema generated from these types.
+
+use crate::gate::GateReport;
+use crate::heatmap::{Heatmap, HeatmapMetric};
+use crate::latency::{self, LatencyHistogram};
+use crate::log_analyzer::{LogEntry, LogLevel, LogStats, ParseWarning};
+use crate::rules::Alert;
+use crate::security::Finding;
+use chrono::{DateTime, Duration, NaiveDateTime, Timelike, Utc};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Version of the JSON layout. 2.0.0 made `stats.slowest_requests` hold at
+/// most `--top` requests, slowest first, instead of every entry.
+pub const SCHEMA_VERSION: &str = "2.0.0";
+
+/// Output formats of the analysis report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Colored terminal report.
+    #[default]
+    Text,
+    /// The versioned JSON report.
+    Json,
+    /// A single self-contained HTML page with charts.
+    Html,
+    /// GitHub-flavored Markdown without ANSI colors.
+    Markdown,
+    /// Prometheus text exposition format, e.g. for node_exporter's textfile
+    /// collector.
+    Prometheus,
+    /// OpenMetrics text format.
+    #[value(name = "openmetrics")]
+    OpenMetrics,
+}
+
+/// Filters that selected the analyzed entries, as given on the command line.
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct AppliedFilters {
+    #[serde(rename = "where", skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+/// What was analyzed.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct InputMetadata {
+    pub path: String,
+    pub size_bytes: u64,
+    /// Last modification time of the file, in UTC.
+    pub modified: Option<NaiveDateTime>,
+    /// Entries parsed from the file, before filtering.
+    pub entries_parsed: usize,
+    /// Entries left after filtering, which the statistics describe.
+    pub entries_analyzed: usize,
+    pub first_timestamp: Option<NaiveDateTime>,
+    pub last_timestamp: Option<NaiveDateTime>,
+    pub filters: AppliedFilters,
+}
+
+impl InputMetadata {
+    /// Describe `path` and the `entries` that survived filtering.
+    pub fn new(
+        path: &Path,
+        entries_parsed: usize,
+        entries: &[LogEntry],
+        filters: AppliedFilters,
+    ) -> Self {
+        let metadata = fs::metadata(path).ok();
+        let timestamps = || entries.iter().filter_map(LogEntry::parsed_timestamp);
+        Self {
+            path: path.display().to_string(),
+            size_bytes: metadata.as_ref().map_or(0, |m| m.len()),
+            modified: metadata
+                .and_then(|m| m.modified().ok())
+                .map(|t| DateTime::<Utc>::from(t).naive_utc()),
+            entries_parsed,
+            entries_analyzed: entries.len(),
+            first_timestamp: timestamps().min(),
+            last_timestamp: timestamps().max(),
+            filters,
+        }
+    }
+}
+
+/// The full analysis. Optional sections are omitted when their feature
+/// (`--rules`, `--security`, `--fail-on`) was not used.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Report<'a> {
+    pub schema_version: &'static str,
+    /// Name and version of the tool that produced the report.
+    pub generator: String,
+    pub input: InputMetadata,
+    pub stats: &'a LogStats,
+    pub parse_warnings: &'a [ParseWarning],
+    /// Latency histograms: all entries first, then each endpoint given with
+    /// `--latency-endpoint`. Empty without response times.
+    pub latency: Vec<LatencyHistogram>,
+    /// Hour-of-day by weekday matrix (`--heatmap-metric`, `--timezone`);
+    /// none without timestamps.
+    pub heatmap: Option<Heatmap>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<&'a [Alert]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub findings: Option<&'a [Finding]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checks: Option<&'a GateReport>,
+}
+
+impl<'a> Report<'a> {
+    /// The report on `stats`; the latency histogram and the heatmap are built
+    /// from `entries`, the entries the statistics describe.
+    pub fn new(
+        input: InputMetadata,
+        stats: &'a LogStats,
+        entries: &[LogEntry],
+        parse_warnings: &'a [ParseWarning],
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            input,
+            stats,
+            parse_warnings,
+            latency: latency::histograms(entries, &[]),
+            heatmap: Heatmap::new(entries, HeatmapMetric::default(), chrono_tz::Tz::UTC),
+            alerts: None,
+            findings: None,
+            checks: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is serializable")
+    }
+}
+
+/// Time-series bucket sizes to choose from, smallest first.
+const SERIES_BUCKETS: [(i64, &str); 7] = [
+    (60, "1 minute"),
+    (5 * 60, "5 minutes"),
+    (15 * 60, "15 minutes"),
+    (60 * 60, "1 hour"),
+    (6 * 60 * 60, "6 hours"),
+    (24 * 60 * 60, "1 day"),
+    (7 * 24 * 60 * 60, "1 week"),
+];
+/// The smallest bucket size giving fewer points than this is chosen.
+const MAX_SERIES_POINTS: i64 = 120;
+
+/// One bucket of a [`TimeSeries`].
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesPoint {
+    pub start: NaiveDateTime,
+    pub requests: usize,
+    pub errors: usize,
+    pub avg_response_time: Option<f64>,
+}
+
+/// Requests, errors and average latency per time bucket, with empty buckets
+/// included so the points are evenly spaced.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeSeries {
+    pub bucket_seconds: i64,
+    /// Human-readable bucket size, e.g. `5 minutes`.
+    pub bucket_name: &'static str,
+    /// Seconds between the first and the last timestamp.
+    pub span_seconds: i64,
+    pub points: Vec<SeriesPoint>,
+}
+
+impl TimeSeries {
+    /// Bucket the entries by timestamp; `None` when no entry has one.
+    pub fn new(entries: &[LogEntry]) -> Option<Self> {
+        let points: Vec<(NaiveDateTime, &LogEntry)> = entries
+            .iter()
+            .filter_map(|e| e.parsed_timestamp().map(|ts| (ts, e)))
+            .collect();
+        let first = points.iter().map(|p| p.0).min()?;
+        let last = points.iter().map(|p| p.0).max()?;
+        let span = (last - first).num_seconds();
+        let (bucket, bucket_name) = SERIES_BUCKETS
+            .iter()
+            .copied()
+            .find(|(size, _)| span / size < MAX_SERIES_POINTS)
+            .unwrap_or(SERIES_BUCKETS[SERIES_BUCKETS.len() - 1]);
+        let start = first
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(first);
+
+        // bucket index -> (requests, errors, response time sum, response time count)
+        let mut buckets: BTreeMap<i64, (usize, usize, f64, usize)> = BTreeMap::new();
+        for (ts, entry) in &points {
+            let slot = buckets.entry((*ts - start).num_seconds() / bucket).or_default();
+            slot.0 += 1;
+            if entry.level == Some(LogLevel::Error) {
+                slot.1 += 1;
+            }
+            if let Some(rt) = entry.response_time {
+                slot.2 += rt;
+                slot.3 += 1;
+            }
+        }
+        let last_slot = buckets.keys().next_back().copied().unwrap_or(0);
+        let points = (0..=last_slot)
+            .map(|slot| {
+                let (requests, errors, sum, count) = buckets.get(&slot).copied().unwrap_or_default();
+                SeriesPoint {
+                    start: start + Duration::seconds(slot * bucket),
+                    requests,
+                    errors,
+                    avg_response_time: (count > 0).then(|| sum / count as f64),
+                }
+            })
+            .collect();
+        Some(TimeSeries {
+            bucket_seconds: bucket,
+            bucket_name,
+            span_seconds: span,
+            points,
+        })
+    }
+}
+
+/// JSON Schema describing [`Report`], pretty-printed.
+pub fn json_schema() -> String {
+    let schema = schemars::schema_for!(Report<'static>);
+    serde_json::to_string_pretty(&schema).expect("schema is serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: [&str; 4] = [
+        "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/orders 500 900ms",
+        "2024-01-15 08:15:20.000 INFO 10.0.0.2 GET /api/users 200 40ms",
+        "2024-01-15 08:15:40.000 WARNING 10.0.0.3 GET /api/orders 200 100ms",
+        r#"{"timestamp":"2024-01-15T08:16:00","level":"info","message":"started","pod":"web-1"}"#,
+    ];
+
+    fn report_json() -> String {
+        let entries: Vec<LogEntry> = LINES.iter().map(|line| LogEntry::parse_log(line).unwrap()).collect();
+        let stats = LogStats::from_entries(&entries);
+        let warnings = [ParseWarning {
+            line_number: 5,
+            line_content: "garbage".to_string(),
+            error: "No timestamp, log level or status code found".to_string(),
+        }];
+        let input = InputMetadata::new(Path::new("server.log"), entries.len(), &entries, AppliedFilters::default());
+        Report::new(input, &stats, &entries, &warnings).to_json()
+    }
+
+    #[test]
+    fn report_carries_the_schema_version() {
+        let report: serde_json::Value = serde_json::from_str(&report_json()).unwrap();
+        assert_eq!(report["schema_version"], SCHEMA_VERSION);
+        assert_eq!(report["stats"]["total_requests"], 4);
+    }
+
+    #[test]
+    fn report_serializes_deterministically() {
+        let first = report_json();
+        assert_eq!(first, report_json());
+        let endpoints = first.find("\"/api/orders\"").unwrap();
+        assert!(endpoints < first.find("\"/api/users\"").unwrap());
+    }
+
+    #[test]
+    fn schema_validates_a_report() {
+        let schema: serde_json::Value = serde_json::from_str(&json_schema()).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let mut report: serde_json::Value = serde_json::from_str(&report_json()).unwrap();
+        let errors: Vec<String> = validator.iter_errors(&report).map(|e| e.to_string()).collect();
+        assert!(errors.is_empty(), "{errors:?}");
+
+        report["stats"]["total_requests"] = "four".into();
+        assert!(!validator.is_valid(&report));
+    }
+}
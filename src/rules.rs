@@ -0,0 +1,623 @@
+use crate::filter::{
+    And, EndpointFilter, Filter, FilterExt, IpFilter, LevelFilter, MessageFilter, MethodFilter,
+    StatusFilter,
+};
+use crate::aggregate::{Accumulator, Agg, key_value};
+use crate::log_analyzer::{AnalyzerError, LogEntry, parse_duration};
+use crate::query::Expr;
+use crate::text_report::TextLayout;
+use chrono::{DateTime, Duration, NaiveDateTime};
+use colored::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    fs,
+    path::Path,
+};
+
+/// A rules file as written by the user.
+///
+/// ```toml
+/// [[rule]]
+/// name = "orders-error-rate"
+/// severity = "critical"
+/// aggregate = "rate"
+/// window = "5m"
+/// group_by = "endpoint"
+/// threshold = 5.0
+///
+/// [rule.filter]
+/// min_level = "ERROR"
+/// endpoint = "^/api/orders"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+    #[serde(rename = "rule", default)]
+    pub rules: Vec<RuleConfig>,
+}
+
+impl RuleSet {
+    pub fn from_file(path: &Path) -> Result<Self, AnalyzerError> {
+        let contents = fs::read_to_string(path)?;
+        Self::from_toml(&contents)
+    }
+
+    /// Parse a rules file. Unknown keys and a file without any `[[rule]]`
+    /// are errors, so a typo cannot quietly disable a rule or its filter.
+    pub fn from_toml(contents: &str) -> Result<Self, AnalyzerError> {
+        let rule_set: Self = toml::from_str(contents)?;
+        if rule_set.rules.is_empty() {
+            return Err(AnalyzerError::NoRules);
+        }
+        Ok(rule_set)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub severity: AlertSeverity,
+    #[serde(default)]
+    pub filter: FilterConfig,
+    /// `count`, `rate` (matching entries as a percentage of all entries in
+    /// the window/group), or any `--agg` aggregate of the matching entries,
+    /// such as `avg(response_time)`, `p95(response_time)` or
+    /// `count_distinct(ip)`.
+    pub aggregate: String,
+    /// Tumbling window size (`30s`, `5m`, `1h`). Without it the whole input is
+    /// a single window.
+    #[serde(default)]
+    pub window: Option<String>,
+    /// Evaluate each value of this field separately: any `--group-by` key,
+    /// including extra fields such as `service`.
+    #[serde(default)]
+    pub group_by: Option<String>,
+    #[serde(default)]
+    pub comparison: Comparison,
+    pub threshold: f64,
+    /// Windows with fewer entries than this are not evaluated, which keeps
+    /// rates and percentiles from firing on a handful of samples.
+    #[serde(default = "default_min_events")]
+    pub min_events: usize,
+}
+
+fn default_min_events() -> usize {
+    1
+}
+
+/// Conditions an entry must meet to be counted by a rule. All set fields must
+/// match; `ip` accepts an address or a CIDR network.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfig {
+    pub level: Option<String>,
+    pub min_level: Option<String>,
+    pub method: Option<String>,
+    /// Regex matched against the endpoint.
+    pub endpoint: Option<String>,
+    /// Regex matched against the message.
+    pub message: Option<String>,
+    pub ip: Option<String>,
+    pub status: Option<u16>,
+    pub status_min: Option<u16>,
+    pub status_max: Option<u16>,
+    /// Query expression, e.g. `status in 500..599 and not ip in 10.0.0.0/8`.
+    #[serde(rename = "where")]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl Display for AlertSeverity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let severity = match self {
+            AlertSeverity::Info => "INFO",
+            AlertSeverity::Warning => "WARNING",
+            AlertSeverity::Critical => "CRITICAL",
+        };
+        write!(f, "{severity}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Comparison {
+    #[default]
+    #[serde(rename = ">")]
+    Above,
+    #[serde(rename = ">=")]
+    AtLeast,
+    #[serde(rename = "<")]
+    Below,
+    #[serde(rename = "<=")]
+    AtMost,
+}
+
+impl Comparison {
+    pub fn breached(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Above => value > threshold,
+            Comparison::AtLeast => value >= threshold,
+            Comparison::Below => value < threshold,
+            Comparison::AtMost => value <= threshold,
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Comparison::Above => ">",
+            Comparison::AtLeast => ">=",
+            Comparison::Below => "<",
+            Comparison::AtMost => "<=",
+        };
+        write!(f, "{op}")
+    }
+}
+
+impl FilterConfig {
+    fn compile(&self, rule: &str) -> Result<And, AnalyzerError> {
+        let mut filters: Vec<Box<dyn Filter>> = Vec::new();
+        if let Some(level) = &self.level {
+            filters.push(LevelFilter::exact(level)?.boxed());
+        }
+        if let Some(level) = &self.min_level {
+            filters.push(LevelFilter::at_least(level)?.boxed());
+        }
+        if let Some(method) = &self.method {
+            filters.push(MethodFilter::new(method)?.boxed());
+        }
+        if let Some(endpoint) = &self.endpoint {
+            filters.push(EndpointFilter::regex(endpoint)?.boxed());
+        }
+        if let Some(message) = &self.message {
+            filters.push(MessageFilter::new(message)?.boxed());
+        }
+        if let Some(ip) = &self.ip {
+            filters.push(IpFilter::new(ip)?.boxed());
+        }
+        if let Some(status) = self.status {
+            filters.push(StatusFilter::exact(status).boxed());
+        }
+        if self.status_min.is_some() || self.status_max.is_some() {
+            let low = self.status_min.unwrap_or(u16::MIN);
+            let high = self.status_max.unwrap_or(u16::MAX);
+            filters.push(StatusFilter::range(low, high)?.boxed());
+        }
+        if let Some(query) = &self.query {
+            let expr = Expr::parse(query).map_err(|e| AnalyzerError::RuleError {
+                rule: rule.to_string(),
+                message: e.to_string(),
+            })?;
+            filters.push(expr.boxed());
+        }
+        Ok(And(filters))
+    }
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub name: String,
+    pub description: Option<String>,
+    pub severity: AlertSeverity,
+    pub aggregation: Agg,
+    pub aggregate: String,
+    pub window: Option<Duration>,
+    pub group_by: Option<String>,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    pub min_events: usize,
+    filter: And,
+}
+
+impl Rule {
+    pub fn compile(config: &RuleConfig) -> Result<Self, AnalyzerError> {
+        let invalid = |message: String| AnalyzerError::RuleError {
+            rule: config.name.clone(),
+            message,
+        };
+        // A rule's share of the window is its rate.
+        let aggregation = match config.aggregate.trim() {
+            "rate" => Agg::Share,
+            aggregate => aggregate.parse().map_err(|_| invalid(format!("unknown aggregate '{aggregate}'")))?,
+        };
+        let window = config.window.as_deref().map(parse_duration).transpose()?;
+        if window.is_some_and(|w| w.num_milliseconds() <= 0) {
+            return Err(invalid("window must be positive".to_string()));
+        }
+        if config.group_by.as_deref().is_some_and(|field| field.trim().is_empty()) {
+            return Err(invalid("group_by needs a field".to_string()));
+        }
+        Ok(Self {
+            name: config.name.clone(),
+            description: config.description.clone(),
+            severity: config.severity,
+            aggregation,
+            aggregate: config.aggregate.clone(),
+            window,
+            group_by: config.group_by.clone(),
+            comparison: config.comparison,
+            threshold: config.threshold,
+            min_events: config.min_events,
+            filter: config.filter.compile(&config.name)?,
+        })
+    }
+
+    fn bucket(&self, entry: &LogEntry) -> Option<Option<i64>> {
+        match self.window {
+            None => Some(None),
+            Some(window) => {
+                let ts = entry.parsed_timestamp()?.and_utc().timestamp_millis();
+                let size = window.num_milliseconds();
+                Some(Some(ts.div_euclid(size) * size))
+            }
+        }
+    }
+
+    /// End of the window starting at `bucket`, in milliseconds.
+    fn window_end(&self, bucket: Option<i64>) -> Option<i64> {
+        Some(bucket? + self.window?.num_milliseconds())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Alert {
+    pub rule: String,
+    pub severity: AlertSeverity,
+    pub group: Option<String>,
+    pub window_start: Option<NaiveDateTime>,
+    pub window_end: Option<NaiveDateTime>,
+    pub aggregate: String,
+    pub value: f64,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    pub matched: usize,
+}
+
+impl Display for Alert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.severity, self.rule)?;
+        if let Some(group) = &self.group {
+            write!(f, " ({group})")?;
+        }
+        if let (Some(start), Some(end)) = (self.window_start, self.window_end) {
+            write!(f, " {} .. {}", start.format("%Y-%m-%d %H:%M:%S"), end.format("%H:%M:%S"))?;
+        }
+        write!(
+            f,
+            ": {} = {:.2} {} {}",
+            self.aggregate, self.value, self.comparison, self.threshold
+        )
+    }
+}
+
+/// A window's entries, and the aggregate of those matching the rule.
+#[derive(Debug, Default)]
+struct WindowState {
+    total: usize,
+    matched: Accumulator,
+}
+
+type StateKey = (usize, Option<String>, Option<i64>);
+
+/// Evaluates compiled rules over a stream of entries.
+///
+/// Entries are fed one at a time through [`RuleEngine::observe`] so the same
+/// engine works for a whole file or for a tailed stream. When following,
+/// [`RuleEngine::close_windows`] returns the alerts of the windows that have
+/// ended; [`RuleEngine::finish`] closes every open window.
+#[derive(Debug)]
+pub struct RuleEngine {
+    rules: Vec<Rule>,
+    state: BTreeMap<StateKey, WindowState>,
+    /// Windows ending at or before this time (ms) are closed.
+    closed_until: Option<i64>,
+}
+
+impl RuleEngine {
+    pub fn new(rule_set: &RuleSet) -> Result<Self, AnalyzerError> {
+        let rules = rule_set
+            .rules
+            .iter()
+            .map(Rule::compile)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            rules,
+            state: BTreeMap::new(),
+            closed_until: None,
+        })
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Count an entry. Entries of a window that was already closed are
+    /// dropped, so a late line cannot raise the same alert twice.
+    pub fn observe(&mut self, entry: &LogEntry) {
+        for (idx, rule) in self.rules.iter().enumerate() {
+            let Some(bucket) = rule.bucket(entry) else {
+                continue;
+            };
+            if self.closed_until.is_some_and(|closed| rule.window_end(bucket).is_some_and(|end| end <= closed)) {
+                continue;
+            }
+            let group = match &rule.group_by {
+                Some(field) => match key_value(entry, field) {
+                    Some(value) => Some(value),
+                    None => continue,
+                },
+                None => None,
+            };
+            let window = self.state.entry((idx, group, bucket)).or_default();
+            window.total += 1;
+            if rule.filter.matches(entry) {
+                window.matched.add(&rule.aggregation, entry);
+            }
+        }
+    }
+
+    /// Close the windows that ended at or before `now` and return the alerts
+    /// that breached their threshold. Rules without a window stay open until
+    /// [`RuleEngine::finish`].
+    pub fn close_windows(&mut self, now: NaiveDateTime) -> Vec<Alert> {
+        let now = now.and_utc().timestamp_millis();
+        self.closed_until = self.closed_until.max(Some(now));
+        let (closed, open) = std::mem::take(&mut self.state).into_iter().partition(|((idx, _, bucket), _)| {
+            self.rules[*idx].window_end(*bucket).is_some_and(|end| end <= now)
+        });
+        self.state = open;
+        self.alerts(closed)
+    }
+
+    /// Close all windows and return the alerts that breached their threshold,
+    /// ordered by rule and then window start.
+    pub fn finish(&mut self) -> Vec<Alert> {
+        let state = std::mem::take(&mut self.state);
+        self.alerts(state)
+    }
+
+    fn alerts(&self, windows: BTreeMap<StateKey, WindowState>) -> Vec<Alert> {
+        windows
+            .into_iter()
+            .filter_map(|((idx, group, bucket), window)| {
+                let rule = &self.rules[idx];
+                if window.total < rule.min_events {
+                    return None;
+                }
+                let value = window.matched.value(&rule.aggregation, window.total)?;
+                if !rule.comparison.breached(value, rule.threshold) {
+                    return None;
+                }
+                let window_start = bucket
+                    .and_then(DateTime::from_timestamp_millis)
+                    .map(|dt| dt.naive_utc());
+                let window_end = window_start.zip(rule.window).map(|(start, size)| start + size);
+                Some(Alert {
+                    rule: rule.name.clone(),
+                    severity: rule.severity,
+                    group: group.map(|g| format!("{}={}", rule.group_by.as_deref().unwrap_or(""), g)),
+                    window_start,
+                    window_end,
+                    aggregate: rule.aggregate.clone(),
+                    value,
+                    comparison: rule.comparison,
+                    threshold: rule.threshold,
+                    matched: window.matched.entries(),
+                })
+            })
+            .collect()
+    }
+
+    /// Batch-evaluate every rule over the given entries.
+    pub fn evaluate(&mut self, entries: &[LogEntry]) -> Vec<Alert> {
+        for entry in entries {
+            self.observe(entry);
+        }
+        self.finish()
+    }
+}
+
+/// Print the alerts section in the same style as the main report.
+pub fn print_alerts(alerts: &[Alert], layout: &TextLayout) {
+    if alerts.is_empty() {
+        println!("\n{}", layout.heading("🔔", "ALERTS: No rules triggered").bold().green());
+        return;
+    }
+
+    println!("{}", layout.title("🔔", "ALERTS"));
+
+    for alert in alerts {
+        print_alert(alert);
+    }
+}
+
+/// Print one alert, as in the alerts section.
+pub fn print_alert(alert: &Alert) {
+    let severity = match alert.severity {
+        AlertSeverity::Critical => alert.severity.to_string().red().bold(),
+        AlertSeverity::Warning => alert.severity.to_string().yellow(),
+        AlertSeverity::Info => alert.severity.to_string().bright_blue(),
+    };
+    print!("  [{}] {}", severity, alert.rule.bright_white().bold());
+    if let Some(group) = &alert.group {
+        print!(" {}", group.bright_cyan());
+    }
+    println!();
+    if let (Some(start), Some(end)) = (alert.window_start, alert.window_end) {
+        println!(
+            "      {}",
+            format!("{} .. {}", start.format("%Y-%m-%d %H:%M:%S"), end.format("%H:%M:%S"))
+                .bright_black()
+        );
+    }
+    println!(
+        "      {} = {:.2} {} {} ({} matching entries)",
+        alert.aggregate, alert.value, alert.comparison, alert.threshold, alert.matched
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(toml: &str) -> RuleEngine {
+        RuleEngine::new(&RuleSet::from_toml(toml).unwrap()).unwrap()
+    }
+
+    fn entries(lines: &[&str]) -> Vec<LogEntry> {
+        lines.iter().map(|line| LogEntry::parse_log(line).unwrap()).collect()
+    }
+
+    const LINES: [&str; 6] = [
+        "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/orders 500 900ms",
+        "2024-01-15 08:15:20.000 INFO 10.0.0.2 GET /api/users 200 40ms",
+        "2024-01-15 08:15:40.000 ERROR 10.0.0.3 POST /api/orders 503 1200ms",
+        "2024-01-15 08:16:10.000 INFO 10.0.0.1 GET /api/users 200 60ms",
+        "2024-01-15 08:16:30.000 INFO 10.0.0.2 POST /api/orders 201 300ms",
+        "2024-01-15 08:16:50.000 INFO 10.0.0.2 GET /api/users 200 50ms",
+    ];
+
+    const ERROR_RATE: &str = r#"
+        [[rule]]
+        name = "error-rate"
+        aggregate = "rate"
+        window = "1m"
+        threshold = 50.0
+        comparison = ">="
+        [rule.filter]
+        min_level = "ERROR"
+    "#;
+
+    #[test]
+    fn rate_is_a_share_of_the_window() {
+        let alerts = engine(ERROR_RATE).evaluate(&entries(&LINES));
+        assert_eq!(alerts.len(), 1);
+        let alert = &alerts[0];
+        assert!((alert.value - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(alert.matched, 2);
+        assert_eq!(alert.window_start.unwrap().to_string(), "2024-01-15 08:15:00");
+        assert_eq!(alert.window_end.unwrap().to_string(), "2024-01-15 08:16:00");
+    }
+
+    #[test]
+    fn aggregates_are_grouped() {
+        let rules = r#"
+            [[rule]]
+            name = "slow"
+            aggregate = "p95(response_time)"
+            group_by = "endpoint"
+            threshold = 500.0
+        "#;
+        let alerts = engine(rules).evaluate(&entries(&LINES));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].group.as_deref(), Some("endpoint=/api/orders"));
+        assert_eq!(alerts[0].matched, 3);
+    }
+
+    #[test]
+    fn extra_fields_and_derived_keys_can_group() {
+        let lines = [
+            r#"{"timestamp":"2024-01-15T08:15:01","level":"ERROR","status":500,"service":"billing"}"#,
+            r#"{"timestamp":"2024-01-15T08:15:02","level":"INFO","status":200,"service":"search"}"#,
+            r#"{"timestamp":"2024-01-15T08:15:03","level":"ERROR","status":503,"service":"billing"}"#,
+        ];
+        let rules = r#"
+            [[rule]]
+            name = "by-service"
+            aggregate = "count"
+            group_by = "service"
+            threshold = 2.0
+            comparison = ">="
+        "#;
+        let alerts = engine(rules).evaluate(&entries(&lines));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].group.as_deref(), Some("service=billing"));
+        let alerts = engine(&rules.replace("\"service\"", "\"status_class\"")).evaluate(&entries(&lines));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].group.as_deref(), Some("status_class=5xx"));
+    }
+
+    #[test]
+    fn min_events_counts_the_whole_window() {
+        // Only one entry matches, but the window has six.
+        let rules = r#"
+            [[rule]]
+            name = "orders"
+            aggregate = "count"
+            threshold = 0.0
+            min_events = 6
+            [rule.filter]
+            status = 503
+        "#;
+        assert_eq!(engine(rules).evaluate(&entries(&LINES)).len(), 1);
+        let rules = rules.replace("min_events = 6", "min_events = 7");
+        assert!(engine(&rules).evaluate(&entries(&LINES)).is_empty());
+    }
+
+    #[test]
+    fn close_windows_alerts_once_and_drops_late_entries() {
+        let mut engine = engine(ERROR_RATE);
+        let entries = entries(&LINES);
+        let mut alerts = Vec::new();
+        for entry in &entries {
+            engine.observe(entry);
+            alerts.extend(engine.close_windows(entry.parsed_timestamp().unwrap()));
+        }
+        assert_eq!(alerts.len(), 1, "the 08:15 window closes at 08:16:10");
+        engine.observe(&LogEntry::parse_log("2024-01-15 08:15:59.000 ERROR 10.0.0.1 GET /late 500 5ms").unwrap());
+        assert!(engine.finish().is_empty());
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let cases = [
+            ("aggregate = \"p95(\"", "unknown aggregate"),
+            ("aggregate = \"count\"\nwindow = \"0s\"", "window must be positive"),
+            ("aggregate = \"count\"\ngroup_by = \" \"", "group_by needs a field"),
+            ("aggregate = \"count\"\n[rule.filter]\nwhere = \"status >\"", "expected a value"),
+        ];
+        for (body, message) in cases {
+            let toml = format!("[[rule]]\nname = \"r\"\nthreshold = 1.0\n{body}");
+            let err = RuleEngine::new(&RuleSet::from_toml(&toml).unwrap()).unwrap_err().to_string();
+            assert!(err.contains(message), "{body}: {err}");
+        }
+    }
+
+    #[test]
+    fn files_without_rules_are_rejected() {
+        for toml in ["", "# nothing here", "[[rules]]\nname = \"r\"\naggregate = \"count\"\nthreshold = 1.0"] {
+            assert!(RuleSet::from_toml(toml).is_err(), "{toml:?}");
+        }
+        assert!(matches!(RuleSet::from_toml("# nothing here"), Err(AnalyzerError::NoRules)));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let rule = "[[rule]]\nname = \"r\"\naggregate = \"count\"\nthreshold = 1.0\n";
+        let cases = [
+            format!("{rule}treshold = 2.0"),
+            format!("{rule}[rule.filter]\nmin_levle = \"ERROR\""),
+            format!("version = 1\n{rule}"),
+        ];
+        for toml in cases {
+            let err = RuleSet::from_toml(&toml).unwrap_err().to_string();
+            assert!(err.contains("unknown field"), "{toml}: {err}");
+        }
+        assert_eq!(RuleSet::from_toml(rule).unwrap().rules.len(), 1);
+    }
+}
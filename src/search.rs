@@ -0,0 +1,721 @@
+//! Full-text search backed by an inverted index stored next to the log.
+//!
+//! `loggaliza -i app.log index` writes `app.log.lgz-index`, mapping every
+//! word of every line and every `field:value` pair of the parsed entry to
+//! the lines containing it, together with each line's byte offset. Searches
+//! then read only the matching lines (and their context) from the log.
+//!
+//! The index records the size and modification time of the log it was
+//! built from and the parse options it was built with; [`SearchIndex::open`]
+//! rebuilds it when any of them changed.
+//!
+//! Query syntax, all terms must match:
+//!
+//! ```text
+//! timeout                 word anywhere in the line
+//! "connection refused"    consecutive words
+//! level:error status:5xx  field qualifiers (level, method, status, ip,
+//!                         endpoint and top-level extra JSON fields)
+//! endpoint:/api/*  tim*   prefix match
+//! -healthcheck            exclude lines matching the term
+//! ```
+//!
+//! The index is a little-endian binary file that is read with seeks rather
+//! than loaded, so a search only reads the header, the term entries its
+//! binary search visits, the postings of the matching terms and the offsets
+//! of the lines it prints:
+//!
+//! ```text
+//! header    "LGZINDEX", version u32, parse options fingerprint u64,
+//!           source len u64, modified secs u64, modified nanos u32,
+//!           line count u64, term count u64
+//! offsets   byte offset u64 of every line
+//! terms     (name at u64, name len u32, postings at u64, postings len u32)
+//!           for every term, sorted by name; `at` is a position in the file
+//! names     the term names, UTF-8
+//! postings  ascending u32 line indexes (0-based) of each term
+//! ```
+
+use crate::log_analyzer::{AnalyzerError, LogEntry, ParseOptions};
+use crate::query::QueryError;
+use colored::*;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Bumped whenever the on-disk layout changes; older indexes are rebuilt.
+pub const INDEX_VERSION: u32 = 2;
+const INDEX_EXTENSION: &str = "lgz-index";
+const MAGIC: &[u8; 8] = b"LGZINDEX";
+const HEADER_LEN: u64 = 56;
+const TERM_ENTRY_LEN: u64 = 24;
+
+/// Size and modification time of the indexed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceStamp {
+    pub len: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+}
+
+impl SourceStamp {
+    pub fn of(path: &Path) -> Result<Self, AnalyzerError> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self {
+            len: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+/// An open index file. Terms, postings and line offsets are read from it on
+/// demand.
+#[derive(Debug)]
+pub struct SearchIndex {
+    file: File,
+    pub version: u32,
+    pub source: SourceStamp,
+    /// Fingerprint of the parse options the field terms were built with.
+    options: u64,
+    line_count: u64,
+    term_count: u64,
+}
+
+/// A term's entry in the term table.
+struct TermEntry {
+    name_at: u64,
+    name_len: u32,
+    postings_at: u64,
+    postings_len: u32,
+}
+
+/// Where the index for `log` lives.
+pub fn index_path(log: &Path) -> PathBuf {
+    let mut name = log.as_os_str().to_owned();
+    name.push(".");
+    name.push(INDEX_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Lowercased words: runs of alphanumerics and underscores.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// The `field:value` terms for a parsed entry.
+fn field_terms(entry: &LogEntry) -> Vec<String> {
+    let mut terms = Vec::new();
+    for field in ["level", "method", "status", "ip", "endpoint"] {
+        if let Some(value) = entry.field_value(field) {
+            terms.push(format!("{field}:{}", value.to_lowercase()));
+        }
+    }
+    if let Some(status) = entry.status_code {
+        terms.push(format!("status:{}xx", status / 100));
+    }
+    for (key, value) in &entry.extra {
+        let value = match value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Bool(b) => b.to_string(),
+            _ => continue,
+        };
+        terms.push(format!("{}:{}", key.to_lowercase(), value.to_lowercase()));
+    }
+    terms
+}
+
+/// Stable FNV-1a hash of the options as they affect parsing, so an index
+/// built with other logfmt keys or syslog year is rebuilt.
+fn fingerprint(options: &ParseOptions) -> u64 {
+    format!("{options:?}")
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// The options `log` is parsed with, syslog reference included.
+fn options_for(log: &Path, options: &ParseOptions) -> Result<ParseOptions, AnalyzerError> {
+    Ok(options.for_file(&File::open(log)?))
+}
+
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().expect("four bytes"))
+}
+
+fn u64_at(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().expect("eight bytes"))
+}
+
+impl SearchIndex {
+    /// Index every line of `log`, parsing it with `options`, and write the
+    /// index next to it.
+    pub fn build(log: &Path, options: &ParseOptions) -> Result<Self, AnalyzerError> {
+        let options = options_for(log, options)?;
+        let source = SourceStamp::of(log)?;
+        let mut reader = BufReader::new(File::open(log)?);
+        let mut offsets = Vec::new();
+        let mut postings: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        let mut offset = 0u64;
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            let read = reader.read_until(b'\n', &mut buf)?;
+            if read == 0 {
+                break;
+            }
+            let line_index = offsets.len() as u32;
+            offsets.push(offset);
+            offset += read as u64;
+
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut terms: BTreeSet<String> = words(line).collect();
+            if let Ok(entry) = LogEntry::parse_log_with(line, &options) {
+                terms.extend(field_terms(&entry));
+            }
+            for term in terms {
+                postings.entry(term).or_default().push(line_index);
+            }
+        }
+
+        // Write through a temporary file so a reader never sees half an index.
+        let path = index_path(log);
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&INDEX_VERSION.to_le_bytes())?;
+        out.write_all(&fingerprint(&options).to_le_bytes())?;
+        out.write_all(&source.len.to_le_bytes())?;
+        out.write_all(&source.modified_secs.to_le_bytes())?;
+        out.write_all(&source.modified_nanos.to_le_bytes())?;
+        out.write_all(&(offsets.len() as u64).to_le_bytes())?;
+        out.write_all(&(postings.len() as u64).to_le_bytes())?;
+        for offset in &offsets {
+            out.write_all(&offset.to_le_bytes())?;
+        }
+        let names_at = HEADER_LEN + 8 * offsets.len() as u64 + TERM_ENTRY_LEN * postings.len() as u64;
+        let mut name_at = names_at;
+        let mut postings_at = names_at + postings.keys().map(|t| t.len() as u64).sum::<u64>();
+        for (term, lines) in &postings {
+            out.write_all(&name_at.to_le_bytes())?;
+            out.write_all(&(term.len() as u32).to_le_bytes())?;
+            out.write_all(&postings_at.to_le_bytes())?;
+            out.write_all(&(lines.len() as u32).to_le_bytes())?;
+            name_at += term.len() as u64;
+            postings_at += 4 * lines.len() as u64;
+        }
+        for term in postings.keys() {
+            out.write_all(term.as_bytes())?;
+        }
+        for line in postings.values().flatten() {
+            out.write_all(&line.to_le_bytes())?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Self::read(&path)
+    }
+
+    /// Open an index file, reading only its header.
+    pub fn read(path: &Path) -> Result<Self, AnalyzerError> {
+        let mut file = File::open(path)?;
+        let mut header = [0; HEADER_LEN as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| AnalyzerError::IndexError(format!("{} is truncated", path.display())))?;
+        if &header[..8] != MAGIC {
+            return Err(AnalyzerError::IndexError(format!("{} is not a search index", path.display())));
+        }
+        Ok(Self {
+            file,
+            version: u32_at(&header, 8),
+            options: u64_at(&header, 12),
+            source: SourceStamp {
+                len: u64_at(&header, 20),
+                modified_secs: u64_at(&header, 28),
+                modified_nanos: u32_at(&header, 36),
+            },
+            line_count: u64_at(&header, 40),
+            term_count: u64_at(&header, 48),
+        })
+    }
+
+    /// Whether the index was built by this version from `log` as it is now,
+    /// with the same parse options.
+    pub fn is_current(&self, log: &Path, options: &ParseOptions) -> Result<bool, AnalyzerError> {
+        Ok(self.version == INDEX_VERSION
+            && self.source == SourceStamp::of(log)?
+            && self.options == fingerprint(&options_for(log, options)?))
+    }
+
+    /// Open the index for `log`, building it first if it is missing,
+    /// unreadable or stale. The flag tells whether it was rebuilt.
+    pub fn open(log: &Path, options: &ParseOptions) -> Result<(Self, bool), AnalyzerError> {
+        if let Ok(index) = Self::read(&index_path(log))
+            && index.is_current(log, options)?
+        {
+            return Ok((index, false));
+        }
+        Ok((Self::build(log, options)?, true))
+    }
+
+    fn read_at(&self, at: u64, buf: &mut [u8]) -> Result<(), AnalyzerError> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(at))?;
+        file.read_exact(buf)
+            .map_err(|e| AnalyzerError::IndexError(format!("index is truncated: {e}")))
+    }
+
+    fn term_entry(&self, term: u64) -> Result<TermEntry, AnalyzerError> {
+        let mut buf = [0; TERM_ENTRY_LEN as usize];
+        self.read_at(HEADER_LEN + 8 * self.line_count + TERM_ENTRY_LEN * term, &mut buf)?;
+        Ok(TermEntry {
+            name_at: u64_at(&buf, 0),
+            name_len: u32_at(&buf, 8),
+            postings_at: u64_at(&buf, 12),
+            postings_len: u32_at(&buf, 20),
+        })
+    }
+
+    fn term_name(&self, entry: &TermEntry) -> Result<String, AnalyzerError> {
+        let mut buf = vec![0; entry.name_len as usize];
+        self.read_at(entry.name_at, &mut buf)?;
+        String::from_utf8(buf).map_err(|e| AnalyzerError::IndexError(e.to_string()))
+    }
+
+    fn postings(&self, entry: &TermEntry) -> Result<Vec<u32>, AnalyzerError> {
+        let mut buf = vec![0; 4 * entry.postings_len as usize];
+        self.read_at(entry.postings_at, &mut buf)?;
+        Ok(buf.chunks_exact(4).map(|b| u32_at(b, 0)).collect())
+    }
+
+    /// Position of the first term not less than `term`.
+    fn lower_bound(&self, term: &str) -> Result<u64, AnalyzerError> {
+        let (mut low, mut high) = (0, self.term_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.term_name(&self.term_entry(mid)?)?.as_str() < term {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    fn lines_for(&self, word: &Word) -> Result<BTreeSet<u32>, AnalyzerError> {
+        let (Word::Exact(term) | Word::Prefix(term)) = word;
+        let mut lines = BTreeSet::new();
+        for position in self.lower_bound(term)?..self.term_count {
+            let entry = self.term_entry(position)?;
+            let name = self.term_name(&entry)?;
+            let matched = match word {
+                Word::Exact(_) => name == *term,
+                Word::Prefix(_) => name.starts_with(term.as_str()),
+            };
+            if !matched {
+                break;
+            }
+            lines.extend(self.postings(&entry)?);
+        }
+        Ok(lines)
+    }
+
+    /// Lines matching every include clause, before phrases are verified
+    /// against the text.
+    fn candidates(&self, query: &SearchQuery) -> Result<BTreeSet<u32>, AnalyzerError> {
+        let mut result: Option<BTreeSet<u32>> = None;
+        for clause in query.clauses.iter().filter(|c| !c.negated) {
+            let lines = self.clause_lines(&clause.term)?;
+            result = Some(match result {
+                Some(acc) => acc.intersection(&lines).copied().collect(),
+                None => lines,
+            });
+        }
+        let mut result =
+            result.unwrap_or_else(|| (0..self.line_count as u32).collect());
+        for clause in query.clauses.iter().filter(|c| c.negated) {
+            // A negated phrase only excludes lines that really contain it,
+            // which is checked later against the text.
+            if let Term::Word(word) = &clause.term {
+                for line in self.lines_for(word)? {
+                    result.remove(&line);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn clause_lines(&self, term: &Term) -> Result<BTreeSet<u32>, AnalyzerError> {
+        match term {
+            Term::Word(word) => self.lines_for(word),
+            Term::Phrase(words) => {
+                let mut lines: Option<BTreeSet<u32>> = None;
+                for word in words {
+                    let found = self.lines_for(&Word::Exact(word.clone()))?;
+                    lines = Some(match lines {
+                        Some(acc) => acc.intersection(&found).copied().collect(),
+                        None => found,
+                    });
+                }
+                Ok(lines.unwrap_or_default())
+            }
+        }
+    }
+
+    /// Run `query` and return the matching lines in file order.
+    pub fn search(&self, log: &Path, query: &SearchQuery) -> Result<Vec<Line>, AnalyzerError> {
+        let mut file = File::open(log)?;
+        let mut hits = Vec::new();
+        for index in self.candidates(query)? {
+            let line = self.read_line(&mut file, index as usize)?;
+            let line_words: Vec<String> = words(&line.text).collect();
+            let phrases_ok = query.clauses.iter().all(|clause| match &clause.term {
+                Term::Phrase(phrase) => contains_phrase(&line_words, phrase) != clause.negated,
+                Term::Word(_) => true,
+            });
+            if phrases_ok {
+                hits.push(line);
+            }
+        }
+        Ok(hits)
+    }
+
+    /// Byte offset of line `index` (0-based) in the log.
+    fn offset(&self, index: usize) -> Result<u64, AnalyzerError> {
+        let mut buf = [0; 8];
+        self.read_at(HEADER_LEN + 8 * index as u64, &mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    /// Read line `index` (0-based) from the log.
+    pub fn read_line(&self, file: &mut File, index: usize) -> Result<Line, AnalyzerError> {
+        let start = self.offset(index)?;
+        let end = if index + 1 < self.line_count() { self.offset(index + 1)? } else { self.source.len };
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = vec![0; (end - start) as usize];
+        file.read_exact(&mut buf)?;
+        Ok(Line {
+            line_number: index + 1,
+            text: String::from_utf8_lossy(&buf).trim_end_matches(['\n', '\r']).to_string(),
+        })
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count as usize
+    }
+
+    pub fn term_count(&self) -> usize {
+        self.term_count as usize
+    }
+}
+
+fn contains_phrase(line: &[String], phrase: &[String]) -> bool {
+    !phrase.is_empty() && line.windows(phrase.len()).any(|w| w == phrase)
+}
+
+/// A line of the log file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Line {
+    pub line_number: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Word {
+    Exact(String),
+    Prefix(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Word(Word),
+    Phrase(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Clause {
+    term: Term,
+    negated: bool,
+}
+
+/// A parsed search query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    clauses: Vec<Clause>,
+    /// Lowercased words and phrases to highlight in results.
+    highlights: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        let error = |position: usize, message: &str| QueryError {
+            query: query.to_string(),
+            position,
+            message: message.to_string(),
+        };
+        let mut clauses = Vec::new();
+        let mut highlights = Vec::new();
+        let mut chars = query.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            let negated = c == '-';
+            if negated {
+                chars.next();
+            }
+            // Read up to whitespace, keeping quoted sections together.
+            let mut token = String::new();
+            let mut quoted = false;
+            let mut in_quotes = false;
+            while let Some(&(_, ch)) = chars.peek() {
+                if ch == '"' {
+                    in_quotes = !in_quotes;
+                    quoted = true;
+                } else if ch.is_whitespace() && !in_quotes {
+                    break;
+                } else {
+                    token.push(ch);
+                }
+                chars.next();
+            }
+            if in_quotes {
+                return Err(error(start, "unterminated quoted phrase"));
+            }
+
+            let (field, value) = match token.split_once(':') {
+                Some((field, value)) if !field.is_empty() && !field.contains(char::is_whitespace) => {
+                    (Some(field.to_lowercase()), value.to_string())
+                }
+                _ => (None, token),
+            };
+            if value.is_empty() {
+                return Err(error(start, "empty search term"));
+            }
+            let prefix = value.ends_with('*');
+            let value = value.trim_end_matches('*').to_lowercase();
+
+            let term = match field {
+                Some(field) => {
+                    let term = format!("{field}:{value}");
+                    Term::Word(if prefix { Word::Prefix(term) } else { Word::Exact(term) })
+                }
+                None => {
+                    let phrase: Vec<String> = words(&value).collect();
+                    match phrase.as_slice() {
+                        [] => return Err(error(start, "search term has no words")),
+                        [word] if prefix => Term::Word(Word::Prefix(word.clone())),
+                        [word] if !quoted => Term::Word(Word::Exact(word.clone())),
+                        _ => Term::Phrase(phrase),
+                    }
+                }
+            };
+            if !negated {
+                highlights.push(match &term {
+                    Term::Word(Word::Exact(w) | Word::Prefix(w)) => {
+                        w.split_once(':').map(|(_, v)| v.to_string()).unwrap_or(w.clone())
+                    }
+                    Term::Phrase(_) => value,
+                });
+            }
+            clauses.push(Clause { term, negated });
+        }
+        if clauses.iter().all(|c| c.negated) {
+            return Err(error(0, "query needs at least one term to match"));
+        }
+        Ok(Self { clauses, highlights })
+    }
+}
+
+/// Highlight the query's words in `line`, ignoring case.
+fn highlight(line: &str, highlights: &[String]) -> String {
+    let lower = line.to_lowercase();
+    // Lowercasing can change byte lengths outside ASCII; skip highlighting then.
+    if lower.len() != line.len() {
+        return line.to_string();
+    }
+    let mut marked = vec![false; line.len()];
+    for needle in highlights.iter().filter(|h| !h.is_empty()) {
+        for (start, _) in lower.match_indices(needle.as_str()) {
+            marked[start..start + needle.len()].iter_mut().for_each(|m| *m = true);
+        }
+    }
+    let mut out = String::new();
+    let mut run_start = 0;
+    for i in 1..=line.len() {
+        if i == line.len() || (marked[i] != marked[run_start] && line.is_char_boundary(i)) {
+            let run = &line[run_start..i];
+            if marked[run_start] {
+                out.push_str(&run.red().bold().to_string());
+            } else {
+                out.push_str(run);
+            }
+            run_start = i;
+        }
+    }
+    out
+}
+
+/// Print hits grep-style with `context` lines around each, separating
+/// non-adjacent blocks with `--`.
+pub fn print_hits(
+    index: &SearchIndex,
+    log: &Path,
+    query: &SearchQuery,
+    hits: &[Line],
+    context: usize,
+) -> Result<(), AnalyzerError> {
+    let mut file = File::open(log)?;
+    let hit_lines: BTreeSet<usize> = hits.iter().map(|h| h.line_number).collect();
+    let mut last_printed: Option<usize> = None;
+    for hit in hits {
+        let first = hit.line_number.saturating_sub(context).max(1);
+        let last = (hit.line_number + context).min(index.line_count());
+        let first = match last_printed {
+            Some(printed) if printed + 1 >= first => printed + 1,
+            Some(_) => {
+                println!("{}", "--".bright_black());
+                first
+            }
+            None => first,
+        };
+        for line_number in first..=last {
+            let line = index.read_line(&mut file, line_number - 1)?;
+            if hit_lines.contains(&line_number) {
+                println!(
+                    "{}{} {}",
+                    line_number.to_string().green(),
+                    ":".bright_black(),
+                    highlight(&line.text, &query.highlights)
+                );
+            } else {
+                println!(
+                    "{}{} {}",
+                    line_number.to_string().green(),
+                    "-".bright_black(),
+                    line.text.bright_black()
+                );
+            }
+        }
+        last_printed = Some(last.max(last_printed.unwrap_or(0)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logfmt::{KeyMapping, LogfmtKeys};
+
+    const LOG: &str = "\
+2024-01-15 08:15:23.145 INFO 192.168.1.105 GET /api/users 200 45ms
+2024-01-15 08:15:28.567 ERROR 192.168.1.155 POST /api/orders 500 1023ms Database connection timeout
+
+2024-01-15 08:15:57.667 WARNING 192.168.1.167 GET /api/data/export 408 30000ms Request timeout
+{\"level\": \"error\", \"message\": \"Redis connection refused\", \"service\": \"cache\"}
+ts=2024-01-15T08:16:00Z level=info path=/health took=3ms
+";
+
+    fn log_file(contents: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("app.log");
+        fs::write(&log, contents).unwrap();
+        (dir, log)
+    }
+
+    fn search(index: &SearchIndex, log: &Path, query: &str) -> Vec<usize> {
+        let query = SearchQuery::parse(query).unwrap();
+        index.search(log, &query).unwrap().iter().map(|l| l.line_number).collect()
+    }
+
+    #[test]
+    fn terms_phrases_fields_and_exclusions() {
+        let (_dir, log) = log_file(LOG);
+        let index = SearchIndex::build(&log, &ParseOptions::default()).unwrap();
+        assert_eq!(index.line_count(), 6);
+        assert_eq!(search(&index, &log, "timeout"), vec![2, 4]);
+        assert_eq!(search(&index, &log, "timeout -status:5xx"), vec![4]);
+        assert_eq!(search(&index, &log, "\"connection timeout\""), vec![2]);
+        assert_eq!(search(&index, &log, "connection -\"connection timeout\""), vec![5]);
+        assert_eq!(search(&index, &log, "level:error"), vec![2, 5]);
+        assert_eq!(search(&index, &log, "endpoint:/api/*"), vec![1, 2, 4]);
+        assert_eq!(search(&index, &log, "service:cache"), vec![5]);
+        assert_eq!(search(&index, &log, "tim*"), vec![2, 4]);
+        assert_eq!(search(&index, &log, "nothing"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn reads_lines_from_the_log() {
+        let (_dir, log) = log_file(LOG);
+        let index = SearchIndex::build(&log, &ParseOptions::default()).unwrap();
+        let mut file = File::open(&log).unwrap();
+        assert_eq!(index.read_line(&mut file, 2).unwrap().text, "");
+        assert!(index.read_line(&mut file, 5).unwrap().text.ends_with("took=3ms"));
+    }
+
+    #[test]
+    fn uses_the_parse_options() {
+        let (_dir, log) = log_file(LOG);
+        let defaults = ParseOptions::default();
+        let options = ParseOptions {
+            logfmt_keys: LogfmtKeys::with(&["path=message".parse::<KeyMapping>().unwrap()]),
+            ..ParseOptions::default()
+        };
+        let (index, _) = SearchIndex::open(&log, &defaults).unwrap();
+        assert_eq!(search(&index, &log, "endpoint:/health"), vec![6]);
+        assert!(!index.is_current(&log, &options).unwrap());
+
+        let (index, rebuilt) = SearchIndex::open(&log, &options).unwrap();
+        assert!(rebuilt);
+        assert_eq!(search(&index, &log, "endpoint:/health"), Vec::<usize>::new());
+        assert!(!SearchIndex::open(&log, &options).unwrap().1);
+    }
+
+    #[test]
+    fn rebuilds_when_the_log_changes() {
+        let (_dir, log) = log_file(LOG);
+        let options = ParseOptions::default();
+        assert!(SearchIndex::open(&log, &options).unwrap().1);
+        assert!(!SearchIndex::open(&log, &options).unwrap().1);
+        fs::write(&log, format!("{LOG}2024-01-15 08:17:00.000 ERROR 10.0.0.1 GET /late 504 9ms\n")).unwrap();
+        let (index, rebuilt) = SearchIndex::open(&log, &options).unwrap();
+        assert!(rebuilt);
+        assert_eq!(search(&index, &log, "status:504"), vec![7]);
+    }
+
+    #[test]
+    fn rejects_other_files_as_index() {
+        let (_dir, log) = log_file(LOG);
+        let err = SearchIndex::read(&log).unwrap_err().to_string();
+        assert!(err.contains("is not a search index"), "{err}");
+    }
+
+    #[test]
+    fn query_errors() {
+        for (query, message) in [
+            ("\"open phrase", "unterminated quoted phrase"),
+            ("level:", "empty search term"),
+            ("-timeout", "at least one term"),
+            ("***", "has no words"),
+        ] {
+            let err = SearchQuery::parse(query).unwrap_err();
+            assert!(err.message.contains(message), "{query}: {err}");
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use crate::log_analyzer::LogEntry;
+use crate::text_report::{TextLayout, truncate};
+use chrono::{Duration, NaiveDateTime};
+use colored::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display, Formatter},
+    net::IpAddr,
+};
+
+lazy_static! {
+    static ref AUTH_ENDPOINT_PATTERN: Regex =
+        Regex::new(r"(?i)/(?:auth|login|signin|sign-in|session|token)\b").unwrap();
+    static ref ADMIN_ENDPOINT_PATTERN: Regex = Regex::new(r"(?i)/(?:admin|manage|internal)\b").unwrap();
+    static ref FAILED_LOGIN_PATTERN: Regex =
+        Regex::new(r"(?i)invalid credentials|login failed|authentication failed|bad password").unwrap();
+    static ref PAYLOAD_PATTERNS: Vec<(&'static str, Regex)> = vec![
+        ("path traversal", Regex::new(r"(?i)(?:\.\./|\.\.\\|%2e%2e(?:%2f|/|%5c)|/etc/passwd|win\.ini)").unwrap()),
+        ("sql injection", Regex::new(r"(?i)(?:'\s*or\s+'?\d|union(?:\s|%20|\+)+select|;\s*drop\s+table|'--|%27(?:%20|\+)*or|sleep\(\d+\))").unwrap()),
+        ("script injection", Regex::new(r"(?i)(?:<script|%3cscript|javascript:|onerror=)").unwrap()),
+    ];
+}
+
+/// Maximum number of evidence lines attached to a single finding.
+const MAX_EVIDENCE: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum FindingKind {
+    BruteForce,
+    PathScan,
+    ForbiddenBurst,
+    SuspiciousPayload,
+}
+
+impl Display for FindingKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            FindingKind::BruteForce => "Login brute force",
+            FindingKind::PathScan => "Path scanning",
+            FindingKind::ForbiddenBurst => "Forbidden burst",
+            FindingKind::SuspiciousPayload => "Suspicious payload",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let severity = match self {
+            Severity::Low => "LOW",
+            Severity::Medium => "MEDIUM",
+            Severity::High => "HIGH",
+        };
+        write!(f, "{severity}")
+    }
+}
+
+/// A raw log line backing a finding.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Evidence {
+    pub line_number: usize,
+    pub line: String,
+}
+
+impl From<&LogEntry> for Evidence {
+    fn from(entry: &LogEntry) -> Self {
+        Self {
+            line_number: entry.line_number,
+            line: entry.raw.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub severity: Severity,
+    pub source_ip: Option<IpAddr>,
+    pub summary: String,
+    pub evidence: Vec<Evidence>,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ip = self
+            .source_ip
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        write!(f, "[{}] {} from {}: {}", self.severity, self.kind, ip, self.summary)
+    }
+}
+
+/// Thresholds used by the detections. Every windowed detection counts events
+/// per source IP inside a sliding `window`.
+#[derive(Debug, Clone)]
+pub struct SecurityConfig {
+    pub window: Duration,
+    /// Failed logins against auth endpoints before a brute force is reported.
+    pub brute_force_threshold: usize,
+    /// Distinct endpoints answered with 404 before a scan is reported.
+    pub scan_threshold: usize,
+    /// 401/403 responses on admin routes before a burst is reported.
+    pub forbidden_threshold: usize,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::minutes(5),
+            brute_force_threshold: 5,
+            scan_threshold: 10,
+            forbidden_threshold: 3,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SecurityAnalyzer {
+    pub config: SecurityConfig,
+}
+
+impl SecurityAnalyzer {
+    pub fn new(config: SecurityConfig) -> Self {
+        Self { config }
+    }
+
+    /// Run every detection over the entries, most severe findings first.
+    pub fn analyze(&self, entries: &[LogEntry]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        findings.extend(self.detect_brute_force(entries));
+        findings.extend(self.detect_path_scans(entries));
+        findings.extend(self.detect_forbidden_bursts(entries));
+        findings.extend(self.detect_suspicious_payloads(entries));
+        findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+        findings
+    }
+
+    pub fn detect_brute_force(&self, entries: &[LogEntry]) -> Vec<Finding> {
+        let failed_logins = entries.iter().filter(|e| {
+            let is_auth = e
+                .endpoint
+                .as_deref()
+                .map(|endpoint| AUTH_ENDPOINT_PATTERN.is_match(endpoint))
+                .unwrap_or(false);
+            let failed = matches!(e.status_code, Some(401) | Some(403))
+                || e.message
+                    .as_deref()
+                    .map(|m| FAILED_LOGIN_PATTERN.is_match(m))
+                    .unwrap_or(false);
+            is_auth && failed
+        });
+
+        self.windowed(failed_logins, Score::Events)
+            .into_iter()
+            .filter(|(_, events)| events.len() >= self.config.brute_force_threshold)
+            .map(|(ip, events)| Finding {
+                kind: FindingKind::BruteForce,
+                severity: Severity::High,
+                source_ip: ip,
+                summary: format!(
+                    "{} failed logins within {}",
+                    events.len(),
+                    format_window(self.config.window)
+                ),
+                evidence: evidence(&events),
+            })
+            .collect()
+    }
+
+    pub fn detect_path_scans(&self, entries: &[LogEntry]) -> Vec<Finding> {
+        let not_found = entries.iter().filter(|e| e.status_code == Some(404));
+
+        self.windowed(not_found, Score::DistinctEndpoints)
+            .into_iter()
+            .filter(|(_, events)| distinct_endpoints(events) >= self.config.scan_threshold)
+            .map(|(ip, events)| Finding {
+                kind: FindingKind::PathScan,
+                severity: Severity::Medium,
+                source_ip: ip,
+                summary: format!(
+                    "{} distinct paths returned 404 within {}",
+                    distinct_endpoints(&events),
+                    format_window(self.config.window)
+                ),
+                evidence: evidence(&events),
+            })
+            .collect()
+    }
+
+    pub fn detect_forbidden_bursts(&self, entries: &[LogEntry]) -> Vec<Finding> {
+        let forbidden = entries.iter().filter(|e| {
+            matches!(e.status_code, Some(401) | Some(403))
+                && e.endpoint
+                    .as_deref()
+                    .map(|endpoint| ADMIN_ENDPOINT_PATTERN.is_match(endpoint))
+                    .unwrap_or(false)
+        });
+
+        self.windowed(forbidden, Score::Events)
+            .into_iter()
+            .filter(|(_, events)| events.len() >= self.config.forbidden_threshold)
+            .map(|(ip, events)| Finding {
+                kind: FindingKind::ForbiddenBurst,
+                severity: Severity::Medium,
+                source_ip: ip,
+                summary: format!(
+                    "{} denied requests to admin routes within {}",
+                    events.len(),
+                    format_window(self.config.window)
+                ),
+                evidence: evidence(&events),
+            })
+            .collect()
+    }
+
+    /// Flag requests whose path carries traversal, SQL or script injection
+    /// markers. One finding is produced per source IP and payload category,
+    /// ordered by first occurrence and then by category.
+    pub fn detect_suspicious_payloads(&self, entries: &[LogEntry]) -> Vec<Finding> {
+        let mut grouped: BTreeMap<(Option<IpAddr>, &'static str), Vec<&LogEntry>> = BTreeMap::new();
+        for entry in entries {
+            let Some(endpoint) = entry.endpoint.as_deref() else {
+                continue;
+            };
+            for (label, pattern) in PAYLOAD_PATTERNS.iter() {
+                if pattern.is_match(endpoint) {
+                    grouped.entry((entry.ip_address, label)).or_default().push(entry);
+                }
+            }
+        }
+
+        let mut grouped: Vec<_> = grouped.into_iter().collect();
+        grouped.sort_by_key(|((_, label), events)| (events[0].line_number, *label));
+        grouped
+            .into_iter()
+            .map(|((ip, label), events)| Finding {
+                kind: FindingKind::SuspiciousPayload,
+                severity: Severity::High,
+                source_ip: ip,
+                summary: format!("{} request(s) with {} markers", events.len(), label),
+                evidence: evidence(&events),
+            })
+            .collect()
+    }
+
+    /// Group events by source IP and return, for each IP, the densest window
+    /// according to `score`. Entries without a parsable timestamp are ignored.
+    fn windowed<'a, I>(&self, events: I, score: Score) -> Vec<(Option<IpAddr>, Vec<&'a LogEntry>)>
+    where
+        I: Iterator<Item = &'a LogEntry>,
+    {
+        let mut by_ip: HashMap<Option<IpAddr>, Vec<(NaiveDateTime, &LogEntry)>> = HashMap::new();
+        for entry in events {
+            if let Some(ts) = entry.parsed_timestamp() {
+                by_ip.entry(entry.ip_address).or_default().push((ts, entry));
+            }
+        }
+
+        let mut result: Vec<_> = by_ip
+            .into_iter()
+            .map(|(ip, mut events)| {
+                events.sort_by_key(|(ts, _)| *ts);
+                let entries: Vec<&LogEntry> = events.iter().map(|(_, e)| *e).collect();
+                let mut best = (0, 0, 0);
+                let mut start = 0;
+                // Events per endpoint inside the window, kept up to date as it
+                // slides so each step is constant work.
+                let mut endpoints: HashMap<&str, usize> = HashMap::new();
+                for end in 0..events.len() {
+                    if let Some(endpoint) = entries[end].endpoint.as_deref() {
+                        *endpoints.entry(endpoint).or_default() += 1;
+                    }
+                    while events[end].0 - events[start].0 > self.config.window {
+                        if let Some(endpoint) = entries[start].endpoint.as_deref()
+                            && let Some(count) = endpoints.get_mut(endpoint)
+                        {
+                            *count -= 1;
+                            if *count == 0 {
+                                endpoints.remove(endpoint);
+                            }
+                        }
+                        start += 1;
+                    }
+                    let window_score = match score {
+                        Score::Events => end - start + 1,
+                        Score::DistinctEndpoints => endpoints.len(),
+                    };
+                    if window_score > best.0 {
+                        best = (window_score, start, end);
+                    }
+                }
+                let (_, start, end) = best;
+                (ip, entries[start..=end].to_vec())
+            })
+            .collect();
+        result.sort_by_key(|(ip, _)| *ip);
+        result
+    }
+}
+
+/// What makes a window dense.
+#[derive(Debug, Clone, Copy)]
+enum Score {
+    Events,
+    DistinctEndpoints,
+}
+
+fn distinct_endpoints(events: &[&LogEntry]) -> usize {
+    let mut endpoints: Vec<&str> = events.iter().filter_map(|e| e.endpoint.as_deref()).collect();
+    endpoints.sort_unstable();
+    endpoints.dedup();
+    endpoints.len()
+}
+
+fn evidence(events: &[&LogEntry]) -> Vec<Evidence> {
+    events.iter().take(MAX_EVIDENCE).map(|e| Evidence::from(*e)).collect()
+}
+
+fn format_window(window: Duration) -> String {
+    let seconds = window.num_seconds();
+    if seconds % 3600 == 0 {
+        format!("{}h", seconds / 3600)
+    } else if seconds % 60 == 0 {
+        format!("{}m", seconds / 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Print the security findings section in the same style as the main report.
+pub fn print_findings(findings: &[Finding], layout: &TextLayout) {
+    if findings.is_empty() {
+        let title = layout.heading("🛡 ", "SECURITY ANALYSIS: No suspicious activity detected");
+        println!("\n{}", title.bold().green());
+        return;
+    }
+
+    println!("{}", layout.title("🛡 ", "SECURITY ANALYSIS"));
+
+    for finding in findings {
+        let severity = match finding.severity {
+            Severity::High => finding.severity.to_string().red().bold(),
+            Severity::Medium => finding.severity.to_string().yellow(),
+            Severity::Low => finding.severity.to_string().bright_blue(),
+        };
+        let ip = finding
+            .source_ip
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        println!(
+            "\n  [{}] {} from {}",
+            severity,
+            finding.kind.to_string().bright_white().bold(),
+            ip.bright_cyan()
+        );
+        println!("  {}", finding.summary);
+        for line in &finding.evidence {
+            println!(
+                "    {} {}",
+                format!("{:>5}:", line.line_number).bright_black(),
+                truncate(&line.line, layout.width.saturating_sub(11), layout.ascii)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A request from `ip` at `seconds` past 08:00, logged on that line.
+    fn request(seconds: u32, ip: &str, method: &str, endpoint: &str, status: u16) -> LogEntry {
+        let line = format!(
+            "2024-01-15 08:{:02}:{:02}.000 INFO {ip} {method} {endpoint} {status} 12ms",
+            seconds / 60,
+            seconds % 60
+        );
+        LogEntry { line_number: seconds as usize, ..LogEntry::parse_log(&line).unwrap() }
+    }
+
+    fn analyzer() -> SecurityAnalyzer {
+        SecurityAnalyzer::default()
+    }
+
+    #[test]
+    fn brute_force_needs_the_threshold_within_the_window() {
+        let logins = |n: u32, spacing: u32| -> Vec<LogEntry> {
+            (0..n).map(|i| request(i * spacing, "10.0.0.9", "POST", "/api/login", 401)).collect()
+        };
+        assert!(analyzer().detect_brute_force(&logins(4, 1)).is_empty());
+        let findings = analyzer().detect_brute_force(&logins(5, 1));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].source_ip, Some("10.0.0.9".parse().unwrap()));
+        assert_eq!(findings[0].summary, "5 failed logins within 5m");
+        assert_eq!(findings[0].evidence.len(), 5);
+        // Five attempts spanning exactly the window count; a second more doesn't.
+        assert_eq!(analyzer().detect_brute_force(&logins(5, 75)).len(), 1);
+        assert!(analyzer().detect_brute_force(&logins(5, 76)).is_empty());
+
+        let mut entries = logins(4, 1);
+        entries.push(request(5, "10.0.0.8", "POST", "/api/login", 401));
+        entries.push(request(6, "10.0.0.9", "POST", "/api/orders", 401));
+        entries.push(request(7, "10.0.0.9", "POST", "/api/login", 200));
+        assert!(analyzer().detect_brute_force(&entries).is_empty(), "other IPs, endpoints and successes don't count");
+    }
+
+    #[test]
+    fn path_scans_count_distinct_paths() {
+        let probes = |n: u32| -> Vec<LogEntry> {
+            (0..n).map(|i| request(i, "10.0.0.7", "GET", &format!("/probe/{i}"), 404)).collect()
+        };
+        assert!(analyzer().detect_path_scans(&probes(9)).is_empty());
+        let findings = analyzer().detect_path_scans(&probes(10));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].summary, "10 distinct paths returned 404 within 5m");
+        assert_eq!(findings[0].evidence.len(), MAX_EVIDENCE);
+
+        let mut repeated = probes(9);
+        repeated.extend((0..20).map(|i| request(10 + i, "10.0.0.7", "GET", "/probe/0", 404)));
+        assert!(analyzer().detect_path_scans(&repeated).is_empty(), "repeats of one path are not a scan");
+
+        // Paths that slid out of the window stop counting.
+        let mut spread: Vec<LogEntry> = (0..5).map(|i| request(i, "10.0.0.7", "GET", &format!("/a/{i}"), 404)).collect();
+        spread.extend((0..5).map(|i| request(400 + i, "10.0.0.7", "GET", &format!("/b/{i}"), 404)));
+        assert!(analyzer().detect_path_scans(&spread).is_empty());
+        spread.extend((0..5).map(|i| request(410 + i, "10.0.0.7", "GET", &format!("/a/{i}"), 404)));
+        assert_eq!(analyzer().detect_path_scans(&spread).len(), 1);
+    }
+
+    #[test]
+    fn forbidden_bursts_target_admin_routes() {
+        let denied = |n: u32| -> Vec<LogEntry> {
+            (0..n).map(|i| request(i, "10.0.0.5", "GET", "/admin/users", 403)).collect()
+        };
+        assert!(analyzer().detect_forbidden_bursts(&denied(2)).is_empty());
+        let findings = analyzer().detect_forbidden_bursts(&denied(3));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].summary, "3 denied requests to admin routes within 5m");
+        let other: Vec<LogEntry> = (0..5).map(|i| request(i, "10.0.0.5", "GET", "/api/users", 403)).collect();
+        assert!(analyzer().detect_forbidden_bursts(&other).is_empty());
+    }
+
+    #[test]
+    fn payloads_are_reported_per_ip_and_category() {
+        let entries = [
+            request(0, "10.0.0.3", "GET", "/files/../../etc/passwd", 400),
+            request(1, "10.0.0.3", "GET", "/files?name=..%2F..%2Fwin.ini", 400),
+            request(2, "10.0.0.3", "GET", "/search?q=1%27%20or%201=1", 200),
+            request(3, "10.0.0.4", "GET", "/search?q=<script>alert(1)</script>", 200),
+            request(4, "10.0.0.4", "GET", "/search?q=union+select+password", 200),
+            request(5, "10.0.0.4", "GET", "/api/users?page=2", 200),
+        ];
+        let findings = analyzer().detect_suspicious_payloads(&entries);
+        let summaries: Vec<(String, &str)> = findings
+            .iter()
+            .map(|f| (f.source_ip.unwrap().to_string(), f.summary.as_str()))
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                ("10.0.0.3".to_string(), "2 request(s) with path traversal markers"),
+                ("10.0.0.3".to_string(), "1 request(s) with sql injection markers"),
+                ("10.0.0.4".to_string(), "1 request(s) with script injection markers"),
+                ("10.0.0.4".to_string(), "1 request(s) with sql injection markers"),
+            ]
+        );
+        assert_eq!(findings[0].evidence.iter().map(|e| e.line_number).collect::<Vec<_>>(), [0, 1]);
+    }
+
+    #[test]
+    fn findings_are_sorted_by_severity() {
+        let mut entries: Vec<LogEntry> = (0..3).map(|i| request(i, "10.0.0.5", "GET", "/admin", 403)).collect();
+        entries.push(request(10, "10.0.0.6", "GET", "/../etc/passwd", 404));
+        let severities: Vec<Severity> = analyzer().analyze(&entries).iter().map(|f| f.severity).collect();
+        assert_eq!(severities, [Severity::High, Severity::Medium]);
+    }
+
+    #[test]
+    fn payload_categories_of_one_request_are_ordered() {
+        let entries = [request(0, "10.0.0.3", "GET", "/../etc/passwd?q=union+select+1", 400)];
+        for _ in 0..10 {
+            let findings = analyzer().analyze(&entries);
+            let summaries: Vec<&str> = findings.iter().map(|f| f.summary.as_str()).collect();
+            assert_eq!(summaries, [
+                "1 request(s) with path traversal markers",
+                "1 request(s) with sql injection markers",
+            ]);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! grep-style rendering of matched entries with surrounding context.
+
+use crate::log_analyzer::{AnalyzerError, LogEntry, LogLevel, parse_duration};
+use chrono::Duration;
+use colored::*;
+use regex::Regex;
+use std::{collections::BTreeSet, str::FromStr};
+
+/// How much context to show on one side of a match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContextSpan {
+    Lines(usize),
+    /// Every entry whose timestamp is within this distance of the match.
+    Time(Duration),
+}
+
+impl FromStr for ContextSpan {
+    type Err = AnalyzerError;
+
+    /// A bare number is a line count; anything with a unit (`5s`, `2m`) is a
+    /// time span.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(lines) = s.parse() {
+            return Ok(ContextSpan::Lines(lines));
+        }
+        parse_duration(s).map(ContextSpan::Time)
+    }
+}
+
+/// How JSON entries are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum JsonStyle {
+    /// As written in the file.
+    #[default]
+    Raw,
+    /// Re-serialized compactly on one line.
+    Line,
+    /// Pretty-printed over several lines.
+    Expanded,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShowOptions {
+    pub before: Option<ContextSpan>,
+    pub after: Option<ContextSpan>,
+    /// Patterns highlighted inside matched lines.
+    pub highlights: Vec<Regex>,
+    pub json_style: JsonStyle,
+}
+
+/// Index range of the entries to print around the match at `index`. A span
+/// reaching past the representable time range is unbounded.
+fn context_range(entries: &[LogEntry], index: usize, options: &ShowOptions) -> (usize, usize) {
+    let timestamp = entries[index].parsed_timestamp();
+    let first = match (options.before, timestamp) {
+        (Some(ContextSpan::Lines(n)), _) => index.saturating_sub(n),
+        (Some(ContextSpan::Time(span)), Some(ts)) => {
+            let start = ts.checked_sub_signed(span);
+            let mut first = index;
+            // Entries without a timestamp go along with their neighbours.
+            while first > 0
+                && entries[first - 1]
+                    .parsed_timestamp()
+                    .is_none_or(|t| start.is_none_or(|start| t >= start))
+            {
+                first -= 1;
+            }
+            first
+        }
+        _ => index,
+    };
+    let last = match (options.after, timestamp) {
+        (Some(ContextSpan::Lines(n)), _) => (index + n).min(entries.len() - 1),
+        (Some(ContextSpan::Time(span)), Some(ts)) => {
+            let end = ts.checked_add_signed(span);
+            let mut last = index;
+            while last + 1 < entries.len()
+                && entries[last + 1]
+                    .parsed_timestamp()
+                    .is_none_or(|t| end.is_none_or(|end| t <= end))
+            {
+                last += 1;
+            }
+            last
+        }
+        _ => index,
+    };
+    (first, last)
+}
+
+/// The entry's text, with JSON re-rendered according to `style`.
+fn entry_text(entry: &LogEntry, style: JsonStyle) -> String {
+    if style != JsonStyle::Raw
+        && entry.raw.trim_start().starts_with('{')
+        && let Ok(value) = serde_json::from_str::<serde_json::Value>(&entry.raw)
+    {
+        let rendered = match style {
+            JsonStyle::Expanded => serde_json::to_string_pretty(&value),
+            _ => serde_json::to_string(&value),
+        };
+        if let Ok(rendered) = rendered {
+            return rendered;
+        }
+    }
+    entry.raw.clone()
+}
+
+fn level_colored(text: &str, level: Option<&LogLevel>) -> ColoredString {
+    match level {
+        Some(LogLevel::Error) => text.red(),
+        Some(LogLevel::Warning) => text.yellow(),
+        _ => text.normal(),
+    }
+}
+
+/// Color `text` by level, with every highlight match in reverse video.
+fn render_match(text: &str, level: Option<&LogLevel>, highlights: &[Regex]) -> String {
+    let mut spans: Vec<(usize, usize)> = highlights
+        .iter()
+        .flat_map(|re| re.find_iter(text).map(|m| (m.start(), m.end())))
+        .filter(|(start, end)| start < end)
+        .collect();
+    spans.sort();
+
+    let mut out = String::new();
+    let mut pos = 0;
+    for (start, end) in spans {
+        if end <= pos {
+            continue;
+        }
+        let start = start.max(pos);
+        out.push_str(&level_colored(&text[pos..start], level).to_string());
+        out.push_str(&text[start..end].bold().reversed().to_string());
+        pos = end;
+    }
+    out.push_str(&level_colored(&text[pos..], level).to_string());
+    out
+}
+
+/// Index ranges of the entries to print for the matches in `matched`, one
+/// per block: the contexts of overlapping or adjacent matches are merged.
+fn blocks(entries: &[LogEntry], matched: &BTreeSet<usize>, options: &ShowOptions) -> Vec<(usize, usize)> {
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if !matched.contains(&entry.line_number) {
+            continue;
+        }
+        let (first, last) = context_range(entries, index, options);
+        match blocks.last_mut() {
+            Some((_, until)) if *until + 1 >= first => *until = (*until).max(last),
+            _ => blocks.push((first, last)),
+        }
+    }
+    blocks
+}
+
+/// Print the entries whose line numbers are in `matched`, with context from
+/// `entries` (all entries of the file, in order). Non-adjacent blocks are
+/// separated by `--`, like grep. Returns the number of matches printed.
+pub fn print_matches(entries: &[LogEntry], matched: &BTreeSet<usize>, options: &ShowOptions) -> usize {
+    let mut count = 0;
+    for (block, (first, last)) in blocks(entries, matched, options).into_iter().enumerate() {
+        if block > 0 {
+            println!("{}", "--".bright_black());
+        }
+        for entry in &entries[first..=last] {
+            let is_match = matched.contains(&entry.line_number);
+            let text = entry_text(entry, options.json_style);
+            let (separator, body) = if is_match {
+                count += 1;
+                (":", render_match(&text, entry.level.as_ref(), &options.highlights))
+            } else {
+                ("-", text.bright_black().to_string())
+            };
+            println!(
+                "{}{} {}",
+                entry.line_number.to_string().green(),
+                separator.bright_black(),
+                body
+            );
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entries one per line, numbered from 1, at the given seconds past 08:00.
+    fn entries(seconds: &[Option<u32>]) -> Vec<LogEntry> {
+        seconds
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let line = match s {
+                    Some(s) => format!("2024-01-15 08:{:02}:{:02}.000 INFO 10.0.0.1 GET /x 200 1ms", s / 60, s % 60),
+                    None => "{\"message\": \"no timestamp\"}".to_string(),
+                };
+                let mut entry = LogEntry::parse_log(&line).unwrap();
+                entry.line_number = i + 1;
+                entry
+            })
+            .collect()
+    }
+
+    fn options(before: Option<&str>, after: Option<&str>) -> ShowOptions {
+        ShowOptions {
+            before: before.map(|s| s.parse().unwrap()),
+            after: after.map(|s| s.parse().unwrap()),
+            ..ShowOptions::default()
+        }
+    }
+
+    #[test]
+    fn line_context_is_clamped_to_the_file() {
+        let entries = entries(&[Some(0), Some(1), Some(2), Some(3), Some(4)]);
+        assert_eq!(context_range(&entries, 1, &options(Some("3"), Some("1"))), (0, 2));
+        assert_eq!(context_range(&entries, 3, &options(None, Some("5"))), (3, 4));
+        assert_eq!(context_range(&entries, 2, &options(None, None)), (2, 2));
+    }
+
+    #[test]
+    fn time_context_includes_entries_within_the_span() {
+        let entries = entries(&[Some(0), Some(10), None, Some(14), Some(15), Some(16), Some(30)]);
+        // 15s ± 5s: 10s, the untimestamped line and 14s before; 16s after.
+        assert_eq!(context_range(&entries, 4, &options(Some("5s"), Some("5s"))), (1, 5));
+        // The untimestamped line goes along with 14s; 30s is exactly 15s after.
+        assert_eq!(context_range(&entries, 4, &options(Some("1s"), Some("15s"))), (2, 6));
+    }
+
+    #[test]
+    fn huge_time_spans_are_unbounded() {
+        let entries = entries(&[Some(0), Some(10), Some(20)]);
+        let span = ContextSpan::Time(Duration::MAX);
+        let options = ShowOptions { before: Some(span), after: Some(span), ..ShowOptions::default() };
+        assert_eq!(context_range(&entries, 1, &options), (0, 2));
+    }
+
+    #[test]
+    fn adjacent_and_overlapping_blocks_merge() {
+        let entries = entries(&[Some(0), Some(1), Some(2), Some(3), Some(4), Some(5), Some(6), Some(7), Some(8)]);
+        let matched = BTreeSet::from([2, 4, 9]);
+        // Line 2 with one line of context is 1..=3, line 4 is 3..=5: overlapping.
+        assert_eq!(blocks(&entries, &matched, &options(Some("1"), Some("1"))), vec![(0, 4), (7, 8)]);
+        // Without context, lines 2 and 4 are not adjacent; 6 and 7 are.
+        let matched = BTreeSet::from([2, 4, 6, 7]);
+        assert_eq!(blocks(&entries, &matched, &options(None, None)), vec![(1, 1), (3, 3), (5, 6)]);
+    }
+}
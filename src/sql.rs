@@ -0,0 +1,1315 @@
+//! A small SQL dialect over parsed entries.
+//!
+//! ```text
+//! SELECT endpoint, count(*) AS hits, p95(response_time)
+//! FROM logs
+//! WHERE method = 'POST' AND timestamp > '2024-01-15 08:16'
+//! GROUP BY endpoint
+//! HAVING hits > 5
+//! ORDER BY hits DESC
+//! LIMIT 10
+//! ```
+//!
+//! Every entry is a row of the single table `logs`. The typed columns are
+//! `timestamp`, `level`, `ip`, `method`, `endpoint`, `status`,
+//! `response_time`, `message` and `line_number`; any other column name is
+//! looked up in the entry's extra JSON fields, with dotted paths such as
+//! `error.code` reaching into nested objects.
+
+use crate::aggregate::Agg;
+use crate::export::Delimited;
+use crate::log_analyzer::LogEntry;
+use crate::query::QueryError;
+use regex::Regex;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Display, Formatter},
+};
+
+/// Typed columns, in the order `SELECT *` returns them.
+pub const COLUMNS: [&str; 8] = [
+    "timestamp",
+    "level",
+    "ip",
+    "method",
+    "endpoint",
+    "status",
+    "response_time",
+    "message",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl SqlValue {
+    fn from_json(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => SqlValue::Null,
+            serde_json::Value::Bool(b) => SqlValue::Bool(*b),
+            serde_json::Value::Number(n) => n.as_f64().map(SqlValue::Number).unwrap_or(SqlValue::Null),
+            serde_json::Value::String(s) => SqlValue::Text(s.clone()),
+            other => SqlValue::Text(other.to_string()),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            SqlValue::Null => serde_json::Value::Null,
+            SqlValue::Bool(b) => serde_json::Value::Bool(*b),
+            SqlValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => {
+                serde_json::Value::from(*n as i64)
+            }
+            SqlValue::Number(n) => serde_json::Number::from_f64(*n)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            SqlValue::Text(s) => serde_json::Value::String(s.clone()),
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            SqlValue::Number(n) => Some(*n),
+            SqlValue::Text(s) => s.parse().ok(),
+            SqlValue::Bool(b) => Some(f64::from(u8::from(*b))),
+            SqlValue::Null => None,
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            SqlValue::Null => false,
+            SqlValue::Bool(b) => *b,
+            SqlValue::Number(n) => *n != 0.0,
+            SqlValue::Text(s) => !s.is_empty(),
+        }
+    }
+
+    /// SQL comparison: NULL compares to nothing, numbers compare numerically
+    /// (text is coerced when it parses as a number), everything else as text.
+    fn compare(&self, other: &SqlValue) -> Option<Ordering> {
+        match (self, other) {
+            (SqlValue::Null, _) | (_, SqlValue::Null) => None,
+            (SqlValue::Text(a), SqlValue::Text(b)) => Some(a.cmp(b)),
+            (a, b) => match (a.as_f64(), b.as_f64()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => Some(a.to_string().cmp(&b.to_string())),
+            },
+        }
+    }
+
+    /// Total order used by ORDER BY, with NULLs first.
+    fn sort_cmp(&self, other: &SqlValue) -> Ordering {
+        match (self, other) {
+            (SqlValue::Null, SqlValue::Null) => Ordering::Equal,
+            (SqlValue::Null, _) => Ordering::Less,
+            (_, SqlValue::Null) => Ordering::Greater,
+            _ => self.compare(other).unwrap_or(Ordering::Equal),
+        }
+    }
+}
+
+impl Display for SqlValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SqlValue::Null => Ok(()),
+            SqlValue::Bool(b) => write!(f, "{b}"),
+            SqlValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            SqlValue::Number(n) => write!(f, "{n}"),
+            SqlValue::Text(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// Value of a column for one entry.
+pub fn column_value(entry: &LogEntry, column: &str) -> SqlValue {
+    let text = |s: Option<String>| s.map(SqlValue::Text).unwrap_or(SqlValue::Null);
+    match column {
+        "timestamp" => match entry.parsed_timestamp() {
+            Some(ts) => SqlValue::Text(ts.format("%Y-%m-%d %H:%M:%S%.3f").to_string()),
+            None => text(entry.timestamp.clone()),
+        },
+        "level" => text(entry.level.as_ref().map(|l| l.to_string())),
+        "ip" | "ip_address" => text(entry.ip_address.map(|ip| ip.to_string())),
+        "method" => text(entry.method.as_ref().map(|m| m.to_string())),
+        "endpoint" | "path" => text(entry.endpoint.clone()),
+        "status" | "status_code" => entry
+            .status_code
+            .map(|s| SqlValue::Number(f64::from(s)))
+            .unwrap_or(SqlValue::Null),
+        "response_time" => entry.response_time.map(SqlValue::Number).unwrap_or(SqlValue::Null),
+        "message" => text(entry.message.clone()),
+        "line_number" => SqlValue::Number(entry.line_number as f64),
+        "raw" => SqlValue::Text(entry.raw.clone()),
+        other => entry
+            .extra_value(other)
+            .map(SqlValue::from_json)
+            .unwrap_or(SqlValue::Null),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    And,
+    Or,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Column(String),
+    Literal(SqlValue),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    Like { expr: Box<Expr>, pattern: Regex, negated: bool },
+    InList { expr: Box<Expr>, list: Vec<Expr>, negated: bool },
+    Between { expr: Box<Expr>, low: Box<Expr>, high: Box<Expr>, negated: bool },
+    IsNull { expr: Box<Expr>, negated: bool },
+    Lower(Box<Expr>),
+    Upper(Box<Expr>),
+    /// An aggregate of the argument's non-null values, whose field is the
+    /// argument as written. `None` is `count(*)`.
+    Aggregate(Agg, Option<Box<Expr>>),
+}
+
+impl Expr {
+    fn has_aggregate(&self) -> bool {
+        match self {
+            Expr::Aggregate(..) => true,
+            Expr::Column(_) | Expr::Literal(_) => false,
+            Expr::Not(e) | Expr::Neg(e) | Expr::Lower(e) | Expr::Upper(e) => e.has_aggregate(),
+            Expr::Binary(l, _, r) => l.has_aggregate() || r.has_aggregate(),
+            Expr::Like { expr, .. } | Expr::IsNull { expr, .. } => expr.has_aggregate(),
+            Expr::InList { expr, list, .. } => expr.has_aggregate() || list.iter().any(Expr::has_aggregate),
+            Expr::Between { expr, low, high, .. } => {
+                expr.has_aggregate() || low.has_aggregate() || high.has_aggregate()
+            }
+        }
+    }
+
+    /// Replace columns named by a select alias with the aliased expression.
+    fn resolve_aliases(&mut self, items: &[SelectItem]) {
+        match self {
+            Expr::Column(name) => {
+                if let Some(item) = items.iter().find(|i| i.name == *name) {
+                    *self = item.expr.clone();
+                }
+            }
+            Expr::Literal(_) | Expr::Aggregate(..) => {}
+            Expr::Not(e) | Expr::Neg(e) | Expr::Lower(e) | Expr::Upper(e) => e.resolve_aliases(items),
+            Expr::Binary(l, _, r) => {
+                l.resolve_aliases(items);
+                r.resolve_aliases(items);
+            }
+            Expr::Like { expr, .. } | Expr::IsNull { expr, .. } => expr.resolve_aliases(items),
+            Expr::InList { expr, list, .. } => {
+                expr.resolve_aliases(items);
+                list.iter_mut().for_each(|e| e.resolve_aliases(items));
+            }
+            Expr::Between { expr, low, high, .. } => {
+                for e in [expr, low, high] {
+                    e.resolve_aliases(items);
+                }
+            }
+        }
+    }
+
+    /// Evaluate against a group of rows. Aggregates consume the whole group;
+    /// plain columns read the group's first row.
+    fn eval(&self, rows: &[&LogEntry]) -> SqlValue {
+        match self {
+            Expr::Column(name) => rows
+                .first()
+                .map(|row| column_value(row, name))
+                .unwrap_or(SqlValue::Null),
+            Expr::Literal(value) => value.clone(),
+            Expr::Not(e) => match e.eval(rows) {
+                SqlValue::Null => SqlValue::Null,
+                v => SqlValue::Bool(!v.is_truthy()),
+            },
+            Expr::Neg(e) => e
+                .eval(rows)
+                .as_f64()
+                .map(|n| SqlValue::Number(-n))
+                .unwrap_or(SqlValue::Null),
+            Expr::Binary(lhs, op, rhs) => eval_binary(lhs.eval(rows), *op, || rhs.eval(rows)),
+            Expr::Like { expr, pattern, negated } => match expr.eval(rows) {
+                SqlValue::Null => SqlValue::Null,
+                v => SqlValue::Bool(pattern.is_match(&v.to_string()) != *negated),
+            },
+            Expr::InList { expr, list, negated } => {
+                let value = expr.eval(rows);
+                if value == SqlValue::Null {
+                    return SqlValue::Null;
+                }
+                let found = list
+                    .iter()
+                    .any(|item| value.compare(&item.eval(rows)) == Some(Ordering::Equal));
+                SqlValue::Bool(found != *negated)
+            }
+            Expr::Between { expr, low, high, negated } => {
+                let value = expr.eval(rows);
+                match (value.compare(&low.eval(rows)), value.compare(&high.eval(rows))) {
+                    (Some(lo), Some(hi)) => {
+                        SqlValue::Bool((lo != Ordering::Less && hi != Ordering::Greater) != *negated)
+                    }
+                    _ => SqlValue::Null,
+                }
+            }
+            Expr::IsNull { expr, negated } => SqlValue::Bool((expr.eval(rows) == SqlValue::Null) != *negated),
+            Expr::Lower(e) => match e.eval(rows) {
+                SqlValue::Null => SqlValue::Null,
+                v => SqlValue::Text(v.to_string().to_lowercase()),
+            },
+            Expr::Upper(e) => match e.eval(rows) {
+                SqlValue::Null => SqlValue::Null,
+                v => SqlValue::Text(v.to_string().to_uppercase()),
+            },
+            Expr::Aggregate(agg, arg) => eval_aggregate(agg, arg.as_deref(), rows),
+        }
+    }
+}
+
+fn eval_binary(lhs: SqlValue, op: BinOp, rhs: impl FnOnce() -> SqlValue) -> SqlValue {
+    match op {
+        BinOp::And => {
+            if lhs != SqlValue::Null && !lhs.is_truthy() {
+                return SqlValue::Bool(false);
+            }
+            let rhs = rhs();
+            match (lhs, rhs) {
+                (_, r) if r != SqlValue::Null && !r.is_truthy() => SqlValue::Bool(false),
+                (SqlValue::Null, _) | (_, SqlValue::Null) => SqlValue::Null,
+                _ => SqlValue::Bool(true),
+            }
+        }
+        BinOp::Or => {
+            if lhs.is_truthy() {
+                return SqlValue::Bool(true);
+            }
+            let rhs = rhs();
+            match (lhs, rhs) {
+                (_, r) if r.is_truthy() => SqlValue::Bool(true),
+                (SqlValue::Null, _) | (_, SqlValue::Null) => SqlValue::Null,
+                _ => SqlValue::Bool(false),
+            }
+        }
+        BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+            let Some(ordering) = lhs.compare(&rhs()) else {
+                return SqlValue::Null;
+            };
+            SqlValue::Bool(match op {
+                BinOp::Eq => ordering == Ordering::Equal,
+                BinOp::Ne => ordering != Ordering::Equal,
+                BinOp::Lt => ordering == Ordering::Less,
+                BinOp::Le => ordering != Ordering::Greater,
+                BinOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            })
+        }
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => {
+            let (Some(a), Some(b)) = (lhs.as_f64(), rhs().as_f64()) else {
+                return SqlValue::Null;
+            };
+            match op {
+                BinOp::Add => SqlValue::Number(a + b),
+                BinOp::Sub => SqlValue::Number(a - b),
+                BinOp::Mul => SqlValue::Number(a * b),
+                _ if b == 0.0 => SqlValue::Null,
+                _ => SqlValue::Number(a / b),
+            }
+        }
+    }
+}
+
+fn eval_aggregate(agg: &Agg, arg: Option<&Expr>, rows: &[&LogEntry]) -> SqlValue {
+    let Some(arg) = arg else {
+        return SqlValue::Number(rows.len() as f64);
+    };
+    let values: Vec<SqlValue> = rows
+        .iter()
+        .map(|row| arg.eval(std::slice::from_ref(row)))
+        .filter(|v| *v != SqlValue::Null)
+        .collect();
+    match agg {
+        // Unlike the numeric aggregates, these also apply to text.
+        Agg::Count => SqlValue::Number(values.len() as f64),
+        Agg::CountDistinct(_) => {
+            SqlValue::Number(values.iter().map(SqlValue::to_string).collect::<BTreeSet<_>>().len() as f64)
+        }
+        Agg::Min(_) => values
+            .into_iter()
+            .min_by(|a, b| a.sort_cmp(b))
+            .unwrap_or(SqlValue::Null),
+        Agg::Max(_) => values
+            .into_iter()
+            .max_by(|a, b| a.sort_cmp(b))
+            .unwrap_or(SqlValue::Null),
+        _ => {
+            let numbers: Vec<f64> = values.iter().filter_map(SqlValue::as_f64).collect();
+            agg.of_numbers(&numbers).map(SqlValue::Number).unwrap_or(SqlValue::Null)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectItem {
+    pub expr: Expr,
+    /// Column header: the alias, or the expression as written.
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderBy {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+/// A parsed `SELECT` statement. `items` is `None` for `SELECT *`.
+#[derive(Debug, Clone)]
+pub struct Select {
+    pub items: Option<Vec<SelectItem>>,
+    pub filter: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    /// Filter on the groups, evaluated after aggregation.
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderBy>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// Query output: column names and rows of values.
+#[derive(Debug, Clone, Default)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<SqlValue>>,
+}
+
+impl Select {
+    pub fn parse(sql: &str) -> Result<Self, QueryError> {
+        let tokens = tokenize(sql)?;
+        let mut parser = Parser { sql, tokens, pos: 0 };
+        let select = parser.parse_select()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.error_at(token.start, format!("unexpected '{}'", token.text)));
+        }
+        Ok(select)
+    }
+
+    fn is_aggregate(&self) -> bool {
+        !self.group_by.is_empty()
+            || self.having.is_some()
+            || self
+                .items
+                .iter()
+                .flatten()
+                .any(|item| item.expr.has_aggregate())
+    }
+
+    pub fn execute(&self, entries: &[LogEntry]) -> ResultSet {
+        let rows: Vec<&LogEntry> = entries
+            .iter()
+            .filter(|entry| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|f| f.eval(std::slice::from_ref(entry)).is_truthy())
+            })
+            .collect();
+
+        let items = match &self.items {
+            Some(items) => items.clone(),
+            None => star_items(&rows),
+        };
+        let columns: Vec<String> = items.iter().map(|i| i.name.clone()).collect();
+
+        // Each output row keeps the rows it was computed from, so ORDER BY can
+        // use expressions that are not in the select list.
+        let groups: Vec<Vec<&LogEntry>> = if self.is_aggregate() {
+            let mut order: Vec<Vec<String>> = Vec::new();
+            let mut groups: HashMap<Vec<String>, Vec<&LogEntry>> = HashMap::new();
+            for row in rows {
+                let key: Vec<String> = self
+                    .group_by
+                    .iter()
+                    .map(|e| format!("{:?}", e.eval(std::slice::from_ref(&row))))
+                    .collect();
+                groups
+                    .entry(key.clone())
+                    .or_insert_with(|| {
+                        order.push(key);
+                        Vec::new()
+                    })
+                    .push(row);
+            }
+            if order.is_empty() && self.group_by.is_empty() {
+                vec![Vec::new()]
+            } else {
+                order.into_iter().map(|k| groups.remove(&k).unwrap_or_default()).collect()
+            }
+        } else {
+            rows.into_iter().map(|row| vec![row]).collect()
+        };
+
+        let mut output: Vec<(Vec<SqlValue>, Vec<SqlValue>)> = groups
+            .iter()
+            .filter(|group| self.having.as_ref().is_none_or(|h| h.eval(group).is_truthy()))
+            .map(|group| {
+                let values: Vec<SqlValue> = items.iter().map(|i| i.expr.eval(group)).collect();
+                let sort_keys = self
+                    .order_by
+                    .iter()
+                    .map(|o| order_key(&o.expr, &columns, &values, group))
+                    .collect();
+                (values, sort_keys)
+            })
+            .collect();
+
+        output.sort_by(|(_, a), (_, b)| {
+            self.order_by
+                .iter()
+                .zip(a.iter().zip(b))
+                .map(|(o, (x, y))| {
+                    let ordering = x.sort_cmp(y);
+                    if o.descending { ordering.reverse() } else { ordering }
+                })
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let rows = output
+            .into_iter()
+            .map(|(values, _)| values)
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        ResultSet { columns, rows }
+    }
+}
+
+/// ORDER BY may name an output column (by alias or position) or be any
+/// expression over the source rows.
+fn order_key(expr: &Expr, columns: &[String], values: &[SqlValue], group: &[&LogEntry]) -> SqlValue {
+    match expr {
+        Expr::Column(name) => {
+            if let Some(idx) = columns.iter().position(|c| c == name) {
+                return values[idx].clone();
+            }
+        }
+        Expr::Literal(SqlValue::Number(n)) if n.fract() == 0.0 && *n >= 1.0 => {
+            if let Some(value) = values.get(*n as usize - 1) {
+                return value.clone();
+            }
+        }
+        _ => {}
+    }
+    expr.eval(group)
+}
+
+fn star_items(rows: &[&LogEntry]) -> Vec<SelectItem> {
+    let extra: BTreeSet<&String> = rows.iter().flat_map(|r| r.extra.keys()).collect();
+    COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .chain(extra.into_iter().cloned())
+        .map(|name| SelectItem {
+            expr: Expr::Column(name.clone()),
+            name,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident,
+    Str,
+    Number,
+    Symbol,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, QueryError> {
+    let error = |position: usize, message: &str| QueryError {
+        query: sql.to_string(),
+        position,
+        message: message.to_string(),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let (kind, text) = match c {
+            '\'' | '"' | '`' => {
+                chars.next();
+                let mut text = String::new();
+                let mut closed = false;
+                while let Some((_, ch)) = chars.next() {
+                    if ch == c {
+                        // A doubled quote is an escaped quote.
+                        if chars.peek().is_some_and(|(_, next)| *next == c) {
+                            chars.next();
+                            text.push(c);
+                            continue;
+                        }
+                        closed = true;
+                        break;
+                    }
+                    text.push(ch);
+                }
+                if !closed {
+                    return Err(error(start, "unterminated quoted string"));
+                }
+                let kind = if c == '\'' { TokenKind::Str } else { TokenKind::Ident };
+                (kind, text)
+            }
+            c if c.is_ascii_digit() || (c == '.' && sql[start + 1..].starts_with(|d: char| d.is_ascii_digit())) => {
+                let mut text = String::new();
+                while let Some(&(_, ch)) = chars.peek() {
+                    if ch.is_ascii_digit() || ch == '.' {
+                        text.push(ch);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                (TokenKind::Number, text)
+            }
+            c if c.is_alphabetic() || c == '_' || c == '@' => {
+                let mut text = String::new();
+                while let Some(&(_, ch)) = chars.peek() {
+                    if ch.is_alphanumeric() || ch == '_' || ch == '.' || ch == '@' {
+                        text.push(ch);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                (TokenKind::Ident, text)
+            }
+            _ => {
+                chars.next();
+                let two: String = [c].into_iter().chain(chars.peek().map(|(_, n)| *n)).collect();
+                if ["!=", "<>", "<=", ">="].contains(&two.as_str()) {
+                    chars.next();
+                    (TokenKind::Symbol, two)
+                } else if "(),*+-/=<>;".contains(c) {
+                    (TokenKind::Symbol, c.to_string())
+                } else {
+                    return Err(error(start, &format!("unexpected character '{c}'")));
+                }
+            }
+        };
+        let end = chars.peek().map(|(i, _)| *i).unwrap_or(sql.len());
+        tokens.push(Token { kind, text, start, end });
+    }
+    // A trailing semicolon is allowed and ignored.
+    if tokens.last().is_some_and(|t| t.kind == TokenKind::Symbol && t.text == ";") {
+        tokens.pop();
+    }
+    Ok(tokens)
+}
+
+const RESERVED: [&str; 20] = [
+    "select", "from", "where", "group", "by", "order", "limit", "offset", "as", "and", "or", "not",
+    "like", "in", "between", "is", "null", "asc", "desc", "having",
+];
+
+struct Parser<'a> {
+    sql: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error_at(&self, position: usize, message: String) -> QueryError {
+        QueryError {
+            query: self.sql.to_string(),
+            position,
+            message,
+        }
+    }
+
+    fn error_here(&self, expected: &str) -> QueryError {
+        match self.peek() {
+            Some(t) => self.error_at(t.start, format!("expected {expected}, found '{}'", t.text)),
+            None => self.error_at(self.sql.len(), format!("expected {expected}")),
+        }
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| t.kind == TokenKind::Ident && t.text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), QueryError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error_here(&keyword.to_uppercase()))
+        }
+    }
+
+    fn at_symbol(&self, symbol: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| t.kind == TokenKind::Symbol && t.text == symbol)
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.at_symbol(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), QueryError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error_here(&format!("'{symbol}'")))
+        }
+    }
+
+    fn parse_select(&mut self) -> Result<Select, QueryError> {
+        self.expect_keyword("select")?;
+        let items = if self.eat_symbol("*") {
+            None
+        } else {
+            let mut items = vec![self.parse_select_item()?];
+            while self.eat_symbol(",") {
+                items.push(self.parse_select_item()?);
+            }
+            Some(items)
+        };
+
+        if self.eat_keyword("from") {
+            let table = self.next().ok_or_else(|| self.error_here("a table name"))?;
+            if !["logs", "entries"].iter().any(|t| table.text.eq_ignore_ascii_case(t)) {
+                return Err(self.error_at(
+                    table.start,
+                    format!("unknown table '{}' (the only table is 'logs')", table.text),
+                ));
+            }
+        }
+
+        let filter = if self.eat_keyword("where") {
+            let start = self.peek().map(|t| t.start);
+            let filter = self.parse_expr()?;
+            if filter.has_aggregate() {
+                return Err(self.error_at(
+                    start.unwrap_or(0),
+                    "aggregate functions are not allowed in WHERE".to_string(),
+                ));
+            }
+            Some(filter)
+        } else {
+            None
+        };
+
+        let mut group_by = Vec::new();
+        if self.eat_keyword("group") {
+            self.expect_keyword("by")?;
+            group_by.push(self.parse_expr()?);
+            while self.eat_symbol(",") {
+                group_by.push(self.parse_expr()?);
+            }
+        }
+        // HAVING may refer to a select item by its alias.
+        let having = if self.eat_keyword("having") {
+            let mut having = self.parse_expr()?;
+            having.resolve_aliases(items.as_deref().unwrap_or_default());
+            Some(having)
+        } else {
+            None
+        };
+
+        let mut order_by = Vec::new();
+        if self.eat_keyword("order") {
+            self.expect_keyword("by")?;
+            loop {
+                let expr = self.parse_expr()?;
+                let descending = if self.eat_keyword("desc") {
+                    true
+                } else {
+                    self.eat_keyword("asc");
+                    false
+                };
+                order_by.push(OrderBy { expr, descending });
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+
+        let mut limit = None;
+        let mut offset = 0;
+        if self.eat_keyword("limit") {
+            limit = Some(self.parse_count()?);
+            if self.eat_keyword("offset") {
+                offset = self.parse_count()?;
+            }
+        }
+
+        Ok(Select {
+            items,
+            filter,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+
+    fn parse_count(&mut self) -> Result<usize, QueryError> {
+        match self.next() {
+            Some(t) if t.kind == TokenKind::Number => t
+                .text
+                .parse()
+                .map_err(|_| self.error_at(t.start, format!("expected a whole number, found '{}'", t.text))),
+            _ => {
+                self.pos -= 1;
+                Err(self.error_here("a number"))
+            }
+        }
+    }
+
+    fn parse_select_item(&mut self) -> Result<SelectItem, QueryError> {
+        let start = self.peek().map(|t| t.start).unwrap_or(self.sql.len());
+        let expr = self.parse_expr()?;
+        let end = self.tokens[self.pos - 1].end;
+        let name = if self.eat_keyword("as") {
+            match self.next() {
+                Some(t) if t.kind == TokenKind::Ident => t.text,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error_here("an alias"));
+                }
+            }
+        } else {
+            self.sql[start..end].to_string()
+        };
+        Ok(SelectItem { expr, name })
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, QueryError> {
+        let mut lhs = self.parse_and()?;
+        while self.eat_keyword("or") {
+            let rhs = self.parse_and()?;
+            lhs = Expr::Binary(Box::new(lhs), BinOp::Or, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut lhs = self.parse_not()?;
+        while self.eat_keyword("and") {
+            let rhs = self.parse_not()?;
+            lhs = Expr::Binary(Box::new(lhs), BinOp::And, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, QueryError> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, QueryError> {
+        let lhs = self.parse_additive()?;
+
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Expr::IsNull {
+                expr: Box::new(lhs),
+                negated,
+            });
+        }
+
+        let negated = self.eat_keyword("not");
+        if self.at_keyword("like") {
+            self.pos += 1;
+            let pattern = match self.next() {
+                Some(t) if t.kind == TokenKind::Str => t,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error_here("a quoted LIKE pattern"));
+                }
+            };
+            return Ok(Expr::Like {
+                expr: Box::new(lhs),
+                pattern: like_to_regex(&pattern.text),
+                negated,
+            });
+        }
+        if self.eat_keyword("in") {
+            self.expect_symbol("(")?;
+            let mut list = vec![self.parse_additive()?];
+            while self.eat_symbol(",") {
+                list.push(self.parse_additive()?);
+            }
+            self.expect_symbol(")")?;
+            return Ok(Expr::InList {
+                expr: Box::new(lhs),
+                list,
+                negated,
+            });
+        }
+        if self.eat_keyword("between") {
+            let low = self.parse_additive()?;
+            self.expect_keyword("and")?;
+            let high = self.parse_additive()?;
+            return Ok(Expr::Between {
+                expr: Box::new(lhs),
+                low: Box::new(low),
+                high: Box::new(high),
+                negated,
+            });
+        }
+        if negated {
+            return Err(self.error_here("LIKE, IN or BETWEEN after NOT"));
+        }
+
+        let op = match self.peek() {
+            Some(t) if t.kind == TokenKind::Symbol => match t.text.as_str() {
+                "=" => BinOp::Eq,
+                "!=" | "<>" => BinOp::Ne,
+                "<" => BinOp::Lt,
+                "<=" => BinOp::Le,
+                ">" => BinOp::Gt,
+                ">=" => BinOp::Ge,
+                _ => return Ok(lhs),
+            },
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        let rhs = self.parse_additive()?;
+        Ok(Expr::Binary(Box::new(lhs), op, Box::new(rhs)))
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, QueryError> {
+        let mut lhs = self.parse_multiplicative()?;
+        loop {
+            let op = if self.eat_symbol("+") {
+                BinOp::Add
+            } else if self.eat_symbol("-") {
+                BinOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.parse_multiplicative()?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, QueryError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = if self.eat_symbol("*") {
+                BinOp::Mul
+            } else if self.eat_symbol("/") {
+                BinOp::Div
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.parse_unary()?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        if self.eat_symbol("-") {
+            return Ok(Expr::Neg(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        let token = self.next().ok_or_else(|| self.error_at(self.sql.len(), "expected an expression".to_string()))?;
+        match token.kind {
+            TokenKind::Number => token
+                .text
+                .parse()
+                .map(|n| Expr::Literal(SqlValue::Number(n)))
+                .map_err(|_| self.error_at(token.start, format!("invalid number '{}'", token.text))),
+            TokenKind::Str => Ok(Expr::Literal(SqlValue::Text(token.text))),
+            TokenKind::Symbol if token.text == "(" => {
+                let expr = self.parse_expr()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            TokenKind::Symbol => Err(self.error_at(
+                token.start,
+                format!("expected an expression, found '{}'", token.text),
+            )),
+            TokenKind::Ident => {
+                let lower = token.text.to_lowercase();
+                if self.at_symbol("(") && !self.sql[token.start..].starts_with(['"', '`']) {
+                    self.pos += 1;
+                    return self.parse_function(&token, &lower);
+                }
+                match lower.as_str() {
+                    "null" => Ok(Expr::Literal(SqlValue::Null)),
+                    "true" => Ok(Expr::Literal(SqlValue::Bool(true))),
+                    "false" => Ok(Expr::Literal(SqlValue::Bool(false))),
+                    kw if RESERVED.contains(&kw) && !self.sql[token.start..].starts_with(['"', '`']) => {
+                        Err(self.error_at(token.start, format!("expected an expression, found '{}'", token.text)))
+                    }
+                    _ => Ok(Expr::Column(token.text)),
+                }
+            }
+        }
+    }
+
+    /// Parse the arguments of `name(` up to and including the closing `)`.
+    fn parse_function(&mut self, token: &Token, name: &str) -> Result<Expr, QueryError> {
+        match name {
+            "lower" | "upper" => {
+                let arg = self.parse_expr()?;
+                self.expect_symbol(")")?;
+                return Ok(if name == "lower" {
+                    Expr::Lower(Box::new(arg))
+                } else {
+                    Expr::Upper(Box::new(arg))
+                });
+            }
+            "count" | "percentile" => {}
+            _ if Agg::call(name, "").is_some() => {}
+            _ => {
+                return Err(self.error_at(token.start, format!("unknown function '{}'", token.text)));
+            }
+        }
+
+        if name == "count" && self.eat_symbol("*") {
+            self.expect_symbol(")")?;
+            return Ok(Expr::Aggregate(Agg::Count, None));
+        }
+        let arg_start = self.peek().map(|t| t.start).unwrap_or(self.sql.len());
+        let arg = self.parse_expr()?;
+        if arg.has_aggregate() {
+            return Err(self.error_at(arg_start, "aggregate functions cannot be nested".to_string()));
+        }
+        let field = self.sql[arg_start..self.tokens[self.pos - 1].end].to_string();
+        let aggregate = match name {
+            "count" => Agg::Count,
+            "percentile" => {
+                // percentile(column, 95)
+                self.expect_symbol(",")?;
+                let pct = self.next().filter(|t| t.kind == TokenKind::Number);
+                match pct.as_ref().and_then(|t| t.text.parse::<f64>().ok()) {
+                    Some(p) if (0.0..=100.0).contains(&p) => Agg::Percentile(p, field),
+                    _ => {
+                        self.pos -= 1;
+                        return Err(self.error_here("a percentile between 0 and 100"));
+                    }
+                }
+            }
+            _ => Agg::call(name, field).expect("checked above"),
+        };
+        self.expect_symbol(")")?;
+        Ok(Expr::Aggregate(aggregate, Some(Box::new(arg))))
+    }
+}
+
+/// Translate a LIKE pattern (`%` any run, `_` one character) into a
+/// case-insensitive anchored regex.
+fn like_to_regex(pattern: &str) -> Regex {
+    let mut regex = String::from("(?is)^");
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).expect("escaped LIKE pattern is a valid regex")
+}
+
+/// Output format for a [`ResultSet`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SqlFormat {
+    #[default]
+    Table,
+    Csv,
+    Tsv,
+    Json,
+}
+
+impl ResultSet {
+    pub fn render(&self, format: SqlFormat) -> String {
+        match format {
+            SqlFormat::Table => self.to_table(),
+            SqlFormat::Csv => self.to_delimited(Delimited::Csv),
+            SqlFormat::Tsv => self.to_delimited(Delimited::Tsv),
+            SqlFormat::Json => self.to_json(),
+        }
+    }
+
+    /// Aligned plain-text table; numeric columns are right-aligned and
+    /// fractions are rounded to two decimals.
+    pub fn to_table(&self) -> String {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|v| match v {
+                        SqlValue::Null => "NULL".to_string(),
+                        SqlValue::Number(n) if n.fract() != 0.0 && n.is_finite() => format!("{n:.2}"),
+                        v => v.to_string().replace(['\n', '\r', '\t'], " "),
+                    })
+                    .collect()
+            })
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([c.chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let numeric: Vec<bool> = (0..self.columns.len())
+            .map(|i| {
+                self.rows.iter().any(|r| matches!(r[i], SqlValue::Number(_)))
+                    && self
+                        .rows
+                        .iter()
+                        .all(|r| matches!(r[i], SqlValue::Number(_) | SqlValue::Null))
+            })
+            .collect();
+
+        let format_row = |row: &[String]| -> String {
+            row.iter()
+                .enumerate()
+                .map(|(i, cell)| {
+                    let pad = widths[i] - cell.chars().count();
+                    if numeric[i] {
+                        format!("{}{}", " ".repeat(pad), cell)
+                    } else {
+                        format!("{}{}", cell, " ".repeat(pad))
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" │ ")
+                .trim_end()
+                .to_string()
+        };
+
+        let mut out = String::new();
+        out.push_str(&format_row(&self.columns));
+        out.push('\n');
+        out.push_str(
+            &widths
+                .iter()
+                .map(|w| "─".repeat(*w))
+                .collect::<Vec<_>>()
+                .join("─┼─"),
+        );
+        out.push('\n');
+        for row in &cells {
+            out.push_str(&format_row(row));
+            out.push('\n');
+        }
+        out.push_str(&format!("({} row{})\n", self.rows.len(), if self.rows.len() == 1 { "" } else { "s" }));
+        out
+    }
+
+    /// CSV or TSV with a header row. Numbers keep their full precision.
+    pub fn to_delimited(&self, format: Delimited) -> String {
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(SqlValue::to_string).collect())
+            .collect();
+        format.table(&self.columns, &rows)
+    }
+
+    /// A JSON array with one object per row, keys in column order.
+    pub fn to_json(&self) -> String {
+        let rows: Vec<serde_json::Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let object: serde_json::Map<String, serde_json::Value> = self
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(SqlValue::to_json))
+                    .collect();
+                serde_json::Value::Object(object)
+            })
+            .collect();
+        serde_json::to_string_pretty(&rows).expect("result set is serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<LogEntry> {
+        [
+            "2024-01-15 08:15:23.145 INFO 192.168.1.105 GET /api/users 200 45ms",
+            "2024-01-15 08:15:24.332 INFO 192.168.1.108 GET /api/users 200 55ms",
+            "2024-01-15 08:15:25.891 ERROR 192.168.1.112 POST /api/orders 500 900ms",
+            "2024-01-15 08:15:26.002 INFO 192.168.1.112 POST /api/orders 201 300ms",
+            "2024-01-15 08:15:27.410 INFO 192.168.1.105 GET /health 200 2ms",
+            r#"{"level": "warn", "message": "disk almost full", "user": "alice"}"#,
+        ]
+        .iter()
+        .map(|line| LogEntry::parse_log(line).unwrap())
+        .collect()
+    }
+
+    fn query(sql: &str) -> Vec<Vec<SqlValue>> {
+        Select::parse(sql).unwrap().execute(&entries()).rows
+    }
+
+    fn text(s: &str) -> SqlValue {
+        SqlValue::Text(s.to_string())
+    }
+
+    #[test]
+    fn having_filters_groups_by_alias_and_aggregate() {
+        let rows = query(
+            "SELECT endpoint, count(*) AS hits FROM logs WHERE endpoint IS NOT NULL \
+             GROUP BY endpoint HAVING hits > 1 AND max(response_time) > 100",
+        );
+        assert_eq!(rows, vec![vec![text("/api/orders"), SqlValue::Number(2.0)]]);
+    }
+
+    #[test]
+    fn having_without_group_by_aggregates_the_whole_table() {
+        assert_eq!(query("SELECT count(*) AS n FROM logs HAVING n > 100"), Vec::<Vec<SqlValue>>::new());
+        assert_eq!(query("SELECT count(*) AS n FROM logs HAVING n > 1"), vec![vec![SqlValue::Number(6.0)]]);
+    }
+
+    #[test]
+    fn quoted_identifiers_are_not_function_calls() {
+        for sql in ["SELECT `count`(status) FROM logs", r#"SELECT "count"(status) FROM logs"#] {
+            let err = Select::parse(sql).unwrap_err().to_string();
+            assert!(err.contains("unexpected '('"), "{sql}: {err}");
+        }
+        assert_eq!(query("SELECT `user` FROM logs WHERE `user` IS NOT NULL"), vec![vec![text("alice")]]);
+    }
+
+    #[test]
+    fn unknown_functions_are_rejected() {
+        let err = Select::parse("SELECT foo(status) FROM logs").unwrap_err().to_string();
+        assert!(err.contains("unknown function 'foo'"), "{err}");
+    }
+
+    #[test]
+    fn comparisons_with_null_match_nothing() {
+        assert_eq!(query("SELECT count(*) FROM logs WHERE status = NULL"), vec![vec![SqlValue::Number(0.0)]]);
+        assert_eq!(query("SELECT count(*) FROM logs WHERE NOT status > 0"), vec![vec![SqlValue::Number(0.0)]]);
+        assert_eq!(query("SELECT count(*) FROM logs WHERE status IS NULL"), vec![vec![SqlValue::Number(1.0)]]);
+        assert_eq!(query("SELECT NULL AND false, NULL OR true, NULL AND true")[0], vec![
+            SqlValue::Bool(false),
+            SqlValue::Bool(true),
+            SqlValue::Null,
+        ]);
+    }
+
+    #[test]
+    fn aggregates_skip_nulls() {
+        let rows = query("SELECT count(*), count(status), avg(status), min(user), max(user) FROM logs");
+        assert_eq!(rows, vec![vec![
+            SqlValue::Number(6.0),
+            SqlValue::Number(5.0),
+            SqlValue::Number(260.2),
+            text("alice"),
+            text("alice"),
+        ]]);
+        let rows = query("SELECT avg(response_time), min(user) FROM logs WHERE status IS NULL");
+        assert_eq!(rows, vec![vec![SqlValue::Null, text("alice")]]);
+    }
+
+    #[test]
+    fn nulls_sort_first() {
+        let rows = query("SELECT status FROM logs ORDER BY status LIMIT 2");
+        assert_eq!(rows, vec![vec![SqlValue::Null], vec![SqlValue::Number(200.0)]]);
+    }
+
+    #[test]
+    fn order_by_alias_position_and_hidden_expression() {
+        let sql = "SELECT endpoint AS e, sum(response_time) AS total FROM logs \
+                   WHERE endpoint IS NOT NULL GROUP BY endpoint";
+        let by_alias = query(&format!("{sql} ORDER BY total DESC"));
+        assert_eq!(by_alias.iter().map(|r| r[0].clone()).collect::<Vec<_>>(), vec![
+            text("/api/orders"),
+            text("/api/users"),
+            text("/health"),
+        ]);
+        assert_eq!(query(&format!("{sql} ORDER BY 2 DESC")), by_alias);
+        let by_hidden = query(&format!("{sql} ORDER BY count(*) DESC, e DESC"));
+        assert_eq!(by_hidden.iter().map(|r| r[0].clone()).collect::<Vec<_>>(), vec![
+            text("/api/users"),
+            text("/api/orders"),
+            text("/health"),
+        ]);
+    }
+
+    #[test]
+    fn only_the_table_rounds_fractions() {
+        let result = ResultSet {
+            columns: vec!["avg".to_string()],
+            rows: vec![vec![SqlValue::Number(0.0042)], vec![SqlValue::Number(3.0)]],
+        };
+        assert!(result.to_table().contains("0.00\n"));
+        assert_eq!(result.to_delimited(Delimited::Csv), "avg\n0.0042\n3\n");
+        assert_eq!(result.to_delimited(Delimited::Tsv), "avg\n0.0042\n3\n");
+    }
+}
@@ -0,0 +1,349 @@
+//! SQLite export for ad-hoc analysis with standard SQL tools.
+//!
+//! Each run adds one row to `sources` and tags everything it writes with
+//! that row's id, so `--append` can collect several files (or several runs
+//! over the same file) in one database:
+//!
+//! ```text
+//! sources         id, path, size_bytes, modified, imported_at,
+//!                 entries_parsed, entries, filters (JSON)
+//! entries         source_id, line_number, timestamp, level, ip, method,
+//!                 endpoint, status, response_time, message, extra (JSON), raw
+//! summaries       source_id, total, info, warning, error, error_rate,
+//!                 avg_response_time, p50, p95, p99
+//! endpoint_stats  source_id, endpoint, requests, errors, error_rate,
+//!                 avg_response_time, p50, p95, p99
+//! ```
+//!
+//! Timestamps are stored as `YYYY-MM-DD HH:MM:SS.sss` text, which sorts
+//! correctly and works with SQLite's date functions.
+
+use crate::log_analyzer::{AnalyzerError, LogEntry, LogStats, endpoint_stats};
+use crate::report::InputMetadata;
+use crate::sql::{SqlValue, column_value};
+use rusqlite::{Connection, params};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sources (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    modified TEXT,
+    imported_at TEXT NOT NULL,
+    entries_parsed INTEGER NOT NULL,
+    entries INTEGER NOT NULL,
+    filters TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS entries (
+    source_id INTEGER NOT NULL REFERENCES sources(id),
+    line_number INTEGER NOT NULL,
+    timestamp TEXT,
+    level TEXT,
+    ip TEXT,
+    method TEXT,
+    endpoint TEXT,
+    status INTEGER,
+    response_time REAL,
+    message TEXT,
+    extra TEXT,
+    raw TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS entries_timestamp ON entries(timestamp);
+CREATE INDEX IF NOT EXISTS entries_level ON entries(level);
+CREATE INDEX IF NOT EXISTS entries_endpoint ON entries(endpoint);
+CREATE INDEX IF NOT EXISTS entries_status ON entries(status);
+CREATE INDEX IF NOT EXISTS entries_ip ON entries(ip);
+CREATE INDEX IF NOT EXISTS entries_source ON entries(source_id, line_number);
+CREATE TABLE IF NOT EXISTS summaries (
+    source_id INTEGER PRIMARY KEY REFERENCES sources(id),
+    total INTEGER NOT NULL,
+    info INTEGER NOT NULL,
+    warning INTEGER NOT NULL,
+    error INTEGER NOT NULL,
+    error_rate REAL NOT NULL,
+    avg_response_time REAL,
+    p50 REAL,
+    p95 REAL,
+    p99 REAL
+);
+CREATE TABLE IF NOT EXISTS endpoint_stats (
+    source_id INTEGER NOT NULL REFERENCES sources(id),
+    endpoint TEXT NOT NULL,
+    requests INTEGER NOT NULL,
+    errors INTEGER NOT NULL,
+    error_rate REAL NOT NULL,
+    avg_response_time REAL,
+    p50 REAL,
+    p95 REAL,
+    p99 REAL,
+    PRIMARY KEY (source_id, endpoint)
+);
+";
+
+fn sqlite_error(e: rusqlite::Error) -> AnalyzerError {
+    AnalyzerError::ExportError(e.to_string())
+}
+
+fn text(value: SqlValue) -> Option<String> {
+    match value {
+        SqlValue::Null => None,
+        value => Some(value.to_string()),
+    }
+}
+
+/// Whether `path` holds a SQLite database, judged by its header. An empty
+/// file counts, since replacing it loses nothing.
+fn is_database(path: &Path) -> Result<bool, AnalyzerError> {
+    let mut header = Vec::new();
+    File::open(path)?.take(16).read_to_end(&mut header)?;
+    Ok(header.is_empty() || header == b"SQLite format 3\0")
+}
+
+/// Write the entries and their aggregates to the database at `path`. Without
+/// `append` an existing database is replaced; the new one is built next to it
+/// and renamed into place, so a failed run leaves the old one intact. Files
+/// that are not SQLite databases are never replaced. Returns the new source id.
+pub fn write(
+    path: &Path,
+    append: bool,
+    input: &InputMetadata,
+    entries: &[LogEntry],
+    stats: &LogStats,
+) -> Result<i64, AnalyzerError> {
+    if path.exists() && !is_database(path)? {
+        return Err(AnalyzerError::OutputError(format!(
+            "{} exists and is not a SQLite database; refusing to replace it",
+            path.display()
+        )));
+    }
+    if append {
+        return write_to(path, input, entries, stats);
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    if tmp.exists() {
+        std::fs::remove_file(&tmp)?;
+    }
+    match write_to(&tmp, input, entries, stats) {
+        Ok(source_id) => {
+            std::fs::rename(&tmp, path)?;
+            Ok(source_id)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+fn write_to(
+    path: &Path,
+    input: &InputMetadata,
+    entries: &[LogEntry],
+    stats: &LogStats,
+) -> Result<i64, AnalyzerError> {
+    let mut conn = Connection::open(path).map_err(sqlite_error)?;
+    conn.execute_batch(SCHEMA).map_err(sqlite_error)?;
+    let tx = conn.transaction().map_err(sqlite_error)?;
+
+    let filters = serde_json::to_string(&input.filters).expect("filters are serializable");
+    tx.execute(
+        "INSERT INTO sources (path, size_bytes, modified, imported_at, entries_parsed, entries, filters)
+         VALUES (?1, ?2, ?3, datetime('now'), ?4, ?5, ?6)",
+        params![
+            input.path,
+            input.size_bytes as i64,
+            input.modified.map(|m| m.format("%Y-%m-%d %H:%M:%S").to_string()),
+            input.entries_parsed as i64,
+            input.entries_analyzed as i64,
+            filters,
+        ],
+    )
+    .map_err(sqlite_error)?;
+    let source_id = tx.last_insert_rowid();
+
+    {
+        let mut insert = tx
+            .prepare(
+                "INSERT INTO entries (source_id, line_number, timestamp, level, ip, method, endpoint,
+                                      status, response_time, message, extra, raw)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )
+            .map_err(sqlite_error)?;
+        for entry in entries {
+            let extra = (!entry.extra.is_empty())
+                .then(|| serde_json::to_string(&entry.extra).expect("extra fields are serializable"));
+            insert
+                .execute(params![
+                    source_id,
+                    entry.line_number as i64,
+                    text(column_value(entry, "timestamp")),
+                    entry.level.as_ref().map(|l| l.to_string()),
+                    entry.ip_address.map(|ip| ip.to_string()),
+                    entry.method.as_ref().map(|m| m.to_string()),
+                    entry.endpoint,
+                    entry.status_code,
+                    entry.response_time,
+                    entry.message,
+                    extra,
+                    entry.raw,
+                ])
+                .map_err(sqlite_error)?;
+        }
+    }
+
+    tx.execute(
+        "INSERT INTO summaries (source_id, total, info, warning, error, error_rate, avg_response_time, p50, p95, p99)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            source_id,
+            stats.total_requests as i64,
+            stats.info_count as i64,
+            stats.warning_count as i64,
+            stats.error_count as i64,
+            stats.error_rate(),
+            (stats.total_requests > 0).then_some(stats.avg_response_time),
+            stats.response_time_percentile(50.0),
+            stats.response_time_percentile(95.0),
+            stats.response_time_percentile(99.0),
+        ],
+    )
+    .map_err(sqlite_error)?;
+
+    {
+        let mut insert = tx
+            .prepare(
+                "INSERT INTO endpoint_stats (source_id, endpoint, requests, errors, error_rate,
+                                             avg_response_time, p50, p95, p99)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )
+            .map_err(sqlite_error)?;
+        for row in endpoint_stats(entries) {
+            insert
+                .execute(params![
+                    source_id,
+                    row.endpoint,
+                    row.requests as i64,
+                    row.errors as i64,
+                    row.error_rate,
+                    row.avg_response_time,
+                    row.p50,
+                    row.p95,
+                    row.p99,
+                ])
+                .map_err(sqlite_error)?;
+        }
+    }
+
+    tx.commit().map_err(sqlite_error)?;
+    Ok(source_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::AppliedFilters;
+
+    const LINES: [&str; 4] = [
+        "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/orders 500 900ms",
+        "2024-01-15 08:15:20.000 INFO 10.0.0.2 GET /api/users 200 40ms",
+        "2024-01-15 08:15:40.000 INFO 10.0.0.3 POST /api/orders 201 100ms",
+        r#"{"timestamp":"2024-01-15T08:16:00","level":"warn","message":"disk almost full","disk":"sda"}"#,
+    ];
+
+    fn entries() -> Vec<LogEntry> {
+        LINES
+            .iter()
+            .enumerate()
+            .map(|(i, line)| LogEntry { line_number: i + 1, ..LogEntry::parse_log(line).unwrap() })
+            .collect()
+    }
+
+    fn export(path: &Path, append: bool, entries: &[LogEntry]) -> Result<i64, AnalyzerError> {
+        let input = InputMetadata::new(Path::new("server.log"), entries.len(), entries, AppliedFilters::default());
+        write(path, append, &input, entries, &LogStats::from_entries(entries))
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn append_adds_a_source_and_replace_starts_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.db");
+        let entries = entries();
+        assert_eq!(export(&path, false, &entries).unwrap(), 1);
+        assert_eq!(export(&path, true, &entries[..2]).unwrap(), 2);
+        {
+            let conn = Connection::open(&path).unwrap();
+            assert_eq!(count(&conn, "SELECT count(*) FROM sources"), 2);
+            assert_eq!(count(&conn, "SELECT count(*) FROM entries WHERE source_id = 1"), 4);
+            assert_eq!(count(&conn, "SELECT count(*) FROM entries WHERE source_id = 2"), 2);
+            assert_eq!(count(&conn, "SELECT count(*) FROM summaries"), 2);
+        }
+
+        // A stale temporary file from an interrupted run doesn't get in the way.
+        std::fs::write(dir.path().join("logs.db.tmp"), "partial").unwrap();
+        assert_eq!(export(&path, false, &entries[..1]).unwrap(), 1);
+        assert!(!dir.path().join("logs.db.tmp").exists());
+        let conn = Connection::open(&path).unwrap();
+        assert_eq!(count(&conn, "SELECT count(*) FROM sources"), 1);
+        assert_eq!(count(&conn, "SELECT count(*) FROM entries"), 1);
+    }
+
+    #[test]
+    fn files_that_are_not_databases_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.log");
+        std::fs::write(&path, LINES.join("\n")).unwrap();
+        for append in [false, true] {
+            let err = export(&path, append, &entries()).unwrap_err().to_string();
+            assert!(err.contains("is not a SQLite database"), "{err}");
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), LINES.join("\n"));
+
+        let empty = dir.path().join("empty.db");
+        std::fs::write(&empty, "").unwrap();
+        assert_eq!(export(&empty, false, &entries()).unwrap(), 1);
+    }
+
+    #[test]
+    fn entries_and_aggregates_are_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.db");
+        export(&path, false, &entries()).unwrap();
+        let conn = Connection::open(&path).unwrap();
+
+        let (timestamp, level, extra): (String, String, String) = conn
+            .query_row("SELECT timestamp, level, extra FROM entries WHERE line_number = 4", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!((timestamp.as_str(), level.as_str(), extra.as_str()), ("2024-01-15 08:16:00.000", "WARNING", r#"{"disk":"sda"}"#));
+
+        let summary: (i64, i64, i64, i64, f64, f64) = conn
+            .query_row("SELECT total, info, warning, error, error_rate, p50 FROM summaries", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+            })
+            .unwrap();
+        assert_eq!(summary, (4, 2, 1, 1, 25.0, 100.0));
+
+        let mut statement = conn
+            .prepare("SELECT endpoint, requests, errors, error_rate, avg_response_time, p99 FROM endpoint_stats ORDER BY endpoint")
+            .unwrap();
+        let rows: Vec<(String, i64, i64, f64, f64, f64)> = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(rows, [
+            ("/api/orders".to_string(), 2, 1, 50.0, 500.0, 900.0),
+            ("/api/users".to_string(), 1, 0, 0.0, 40.0, 40.0),
+        ]);
+    }
+}
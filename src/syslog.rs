@@ -0,0 +1,363 @@
+//! Syslog input in the RFC 5424 and BSD (RFC 3164) formats:
+//!
+//! ```text
+//! <165>1 2003-10-11T22:14:15.003Z host app 1234 ID47 [origin ip="10.0.0.1"] message
+//! <34>Oct 11 22:14:15 host su[1234]: message
+//! Oct 11 22:14:15 host su: message
+//! ```
+//!
+//! The PRI value splits into facility and severity; severities `emerg` to
+//! `err` count as errors, `warning` as a warning and the rest as info. The
+//! header fields go to the extra fields `facility`, `severity`, `hostname`,
+//! `app_name`, `procid` and `msgid` (nil values `-` are left out), and
+//! structured data to an `sd` object holding one object of parameters per
+//! SD-ID, e.g. `{"origin": {"ip": "10.0.0.1"}}`; repeated parameters become
+//! arrays.
+//!
+//! RFC 3164 timestamps have no year. It is taken from a reference time (the
+//! file's modification time when reading a file), stepping back a year for
+//! dates that would otherwise lie more than a day after it, so a log spanning
+//! New Year's Eve keeps its order.
+
+use crate::log_analyzer::{LogEntry, LogLevel};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv", "ftp", "ntp",
+    "audit", "alert", "clock", "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7",
+];
+const SEVERITIES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Level of a syslog severity (0 to 7).
+pub fn severity_level(severity: u8) -> LogLevel {
+    match severity {
+        0..=3 => LogLevel::Error,
+        4 => LogLevel::Warning,
+        _ => LogLevel::Info,
+    }
+}
+
+/// `<PRI>` at the start of `line` (0 to 191), and the rest of the line.
+fn pri(line: &str) -> Option<(u8, &str)> {
+    let rest = line.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    let digits = &rest[..end];
+    if digits.is_empty() || digits.len() > 3 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let pri: u8 = digits.parse().ok().filter(|p| *p <= 191)?;
+    Some((pri, &rest[end + 1..]))
+}
+
+/// Month, day and time of an RFC 3164 timestamp (`Oct 11 22:14:15`, day
+/// space-padded) starting `line`, and the rest of the line.
+fn bsd_timestamp(line: &str) -> Option<((u32, u32, NaiveTime), &str)> {
+    let month = MONTHS.iter().position(|m| line.starts_with(m))? as u32 + 1;
+    let stamp = line.get(..15)?;
+    if !stamp.is_ascii() || stamp.as_bytes()[3] != b' ' {
+        return None;
+    }
+    let day: u32 = stamp[4..6].trim_start().parse().ok()?;
+    let time = NaiveTime::parse_from_str(&stamp[7..], "%H:%M:%S").ok()?;
+    let rest = line[15..].strip_prefix(' ').unwrap_or(&line[15..]);
+    Some(((month, day, time), rest))
+}
+
+/// The date in the latest year that puts it no more than a day after
+/// `reference`.
+fn infer_year((month, day, time): (u32, u32, NaiveTime), reference: NaiveDateTime) -> Option<NaiveDateTime> {
+    let latest = reference + Duration::days(1);
+    // Eight years back always reaches a leap year for Feb 29.
+    (0..=8).find_map(|back| {
+        NaiveDate::from_ymd_opt(reference.year() - back, month, day)
+            .map(|date| date.and_time(time))
+            .filter(|ts| *ts <= latest)
+    })
+}
+
+/// Whether `line` starts with a PRI or an RFC 3164 timestamp.
+pub fn looks_like_syslog(line: &str) -> bool {
+    pri(line).is_some() || bsd_timestamp(line).is_some()
+}
+
+/// The next space-separated field and the rest.
+fn field(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start_matches(' ');
+    if s.is_empty() {
+        return None;
+    }
+    Some(s.split_once(' ').unwrap_or((s, "")))
+}
+
+fn nil(value: &str) -> Option<&str> {
+    (value != "-").then_some(value)
+}
+
+/// RFC 5424 structured data (`[id k="v"][id2]` or `-`) as an object per
+/// SD-ID, and the rest of the line. Repeated parameters become arrays.
+fn structured_data(s: &str) -> Result<(Map<String, Value>, &str), String> {
+    let mut elements = Map::new();
+    if let Some(rest) = s.strip_prefix('-') {
+        return Ok((elements, rest));
+    }
+    let mut chars = s.char_indices().peekable();
+    while let Some((_, '[')) = chars.peek() {
+        chars.next();
+        let mut id = String::new();
+        while let Some((_, c)) = chars.next_if(|(_, c)| !matches!(c, ' ' | ']')) {
+            id.push(c);
+        }
+        let mut params: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        loop {
+            match chars.next() {
+                Some((_, ']')) => break,
+                Some((_, ' ')) => {}
+                Some((_, c)) => {
+                    let mut name = c.to_string();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| *c != '=') {
+                        name.push(c);
+                    }
+                    if chars.next().map(|(_, c)| c) != Some('=') || chars.next().map(|(_, c)| c) != Some('"') {
+                        return Err(format!("expected {name}=\"…\" in structured data element '{id}'"));
+                    }
+                    let mut value = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, '\\')) => match chars.next() {
+                                Some((_, c @ ('"' | '\\' | ']'))) => value.push(c),
+                                Some((_, c)) => {
+                                    value.push('\\');
+                                    value.push(c);
+                                }
+                                None => return Err(format!("unterminated structured data element '{id}'")),
+                            },
+                            Some((_, c)) => value.push(c),
+                            None => return Err(format!("unterminated structured data element '{id}'")),
+                        }
+                    }
+                    params.entry(name).or_default().push(value);
+                }
+                None => return Err(format!("unterminated structured data element '{id}'")),
+            }
+        }
+        let params = params
+            .into_iter()
+            .map(|(name, mut values)| {
+                let value = if values.len() == 1 {
+                    Value::String(values.remove(0))
+                } else {
+                    Value::from(values)
+                };
+                (name, value)
+            })
+            .collect();
+        elements.insert(id, Value::Object(params));
+    }
+    let rest = chars.peek().map_or("", |(i, _)| &s[*i..]);
+    if elements.is_empty() {
+        return Err("expected structured data or '-'".to_string());
+    }
+    Ok((elements, rest))
+}
+
+fn entry(line: &str, timestamp: Option<String>, severity: Option<u8>, message: &str, extra: BTreeMap<String, Value>) -> LogEntry {
+    let message = message.strip_prefix('\u{feff}').unwrap_or(message).trim_end();
+    LogEntry {
+        timestamp,
+        level: severity.map(severity_level),
+        ip_address: None,
+        method: None,
+        endpoint: None,
+        status_code: None,
+        response_time: None,
+        message: (!message.is_empty()).then(|| message.to_string()),
+        line_number: 0,
+        raw: line.to_string(),
+        extra,
+    }
+}
+
+fn rfc5424(header: &str, extra: &mut BTreeMap<String, Value>) -> Result<(Option<String>, String), String> {
+    let missing = |name: &str| format!("missing {name} in RFC 5424 header");
+    let (timestamp, rest) = field(header).ok_or_else(|| missing("TIMESTAMP"))?;
+    let (hostname, rest) = field(rest).ok_or_else(|| missing("HOSTNAME"))?;
+    let (app_name, rest) = field(rest).ok_or_else(|| missing("APP-NAME"))?;
+    let (procid, rest) = field(rest).ok_or_else(|| missing("PROCID"))?;
+    let (msgid, rest) = field(rest).ok_or_else(|| missing("MSGID"))?;
+    for (key, value) in [("hostname", hostname), ("app_name", app_name), ("procid", procid), ("msgid", msgid)] {
+        if let Some(value) = nil(value) {
+            extra.insert(key.to_string(), Value::from(value));
+        }
+    }
+    let (sd, message) = structured_data(rest.trim_start_matches(' '))?;
+    if !sd.is_empty() {
+        extra.insert("sd".to_string(), Value::Object(sd));
+    }
+    Ok((nil(timestamp).map(str::to_string), message.strip_prefix(' ').unwrap_or(message).to_string()))
+}
+
+/// `host tag[pid]: message` after an RFC 3164 timestamp. The hostname is
+/// absent when the first word already ends the tag.
+fn rfc3164(rest: &str, extra: &mut BTreeMap<String, Value>) -> String {
+    let (mut first, mut rest) = field(rest).unwrap_or(("", ""));
+    let is_tag = |word: &str| word.ends_with(':') || (word.contains('[') && word.trim_end_matches(':').ends_with(']'));
+    if !first.is_empty() && !is_tag(first) {
+        extra.insert("hostname".to_string(), Value::from(first));
+        (first, rest) = field(rest).unwrap_or(("", ""));
+    }
+    if is_tag(first) {
+        let tag = first.trim_end_matches(':');
+        let (app, procid) = match tag.split_once('[') {
+            Some((app, pid)) => (app, Some(pid.trim_end_matches(']'))),
+            None => (tag, None),
+        };
+        extra.insert("app_name".to_string(), Value::from(app));
+        if let Some(procid) = procid {
+            extra.insert("procid".to_string(), Value::from(procid));
+        }
+        rest.to_string()
+    } else if first.is_empty() {
+        rest.to_string()
+    } else {
+        format!("{first} {rest}")
+    }
+}
+
+/// Parse a syslog line; RFC 3164 years are inferred from `reference`.
+pub fn parse(line: &str, reference: NaiveDateTime) -> Result<LogEntry, String> {
+    let mut extra = BTreeMap::new();
+    let (severity, rest) = match pri(line) {
+        Some((pri, rest)) => {
+            let (facility, severity) = (pri / 8, pri % 8);
+            extra.insert("facility".to_string(), Value::from(FACILITIES[facility as usize]));
+            extra.insert("severity".to_string(), Value::from(SEVERITIES[severity as usize]));
+            (Some(severity), rest)
+        }
+        None => (None, line),
+    };
+    if let Some(header) = rest.strip_prefix("1 ") {
+        let (timestamp, message) = rfc5424(header, &mut extra)?;
+        return Ok(entry(line, timestamp, severity, &message, extra));
+    }
+    let (timestamp, rest) = match bsd_timestamp(rest) {
+        Some((stamp, rest)) => {
+            let ts = infer_year(stamp, reference);
+            (ts.map(|ts| ts.format("%Y-%m-%d %H:%M:%S").to_string()), rest)
+        }
+        None => (None, rest),
+    };
+    let message = rfc3164(rest, &mut extra);
+    Ok(entry(line, timestamp, severity, &message, extra))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reference() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    fn at(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn pri_splits_into_facility_and_severity() {
+        assert_eq!(pri("<0>rest"), Some((0, "rest")));
+        assert_eq!(pri("<191>x"), Some((191, "x")));
+        for line in ["<192>x", "<>x", "<1234>x", "<-1>x", "<12", "12>x", "<1a>x"] {
+            assert_eq!(pri(line), None, "{line}");
+        }
+        let entry = parse("<165>1 - - - - - -", reference()).unwrap();
+        assert_eq!(entry.extra["facility"], "local4");
+        assert_eq!(entry.extra["severity"], "notice");
+        assert_eq!(entry.level, Some(LogLevel::Info));
+        assert_eq!(parse("<3>Oct 11 22:14:15 host app: x", reference()).unwrap().level, Some(LogLevel::Error));
+        assert_eq!(parse("<4>Oct 11 22:14:15 host app: x", reference()).unwrap().level, Some(LogLevel::Warning));
+        assert!(!looks_like_syslog("<192>Oct 11 22:14:15 host app: x"));
+    }
+
+    #[test]
+    fn rfc5424_nil_fields_are_left_out() {
+        let entry = parse("<34>1 - - - - - -", reference()).unwrap();
+        assert_eq!(entry.timestamp, None);
+        assert_eq!(entry.message, None);
+        assert_eq!(entry.extra.keys().collect::<Vec<_>>(), ["facility", "severity"]);
+
+        let line = "<165>1 2003-10-11T22:14:15.003Z host app 1234 ID47 - \u{feff}hello world";
+        let entry = parse(line, reference()).unwrap();
+        assert_eq!(entry.timestamp.as_deref(), Some("2003-10-11T22:14:15.003Z"));
+        assert_eq!(entry.message.as_deref(), Some("hello world"));
+        assert_eq!(entry.extra["hostname"], "host");
+        assert_eq!(entry.extra["app_name"], "app");
+        assert_eq!(entry.extra["procid"], "1234");
+        assert_eq!(entry.extra["msgid"], "ID47");
+        assert!(!entry.extra.contains_key("sd"));
+        assert!(parse("<165>1 2003-10-11T22:14:15Z host", reference()).unwrap_err().contains("APP-NAME"));
+    }
+
+    #[test]
+    fn structured_data_unescapes_and_collects_repeats() {
+        let line = r#"<165>1 - - - - - [origin ip="10.0.0.1" ip="10.0.0.2"][meta note="a \"quoted\" \] \\ \n"][empty] msg"#;
+        let entry = parse(line, reference()).unwrap();
+        assert_eq!(
+            entry.extra["sd"],
+            json!({
+                "origin": {"ip": ["10.0.0.1", "10.0.0.2"]},
+                "meta": {"note": r#"a "quoted" ] \ \n"#},
+                "empty": {},
+            })
+        );
+        assert_eq!(entry.message.as_deref(), Some("msg"));
+
+        assert!(structured_data(r#"[id k="v"#).unwrap_err().contains("unterminated"));
+        assert!(structured_data("[id k=v]").unwrap_err().contains("expected k=\"…\""));
+        assert!(structured_data("msg").unwrap_err().contains("expected structured data"));
+    }
+
+    #[test]
+    fn rfc3164_hostname_and_pid_are_optional() {
+        let entry = parse("<34>Oct 11 22:14:15 mymachine su[1234]: 'su root' failed", reference()).unwrap();
+        assert_eq!(entry.timestamp.as_deref(), Some("2023-10-11 22:14:15"));
+        assert_eq!(entry.extra["hostname"], "mymachine");
+        assert_eq!(entry.extra["app_name"], "su");
+        assert_eq!(entry.extra["procid"], "1234");
+        assert_eq!(entry.message.as_deref(), Some("'su root' failed"));
+
+        let entry = parse("Oct  1 02:00:00 cron: job done", reference()).unwrap();
+        assert_eq!(entry.timestamp.as_deref(), Some("2023-10-01 02:00:00"));
+        assert_eq!(entry.level, None);
+        assert!(!entry.extra.contains_key("hostname"));
+        assert!(!entry.extra.contains_key("procid"));
+        assert_eq!(entry.extra["app_name"], "cron");
+        assert_eq!(entry.message.as_deref(), Some("job done"));
+
+        let entry = parse("May 31 23:59:59 host just a message", reference()).unwrap();
+        assert_eq!(entry.timestamp.as_deref(), Some("2024-05-31 23:59:59"));
+        assert_eq!(entry.extra["hostname"], "host");
+        assert!(!entry.extra.contains_key("app_name"));
+        assert_eq!(entry.message.as_deref(), Some("just a message"));
+    }
+
+    #[test]
+    fn years_are_inferred_from_the_reference() {
+        let midnight = NaiveTime::MIN;
+        // A log read on New Year's Day: December is last year.
+        assert_eq!(infer_year((12, 31, midnight), at(2024, 1, 1)), Some(at(2023, 12, 31)));
+        assert_eq!(infer_year((1, 1, midnight), at(2024, 1, 1)), Some(at(2024, 1, 1)));
+        // Up to a day ahead of the reference stays in its year, for clock skew.
+        assert_eq!(infer_year((1, 2, midnight), at(2024, 1, 1)), Some(at(2024, 1, 2)));
+        assert_eq!(infer_year((1, 3, midnight), at(2024, 1, 1)), Some(at(2023, 1, 3)));
+        // Feb 29 goes back to the latest leap year.
+        assert_eq!(infer_year((2, 29, midnight), at(2024, 3, 1)), Some(at(2024, 2, 29)));
+        assert_eq!(infer_year((2, 29, midnight), at(2027, 6, 1)), Some(at(2024, 2, 29)));
+        assert_eq!(infer_year((2, 29, midnight), at(2024, 2, 1)), Some(at(2020, 2, 29)));
+        assert_eq!(infer_year((2, 30, midnight), at(2024, 6, 1)), None);
+    }
+}
@@ -0,0 +1,291 @@
+//! Text reports from user-supplied [MiniJinja] templates.
+//!
+//! A template sees the JSON report (`stats`, `input`, `parse_warnings`,
+//! `latency`, `heatmap` and, when enabled, `alerts`, `findings` and
+//! `checks`) plus:
+//!
+//! - `endpoints`: per-endpoint `endpoint`, `requests`, `errors`,
+//!   `error_rate`, `avg_response_time`, `p50`, `p95`, `p99`, busiest first
+//! - `percentiles`: `p50`, `p95` and `p99` over all entries
+//! - `slowest`: entries with a response time, slowest first
+//! - `series`: `bucket_name`, `bucket_seconds` and `points` with `start`,
+//!   `requests`, `errors` and `avg_response_time`, or none without timestamps
+//! - `width`, `top` and `ascii` from the layout options
+//!
+//! Helpers: the filters `number`, `pct`, `duration`, `fit`, `pad`, `repeat`,
+//! `color` and `bold`, and the functions `percent(part, total)` and
+//! `table(rows, headers=…, columns=…, align=…)`. Blocks trim their trailing
+//! newline and leading indentation. [`DEFAULT_TEMPLATE`] renders the standard report.
+//!
+//! [MiniJinja]: https://docs.rs/minijinja
+
+use crate::log_analyzer::{AnalyzerError, EndpointStats, LogEntry, endpoint_stats, slowest_first};
+use crate::report::{Report, TimeSeries};
+use crate::text_report::{TextLayout, truncate};
+use colored::{Color, Colorize};
+use minijinja::value::{Kwargs, Value};
+use minijinja::{Environment, Error, ErrorKind, context};
+use unicode_width::UnicodeWidthStr;
+
+/// The standard text report as a template, a starting point for your own.
+pub const DEFAULT_TEMPLATE: &str = include_str!("../templates/report.txt.j2");
+
+/// Per-endpoint statistics, busiest first.
+fn endpoint_rows(entries: &[LogEntry]) -> Vec<EndpointStats> {
+    let mut rows = endpoint_stats(entries);
+    rows.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.endpoint.cmp(&b.endpoint)));
+    rows
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidOperation, message.into())
+}
+
+/// `12345.6|number` → `12,346`; `number(2)` keeps two decimals.
+fn number(value: f64, decimals: Option<usize>) -> String {
+    let formatted = format!("{:.*}", decimals.unwrap_or(0), value.abs());
+    let (int, frac) = formatted.split_once('.').map_or((formatted.as_str(), None), |(i, f)| (i, Some(f)));
+    let mut grouped = String::new();
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    let sign = if value < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') { "-" } else { "" };
+    match frac {
+        Some(frac) => format!("{sign}{grouped}.{frac}"),
+        None => format!("{sign}{grouped}"),
+    }
+}
+
+/// `14.28|pct` → `14.3%`; `3|pct(total=21)` → `14.3%`.
+fn pct(value: f64, kwargs: Kwargs) -> Result<String, Error> {
+    let total: Option<f64> = kwargs.get("total")?;
+    let decimals: Option<usize> = kwargs.get("decimals")?;
+    kwargs.assert_all_used()?;
+    let value = match total {
+        Some(0.0) => 0.0,
+        Some(total) => value / total * 100.0,
+        None => value,
+    };
+    Ok(format!("{:.*}%", decimals.unwrap_or(1), value))
+}
+
+/// Milliseconds for humans: `45ms`, `1.50s`, `2m 5s`, `1h 30m`.
+fn duration(ms: f64) -> String {
+    // Compare after rounding to the shown precision, so 59,999ms is `1m 0s`
+    // rather than `60.00s`.
+    if (ms * 100.0).round() < 100_000.0 {
+        return format!("{}ms", number(ms, Some(if ms.fract() == 0.0 { 0 } else { 2 })));
+    }
+    let seconds = ms / 1000.0;
+    if (seconds * 100.0).round() < 6_000.0 {
+        return format!("{seconds:.2}s");
+    }
+    let total = seconds.round() as u64;
+    let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+    if h > 0 { format!("{h}h {m}m") } else { format!("{m}m {s}s") }
+}
+
+/// `"text"|pad(10)` left-aligns in 10 columns, `pad(10, "right")` right-aligns.
+fn pad(value: String, width: usize, align: Option<String>) -> String {
+    let fill = " ".repeat(width.saturating_sub(value.width()));
+    match align.as_deref() {
+        Some("right") => format!("{fill}{value}"),
+        Some("center") => {
+            let (left, right) = fill.split_at(fill.len() / 2);
+            format!("{left}{value}{right}")
+        }
+        _ => format!("{value}{fill}"),
+    }
+}
+
+/// `"text"|color("red")`, honoring `--color` and `NO_COLOR`.
+fn color(value: String, name: String) -> Result<String, Error> {
+    let color: Color = name.replace(['_', '-'], " ").parse().map_err(|_| invalid(format!("unknown color '{name}'")))?;
+    Ok(value.color(color).to_string())
+}
+
+/// Plain-text table with columns sized to their widest cell. Rows are lists
+/// of cells, or records with `columns` naming the attributes to show.
+/// Keyword arguments: `headers`, `columns` and `align` (one letter per
+/// column, `l` or `r`; default left).
+fn table(rows: Vec<Value>, kwargs: Kwargs) -> Result<String, Error> {
+    let headers: Option<Vec<String>> = kwargs.get("headers")?;
+    let columns: Option<Vec<String>> = kwargs.get("columns")?;
+    let align: Option<String> = kwargs.get("align")?;
+    kwargs.assert_all_used()?;
+    let cell = |v: Value| if v.is_none() || v.is_undefined() { String::new() } else { v.to_string() };
+    let mut grid: Vec<Vec<String>> = Vec::new();
+    if let Some(headers) = &headers {
+        grid.push(headers.clone());
+    }
+    for row in rows {
+        let cells = match &columns {
+            Some(columns) => columns
+                .iter()
+                .map(|c| row.get_attr(c).map(cell))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => row.try_iter()?.map(cell).collect(),
+        };
+        grid.push(cells);
+    }
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..width)
+        .map(|c| grid.iter().filter_map(|row| row.get(c)).map(|s| s.width()).max().unwrap_or(0))
+        .collect();
+    let align: Vec<char> = align.unwrap_or_default().chars().collect();
+    let mut out = String::new();
+    for (i, row) in grid.iter().enumerate() {
+        let cells: Vec<String> = (0..width)
+            .map(|c| {
+                let text = row.get(c).cloned().unwrap_or_default();
+                let side = (align.get(c) == Some(&'r')).then(|| "right".to_string());
+                pad(text, widths[c], side)
+            })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+        if i == 0 && headers.is_some() {
+            let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+            out.push_str(&rule.join("  "));
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+fn environment(ascii: bool) -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.add_filter("number", |value: f64, decimals: Option<usize>| number(value, decimals));
+    env.add_filter("pct", pct);
+    env.add_filter("duration", duration);
+    env.add_filter("fit", move |value: String, width: usize| truncate(&value, width, ascii));
+    env.add_filter("pad", pad);
+    env.add_filter("repeat", |value: String, n: usize| value.repeat(n));
+    env.add_filter("color", color);
+    env.add_filter("bold", |value: String| value.bold().to_string());
+    env.add_function("percent", |part: f64, total: f64| {
+        if total == 0.0 { 0.0 } else { part / total * 100.0 }
+    });
+    env.add_function("table", table);
+    env
+}
+
+/// Render `source` (named `name` in error messages) for the report over
+/// `entries`.
+pub fn render(
+    source: &str,
+    name: &str,
+    report: &Report,
+    entries: &[LogEntry],
+    layout: &TextLayout,
+) -> Result<String, AnalyzerError> {
+    let mut env = environment(layout.ascii);
+    let template_error = |e: Error| AnalyzerError::TemplateError(e.to_string());
+    env.add_template(name, source).map_err(template_error)?;
+    let ctx = context! {
+        ..Value::from_serialize(report),
+        ..context! {
+            endpoints => Value::from_serialize(endpoint_rows(entries)),
+            percentiles => context! {
+                p50 => report.stats.response_time_percentile(50.0),
+                p95 => report.stats.response_time_percentile(95.0),
+                p99 => report.stats.response_time_percentile(99.0),
+            },
+            slowest => Value::from_serialize(slowest_first(entries)),
+            series => Value::from_serialize(TimeSeries::new(entries)),
+            width => layout.width,
+            top => layout.top,
+            ascii => layout.ascii,
+        }
+    };
+    env.get_template(name)
+        .and_then(|template| template.render(ctx))
+        .map_err(template_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_analyzer::LogStats;
+    use crate::report::{AppliedFilters, InputMetadata};
+    use std::path::Path;
+
+    const LINES: [&str; 5] = [
+        "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/orders 500 1900ms Payment failed",
+        "2024-01-15 08:15:20.000 INFO 10.0.0.2 GET /api/users 200 40ms",
+        "2024-01-15 08:15:40.000 WARNING 10.0.0.3 GET /api/a/really/long/endpoint/that/needs/truncating/to/fit 200 700ms",
+        "2024-01-15 08:16:10.000 INFO 10.0.0.1 GET /api/users 200 60ms",
+        "2024-01-15 08:16:30.000 INFO Service started",
+    ];
+
+    #[test]
+    fn default_template_matches_the_built_in_report() {
+        let entries: Vec<LogEntry> = LINES.iter().map(|line| LogEntry::parse_log(line).unwrap()).collect();
+        let stats = LogStats::from_entries(&entries);
+        for (width, ascii) in [(80, false), (60, true), (120, false), (40, true)] {
+            let layout = TextLayout { width, ascii, ..TextLayout::default() };
+            let input = InputMetadata::new(Path::new("server.log"), entries.len(), &entries, AppliedFilters::default());
+            let report = Report::new(input, &stats, &entries, &[]);
+            let rendered = render(DEFAULT_TEMPLATE, "report.txt.j2", &report, &entries, &layout).unwrap();
+            // The CLI prints a rendered template with a trailing newline.
+            assert_eq!(rendered + "\n", crate::text_report::render(&stats, &entries, &layout), "width {width}");
+        }
+    }
+
+    #[test]
+    fn number_groups_thousands_and_drops_the_sign_of_zero() {
+        assert_eq!(number(12345.6, None), "12,346");
+        assert_eq!(number(-1234.5, Some(1)), "-1,234.5");
+        assert_eq!(number(999.0, None), "999");
+        assert_eq!(number(1_000_000.0, Some(2)), "1,000,000.00");
+        assert_eq!(number(-0.4, None), "0");
+        assert_eq!(number(-0.004, Some(2)), "0.00");
+    }
+
+    #[test]
+    fn pct_scales_by_total_and_treats_a_zero_total_as_zero() {
+        let kwargs = |pairs: Vec<(&str, Value)>| Kwargs::from_iter(pairs);
+        assert_eq!(pct(14.28, kwargs(vec![])).unwrap(), "14.3%");
+        assert_eq!(pct(3.0, kwargs(vec![("total", Value::from(21))])).unwrap(), "14.3%");
+        assert_eq!(pct(3.0, kwargs(vec![("total", Value::from(0))])).unwrap(), "0.0%");
+        assert_eq!(pct(1.0, kwargs(vec![("total", Value::from(3)), ("decimals", Value::from(3))])).unwrap(), "33.333%");
+        assert!(pct(1.0, kwargs(vec![("totl", Value::from(3))])).is_err());
+    }
+
+    #[test]
+    fn duration_switches_units_at_the_rounded_value() {
+        assert_eq!(duration(45.0), "45ms");
+        assert_eq!(duration(12.345), "12.35ms");
+        assert_eq!(duration(999.999), "1.00s");
+        assert_eq!(duration(1500.0), "1.50s");
+        assert_eq!(duration(59_999.0), "1m 0s");
+        assert_eq!(duration(125_000.0), "2m 5s");
+        assert_eq!(duration(5_400_000.0), "1h 30m");
+    }
+
+    #[test]
+    fn table_sizes_columns_to_the_widest_cell() {
+        let rows = vec![
+            Value::from_serialize(serde_json::json!({"name": "/api/é", "hits": 5})),
+            Value::from_serialize(serde_json::json!({"name": "/x", "hits": 1234, "extra": true})),
+            Value::from_serialize(serde_json::json!({"name": "/none", "hits": null})),
+        ];
+        let kwargs = Kwargs::from_iter([
+            ("headers", Value::from(vec!["Endpoint", "Hits"])),
+            ("columns", Value::from(vec!["name", "hits"])),
+            ("align", Value::from("lr")),
+        ]);
+        assert_eq!(
+            table(rows, kwargs).unwrap(),
+            "Endpoint  Hits\n--------  ----\n/api/é       5\n/x        1234\n/none\n"
+        );
+
+        let lists = vec![Value::from(vec!["a", "bb"]), Value::from(vec!["ccc"])];
+        assert_eq!(table(lists, Kwargs::from_iter(Vec::<(&str, Value)>::new())).unwrap(), "a    bb\nccc\n");
+    }
+}
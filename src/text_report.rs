@@ -0,0 +1,645 @@
+//! The colored terminal report behind [`LogStats::print_report`], laid out
+//! for a given width with selectable sections.
+//!
+//! Colors come from `colored`, so `NO_COLOR` and `--color` are honored by
+//! setting its global override. Widths are measured in terminal columns, so
+//! wide and multibyte characters neither break the alignment nor get split.
+
+use crate::heatmap::{Heatmap, HeatmapMetric, WEEKDAYS};
+use crate::latency::{self, LatencyHistogram};
+use crate::log_analyzer::{LogEntry, LogStats};
+use chrono_tz::Tz;
+use colored::{Color, ColoredString, Colorize};
+use std::fmt::Write;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Rows per table when `--top` is not given.
+pub const DEFAULT_TOP: usize = 10;
+/// Width of the report when it is not written to a terminal.
+pub const DEFAULT_WIDTH: usize = 80;
+const MIN_WIDTH: usize = 40;
+const MAX_WIDTH: usize = 160;
+
+/// Sections of the text report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Section {
+    Summary,
+    Performance,
+    Endpoints,
+    Errors,
+    Slowest,
+    /// Log-scaled latency histogram with percentile markers.
+    Latency,
+    /// Hour-of-day by weekday heatmap.
+    Heatmap,
+}
+
+impl Section {
+    /// Sections printed unless `--sections` says otherwise.
+    pub const DEFAULT: [Section; 5] = [
+        Section::Summary,
+        Section::Performance,
+        Section::Endpoints,
+        Section::Errors,
+        Section::Slowest,
+    ];
+}
+
+/// When to color terminal output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorMode {
+    /// Color when writing to a terminal and `NO_COLOR` is unset.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Apply the choice to all colored output of the process.
+    pub fn apply(self, is_terminal: bool) {
+        colored::control::set_override(self.enabled(is_terminal, std::env::var_os("NO_COLOR").as_deref()));
+    }
+
+    /// Whether to color, given the value of `NO_COLOR`.
+    fn enabled(self, is_terminal: bool, no_color: Option<&std::ffi::OsStr>) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => is_terminal && no_color.is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextLayout {
+    /// Total width in columns.
+    pub width: usize,
+    /// Rows per table.
+    pub top: usize,
+    /// Sections to print, in order.
+    pub sections: Vec<Section>,
+    /// Only ASCII: no box drawing, bar blocks or emoji.
+    pub ascii: bool,
+    /// Endpoints that get their own histogram in the latency section.
+    pub latency_endpoints: Vec<String>,
+    /// What the heatmap section shows.
+    pub heatmap_metric: HeatmapMetric,
+    /// Timezone of the heatmap's hours and weekdays.
+    pub timezone: Tz,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            top: DEFAULT_TOP,
+            sections: Section::DEFAULT.to_vec(),
+            latency_endpoints: Vec::new(),
+            heatmap_metric: HeatmapMetric::default(),
+            timezone: Tz::UTC,
+            ascii: false,
+        }
+    }
+}
+
+impl TextLayout {
+    /// Width of the terminal on stdout, else `$COLUMNS`, else
+    /// [`DEFAULT_WIDTH`]; clamped to a readable range.
+    pub fn detect_width(is_terminal: bool) -> usize {
+        let terminal = is_terminal
+            .then(|| ratatui::crossterm::terminal::size().ok())
+            .flatten()
+            .map(|(columns, _)| columns as usize);
+        let env = || std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok());
+        terminal.or_else(env).unwrap_or(DEFAULT_WIDTH).clamp(MIN_WIDTH, MAX_WIDTH)
+    }
+
+    /// `emoji title`, or just the title with `ascii`.
+    pub fn heading(&self, emoji: &str, title: &str) -> String {
+        if self.ascii { title.to_string() } else { format!("{emoji} {title}") }
+    }
+
+    /// A horizontal rule across the report.
+    pub fn rule(&self) -> ColoredString {
+        (if self.ascii { "-" } else { "─" }).repeat(self.width).bright_black()
+    }
+
+    /// A section title under a blank line, ruled off from its content, for
+    /// sections printed outside [`render`] such as alerts and findings.
+    pub fn title(&self, emoji: &str, title: &str) -> String {
+        format!("\n{}\n{}", self.heading(emoji, title).bold().bright_white(), self.rule())
+    }
+}
+
+/// Shorten `text` to at most `width` columns, marking the cut with an
+/// ellipsis. Never splits a character.
+pub fn truncate(text: &str, width: usize, ascii: bool) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let ellipsis = if ascii { "..." } else { "…" };
+    let budget = width.saturating_sub(ellipsis.width());
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > budget {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push_str(ellipsis);
+    out
+}
+
+/// Pad to `width` columns on the right (`left` aligned) or on the left.
+pub fn pad(text: &str, width: usize, left: bool) -> String {
+    let fill = " ".repeat(width.saturating_sub(text.width()));
+    if left { format!("{text}{fill}") } else { format!("{fill}{text}") }
+}
+
+fn cell(text: &str, width: usize, ascii: bool) -> String {
+    pad(&truncate(text, width, ascii), width, true)
+}
+
+struct Renderer<'a> {
+    stats: &'a LogStats,
+    entries: &'a [LogEntry],
+    layout: &'a TextLayout,
+    out: String,
+}
+
+impl Renderer<'_> {
+    fn glyph(&self, unicode: &'static str, ascii: &'static str) -> &'static str {
+        if self.layout.ascii { ascii } else { unicode }
+    }
+
+    fn rule(&self) -> ColoredString {
+        self.layout.rule()
+    }
+
+    fn title(&mut self, emoji: &str, title: &str) {
+        let title = self.layout.title(emoji, title);
+        let _ = writeln!(self.out, "{title}");
+    }
+
+    /// Width of the labels of the summary and performance values, which are
+    /// right-aligned in ten columns after them.
+    fn label_width(&self) -> usize {
+        30.min(self.layout.width.saturating_sub(11))
+    }
+
+    fn pct(&self, count: usize) -> f64 {
+        if self.stats.total_requests == 0 {
+            return 0.0;
+        }
+        count as f64 / self.stats.total_requests as f64 * 100.0
+    }
+
+    fn header(&mut self) {
+        let inner = self.layout.width.saturating_sub(2);
+        let (tl, tr, bl, br, h, v) = if self.layout.ascii {
+            ("+", "+", "+", "+", "=", "|")
+        } else {
+            ("╔", "╗", "╚", "╝", "═", "║")
+        };
+        let title = pad(&format!("{}LOG ANALYSIS REPORT", " ".repeat(10.min(inner / 4))), inner, true);
+        let _ = writeln!(self.out, "\n{}", format!("{tl}{}{tr}", h.repeat(inner)).bright_cyan());
+        let _ = writeln!(self.out, "{}", format!("{v}{title}{v}").bright_cyan().bold());
+        let _ = writeln!(self.out, "{}", format!("{bl}{}{br}", h.repeat(inner)).bright_cyan());
+    }
+
+    fn footer(&mut self) {
+        let _ = writeln!(self.out, "{}", self.glyph("═", "=").repeat(self.layout.width).bright_cyan());
+        self.out.push('\n');
+    }
+
+    fn summary(&mut self) {
+        self.title("📊", "SUMMARY STATISTICS");
+        let label_width = self.label_width();
+        let _ = writeln!(
+            self.out,
+            "{:<label_width$} {:>10}",
+            "Total Requests:",
+            self.stats.total_requests.to_string().bright_white().bold()
+        );
+        let stats = self.stats;
+        let _ = writeln!(self.out, "\n{}", "Status Breakdown:".bright_white());
+        let rows = [
+            ("INFO", stats.info_count, "INFO".green(), stats.info_count.to_string().green()),
+            ("WARNING", stats.warning_count, "WARNING".yellow(), stats.warning_count.to_string().yellow()),
+            ("ERROR", stats.error_count, "ERROR".red(), stats.error_count.to_string().red().bold()),
+        ];
+        // Indent, label, count and a share of up to eight columns.
+        let status_width = 26.min(self.layout.width.saturating_sub(2 + 1 + 8 + 2 + 8));
+        for (label, count, colored_label, colored_count) in rows {
+            let _ = writeln!(
+                self.out,
+                "  {colored_label}{} {:>8}  {:>6}",
+                " ".repeat(status_width.saturating_sub(label.len())),
+                colored_count,
+                format!("({:.1}%)", self.pct(count)).bright_black()
+            );
+        }
+
+        let error_pct = self.pct(stats.error_count);
+        if error_pct > 5.0 {
+            let icon = self.glyph("⚠", "!");
+            let _ = writeln!(
+                self.out,
+                "\n  {} {}",
+                icon.yellow(),
+                format!("High error rate detected: {error_pct:.1}%").yellow().bold()
+            );
+        } else if error_pct > 1.0 {
+            let icon = self.glyph("ℹ", "i");
+            let _ = writeln!(
+                self.out,
+                "\n  {} {}",
+                icon.bright_blue(),
+                format!("Moderate error rate: {error_pct:.1}%").bright_blue()
+            );
+        }
+    }
+
+    fn performance(&mut self) {
+        self.title("⚡", "PERFORMANCE METRICS");
+        let stats = self.stats;
+        let label_width = self.label_width();
+        let _ = writeln!(
+            self.out,
+            "{:<label_width$} {:>10}",
+            "Average Response Time:",
+            format!("{:.2}ms", stats.avg_response_time).bright_cyan()
+        );
+        let rows = [
+            ("P50 Response Time:", 50.0, Color::BrightGreen),
+            ("P95 Response Time:", 95.0, Color::Yellow),
+            ("P99 Response Time:", 99.0, Color::Red),
+        ];
+        for (label, pct, color) in rows {
+            if let Some(value) = stats.response_time_percentile(pct) {
+                let _ = writeln!(self.out, "{label:<label_width$} {:>10}", format!("{value:.2}ms").color(color));
+            }
+        }
+    }
+
+    fn sorted(map: &std::collections::HashMap<String, usize>) -> Vec<(&String, &usize)> {
+        let mut rows: Vec<_> = map.iter().collect();
+        rows.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        rows
+    }
+
+    fn table_header(&mut self, columns: &[(&str, usize, bool)]) {
+        let header: Vec<String> = columns.iter().map(|(name, width, left)| pad(name, *width, *left)).collect();
+        let rule = self.rule();
+        let _ = writeln!(self.out, "{}", header.join(" ").trim_end().bright_black());
+        let _ = writeln!(self.out, "{rule}");
+    }
+
+    fn endpoints(&mut self) {
+        let top = self.layout.top;
+        self.title("🔝", &format!("TOP {top} ENDPOINTS BY REQUEST COUNT"));
+        // `#`, endpoint, count and a bar of the endpoint's share of requests.
+        let available = self.layout.width.saturating_sub(4 + 10 + 3);
+        let endpoint_width = (available * 2 / 3).max(12);
+        let bar_width = available.saturating_sub(endpoint_width);
+        self.table_header(&[("#", 4, true), ("Endpoint", endpoint_width, true), ("Count", 10, false)]);
+        let bar = self.glyph("█", "#");
+        for (i, (endpoint, count)) in Self::sorted(&self.stats.endpoint_frequency).into_iter().take(top).enumerate() {
+            let filled = (*count as f64 / self.stats.total_requests as f64 * bar_width as f64) as usize;
+            let _ = writeln!(
+                self.out,
+                "{} {} {} {}",
+                pad(&(i + 1).to_string(), 4, true).bright_cyan(),
+                cell(endpoint, endpoint_width, self.layout.ascii),
+                pad(&count.to_string(), 10, false).bright_white().bold(),
+                bar.repeat(filled).bright_blue()
+            );
+        }
+    }
+
+    fn errors(&mut self) {
+        if self.stats.errors_by_endpoint.is_empty() {
+            let title = self.layout.heading("✅", "ERROR ANALYSIS: No errors detected");
+            let _ = writeln!(self.out, "\n{}", title.bold().green());
+            return;
+        }
+        self.title("🚨", "ERROR ANALYSIS");
+        let endpoint_width = self.layout.width.saturating_sub(4 + 10 + 2).max(12);
+        self.table_header(&[("#", 4, true), ("Endpoint", endpoint_width, true), ("Errors", 10, false)]);
+        for (i, (endpoint, count)) in Self::sorted(&self.stats.errors_by_endpoint)
+            .into_iter()
+            .take(self.layout.top)
+            .enumerate()
+        {
+            let _ = writeln!(
+                self.out,
+                "{} {} {}",
+                pad(&(i + 1).to_string(), 4, true).bright_cyan(),
+                cell(endpoint, endpoint_width, self.layout.ascii),
+                pad(&count.to_string(), 10, false).red().bold()
+            );
+        }
+    }
+
+    fn slowest(&mut self) {
+        let top = self.layout.top;
+        self.title("🐌", &format!("TOP {top} SLOWEST REQUESTS"));
+        let endpoint_width = self.layout.width.saturating_sub(4 + 10 + 12 + 3).max(11);
+        self.table_header(&[("#", 4, true), ("Endpoint", endpoint_width, true), ("Method", 10, true), ("Time", 12, false)]);
+        let slowest: Vec<&LogEntry> = self
+            .stats
+            .slowest_requests
+            .iter()
+            .filter(|e| e.response_time.is_some())
+            .take(top)
+            .collect();
+        for (i, entry) in slowest.into_iter().enumerate() {
+            let response_time = entry.response_time.unwrap_or_default();
+            let endpoint = entry.endpoint.as_deref().unwrap_or("N/A");
+            let method = entry.method.as_ref().map(|m| m.to_string()).unwrap_or_else(|| "N/A".to_string());
+            let time = pad(&format!("{response_time:.2}ms"), 12, false);
+            let time = if response_time > 1000.0 {
+                time.red().bold()
+            } else if response_time > 500.0 {
+                time.yellow()
+            } else {
+                time.bright_white()
+            };
+            let _ = writeln!(
+                self.out,
+                "{} {} {} {}",
+                pad(&(i + 1).to_string(), 4, true).bright_cyan(),
+                cell(endpoint, endpoint_width, self.layout.ascii),
+                pad(&method, 10, true),
+                time
+            );
+        }
+    }
+
+    fn latency(&mut self) {
+        let histograms = latency::histograms(self.entries, &self.layout.latency_endpoints);
+        if histograms.is_empty() {
+            self.title("⏱", "LATENCY DISTRIBUTION");
+            let _ = writeln!(self.out, "{}", "No response times in the input.".bright_black());
+            return;
+        }
+        for histogram in &histograms {
+            self.histogram(histogram);
+        }
+        for endpoint in &self.layout.latency_endpoints {
+            if !histograms.iter().any(|h| h.endpoint.as_ref() == Some(endpoint)) {
+                let _ = writeln!(self.out, "\n{}", format!("No response times for {endpoint}.").bright_black());
+            }
+        }
+    }
+
+    fn histogram(&mut self, histogram: &LatencyHistogram) {
+        let ascii = self.layout.ascii;
+        let title = match &histogram.endpoint {
+            Some(endpoint) => format!("LATENCY DISTRIBUTION: {}", truncate(endpoint, self.layout.width.saturating_sub(25), ascii)),
+            None => "LATENCY DISTRIBUTION".to_string(),
+        };
+        self.title("⏱", &title);
+        let dot = self.glyph(" · ", ", ");
+        let parts = [
+            format!("{} requests", histogram.count),
+            format!("P50 {:.2}ms", histogram.p50),
+            format!("P95 {:.2}ms", histogram.p95),
+            format!("P99 {:.2}ms", histogram.p99),
+        ];
+        // Wrap the summary between its parts when the report is narrow.
+        let mut line = String::new();
+        for part in parts {
+            if !line.is_empty() && line.width() + dot.width() + part.width() > self.layout.width {
+                let _ = writeln!(self.out, "{}", line.bright_black());
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push_str(dot);
+            }
+            line.push_str(&part);
+        }
+        let _ = writeln!(self.out, "{}", line.bright_black());
+        // Bucket, count, cumulative share, a bar and the percentile markers.
+        // The cumulative share is dropped when it would leave no room for bars.
+        let cumulative = self.layout.width >= 10 + 8 + 8 + 4 + 14 + 5;
+        let fixed = if cumulative { 10 + 8 + 8 + 4 + 14 } else { 10 + 8 + 3 + 14 };
+        let bar_width = self.layout.width.saturating_sub(fixed).max(5);
+        if cumulative {
+            self.table_header(&[("Bucket", 10, true), ("Count", 8, false), ("Cum %", 8, false)]);
+        } else {
+            self.table_header(&[("Bucket", 10, true), ("Count", 8, false)]);
+        }
+        let max = histogram.buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+        let bar = self.glyph("█", "#");
+        let arrow = self.glyph("◀", "<");
+        let markers = [("P50", histogram.p50), ("P95", histogram.p95), ("P99", histogram.p99)];
+        for (i, bucket) in histogram.buckets.iter().enumerate() {
+            let filled = (bucket.count as f64 / max as f64 * bar_width as f64).ceil() as usize;
+            let marked: Vec<&str> = markers
+                .iter()
+                .filter(|(_, value)| histogram.bucket_of(*value) == Some(i))
+                .map(|(name, _)| *name)
+                .collect();
+            let (bars, marker) = if marked.is_empty() {
+                (bar.repeat(filled), String::new())
+            } else {
+                (pad(&bar.repeat(filled), bar_width, true), format!(" {arrow} {}", marked.join(" ")))
+            };
+            let _ = write!(
+                self.out,
+                "{} {}",
+                pad(&bucket.label(ascii), 10, true).bright_cyan(),
+                pad(&bucket.count.to_string(), 8, false).bright_white().bold()
+            );
+            if cumulative {
+                let _ = write!(self.out, " {}", pad(&format!("{:.1}%", bucket.cumulative_percent), 8, false).bright_black());
+            }
+            if !bars.is_empty() {
+                let _ = write!(self.out, " {}{}", bars.bright_blue(), marker.yellow().bold());
+            }
+            self.out.push('\n');
+        }
+    }
+
+    fn heatmap(&mut self) {
+        let (metric, timezone) = (self.layout.heatmap_metric, self.layout.timezone);
+        self.title("🗓", &format!("WEEKLY HEATMAP: {} ({})", metric.label().to_uppercase(), timezone.name()));
+        let Some(heatmap) = Heatmap::new(self.entries, metric, timezone) else {
+            let _ = writeln!(self.out, "{}", "No timestamps in the input.".bright_black());
+            return;
+        };
+        // A weekday label, then 24 cells of three columns, or fewer when
+        // narrow; cells of one column have no gap between them.
+        let cell_width = match self.layout.width {
+            w if w >= 5 + 24 * 3 => 3,
+            w if w >= 5 + 24 * 2 => 2,
+            _ => 1,
+        };
+        let hours: String = (0..24)
+            .map(|hour| match cell_width {
+                3 => format!("{hour:>2} "),
+                2 if hour % 3 == 0 => format!("{hour:<2}"),
+                2 => "  ".to_string(),
+                _ if hour % 6 == 0 => format!("{hour:<6}"),
+                _ => String::new(),
+            })
+            .collect();
+        let _ = writeln!(self.out, "{}", format!("     {}", hours.trim_end()).bright_black());
+        let shades: [&str; 5] = if self.layout.ascii {
+            [".", "-", "+", "*", "#"]
+        } else {
+            ["·", "░", "▒", "▓", "█"]
+        };
+        let colors = [Color::BrightBlack, Color::Blue, Color::Cyan, Color::Yellow, Color::Red];
+        let max = heatmap.max.unwrap_or(0.0);
+        let level = |value: f64| if max > 0.0 { ((value / max * 4.0).ceil() as usize).min(4) } else { 0 };
+        for (day, row) in heatmap.values.iter().enumerate() {
+            let mut cells: Vec<String> = row
+                .iter()
+                .map(|value| match value {
+                    Some(value) => {
+                        let level = level(*value);
+                        let shade = shades[level].repeat((cell_width - 1).max(1));
+                        let gap = if cell_width > 1 { " " } else { "" };
+                        format!("{}{gap}", shade.color(colors[level]))
+                    }
+                    None => " ".repeat(cell_width),
+                })
+                .collect();
+            while cells.last().is_some_and(|c| c.trim().is_empty()) {
+                cells.pop();
+            }
+            let label = if cells.is_empty() { WEEKDAYS[day].to_string() } else { pad(WEEKDAYS[day], 5, true) };
+            let _ = writeln!(self.out, "{}{}", label.bright_white(), cells.concat().trim_end());
+        }
+        let threshold = |level: usize| match metric {
+            HeatmapMetric::Volume => format!("{:.0}", max * level as f64 / 4.0),
+            HeatmapMetric::ErrorRate => format!("{:.1}%", max * level as f64 / 4.0),
+            HeatmapMetric::P95 => format!("{:.0}ms", max * level as f64 / 4.0),
+        };
+        let le = self.glyph("≤", "<=");
+        let legend: Vec<String> = (0..5)
+            .map(|level| {
+                let bound = if level == 0 { "0".to_string() } else { format!("{le}{}", threshold(level)) };
+                format!("{} {bound}", shades[level].color(colors[level]))
+            })
+            .collect();
+        let _ = writeln!(self.out, "\n     {}", legend.join("  "));
+        if let Some((day, hour, value)) = heatmap.peak() {
+            let _ = writeln!(
+                self.out,
+                "     {}",
+                format!("Peak: {} {hour:02}:00, {}", WEEKDAYS[day], metric.format(value)).bright_black()
+            );
+        }
+    }
+}
+
+/// Render the report for `layout`; the latency and heatmap sections are
+/// built from `entries`.
+pub fn render(stats: &LogStats, entries: &[LogEntry], layout: &TextLayout) -> String {
+    let mut renderer = Renderer { stats, entries, layout, out: String::new() };
+    renderer.header();
+    for section in &layout.sections {
+        match section {
+            Section::Summary => renderer.summary(),
+            Section::Performance => renderer.performance(),
+            Section::Endpoints => renderer.endpoints(),
+            Section::Errors => renderer.errors(),
+            Section::Slowest => renderer.slowest(),
+            Section::Latency => renderer.latency(),
+            Section::Heatmap => renderer.heatmap(),
+        }
+    }
+    renderer.footer();
+    renderer.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn truncate_counts_columns_and_keeps_characters_whole() {
+        assert_eq!(truncate("/api/users", 10, false), "/api/users");
+        assert_eq!(truncate("/api/users", 8, false), "/api/us…");
+        assert_eq!(truncate("/api/users", 8, true), "/api/...");
+        assert_eq!(truncate("/café/crème", 8, false), "/café/c…");
+        // Wide characters take two columns and are never split.
+        assert_eq!(truncate("/日本語/路径", 6, false), "/日本…");
+        assert_eq!(truncate("/日本語/路径", 5, false), "/日…");
+        assert_eq!(truncate("🚀🚀🚀", 4, true), "...");
+        assert_eq!(truncate("🚀🚀🚀", 5, false), "🚀🚀…");
+        for width in 0..12 {
+            assert!(truncate("/日本語/路径/🚀", width, false).width() <= width.max(1), "width {width}");
+        }
+    }
+
+    #[test]
+    fn truncate_narrower_than_the_ellipsis() {
+        assert_eq!(truncate("/api/users", 1, false), "…");
+        assert_eq!(truncate("/api/users", 2, true), "...");
+        assert_eq!(truncate("/api/users", 0, false), "…");
+    }
+
+    #[test]
+    fn pad_fills_columns_on_either_side() {
+        assert_eq!(pad("ab", 4, true), "ab  ");
+        assert_eq!(pad("ab", 4, false), "  ab");
+        assert_eq!(pad("日本", 6, true), "日本  ");
+        assert_eq!(pad("toolong", 3, true), "toolong");
+        assert_eq!(cell("/日本語", 6, false), "/日本…");
+    }
+
+    #[test]
+    fn auto_color_honors_no_color() {
+        let set = Some(std::ffi::OsStr::new("1"));
+        let empty = Some(std::ffi::OsStr::new(""));
+        assert!(ColorMode::Always.enabled(false, None));
+        assert!(!ColorMode::Never.enabled(true, None));
+        assert!(!ColorMode::Auto.enabled(true, set));
+        assert!(ColorMode::Always.enabled(true, set));
+        assert!(ColorMode::Auto.enabled(true, empty));
+        assert!(ColorMode::Auto.enabled(true, None));
+        assert!(!ColorMode::Auto.enabled(false, None));
+    }
+
+    #[test]
+    fn narrow_ascii_report_fits_its_width() {
+        let lines = [
+            "2024-01-15 08:15:01.000 ERROR 10.0.0.1 POST /api/orders/with/a/rather/long/path/to/truncate 500 1900ms",
+            "2024-01-15 08:15:20.000 INFO 10.0.0.2 GET /api/users 200 40ms",
+            "2024-01-15 09:15:40.000 WARNING 10.0.0.3 GET /launch 200 700ms",
+            "2024-01-16 23:16:10.000 ERROR 10.0.0.1 GET /api/users 503 60ms",
+        ];
+        let entries: Vec<LogEntry> = lines.iter().map(|line| LogEntry::parse_log(line).unwrap()).collect();
+        let stats = LogStats::from_entries(&entries);
+        let layout = TextLayout {
+            width: MIN_WIDTH,
+            ascii: true,
+            sections: vec![
+                Section::Summary,
+                Section::Performance,
+                Section::Endpoints,
+                Section::Errors,
+                Section::Slowest,
+                Section::Latency,
+                Section::Heatmap,
+            ],
+            ..TextLayout::default()
+        };
+        let ansi = Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        let report = render(&stats, &entries, &layout);
+        let report = ansi.replace_all(&report, "");
+        for line in report.lines() {
+            assert!(line.is_ascii(), "{line:?}");
+            assert!(line.len() <= MIN_WIDTH, "{} columns: {line:?}", line.len());
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! `--since` / `--until` expressions and seeking inside time-sorted files.
+//!
+//! Relative expressions are resolved against an anchor, which is the newest
+//! entry in the input rather than the wall clock: analyzing yesterday's
+//! archive with `--since "last 15m"` selects its final fifteen minutes.
+
+use crate::filter::TimeRangeFilter;
+use crate::log_analyzer::{AnalyzerError, LogEntry, ParseOptions, parse_duration};
+use chrono::{DateTime, Days, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    str::FromStr,
+};
+
+/// How many lines a probe reads looking for a timestamp before giving up.
+const MAX_PROBE_LINES: usize = 64;
+
+/// A point in time as written on the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeSpec {
+    /// A full date/time; offsets such as `+02:00` are converted to UTC.
+    Absolute(NaiveDateTime),
+    /// `2h ago` or `-2h`.
+    Ago(Duration),
+    /// `last 15m`: the same as `15m ago`, read as the start of a window.
+    Last(Duration),
+    /// `today`, `today 14:00`, `yesterday`, `yesterday 14:00`.
+    DaysBack(i64, NaiveTime),
+    /// A bare time of day such as `08:16`, on the anchor's date.
+    TimeOfDay(NaiveTime),
+}
+
+impl TimeSpec {
+    /// Whether resolving this spec needs the newest timestamp in the input.
+    pub fn is_relative(&self) -> bool {
+        !matches!(self, TimeSpec::Absolute(_))
+    }
+
+    /// The point in time this spec names, relative to `anchor`. Fails when
+    /// it lies outside the representable range.
+    pub fn resolve(&self, anchor: NaiveDateTime) -> Result<NaiveDateTime, AnalyzerError> {
+        let resolved = match self {
+            TimeSpec::Absolute(ts) => Some(*ts),
+            TimeSpec::Ago(d) | TimeSpec::Last(d) => anchor.checked_sub_signed(*d),
+            TimeSpec::DaysBack(days, time) => u64::try_from(*days)
+                .ok()
+                .and_then(|days| anchor.date().checked_sub_days(Days::new(days)))
+                .map(|date| date.and_time(*time)),
+            TimeSpec::TimeOfDay(time) => Some(anchor.date().and_time(*time)),
+        };
+        resolved.ok_or_else(|| AnalyzerError::InvalidFilter("time is out of range".to_string()))
+    }
+}
+
+impl FromStr for TimeSpec {
+    type Err = AnalyzerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || AnalyzerError::InvalidFilter(format!("invalid time expression '{s}'"));
+        let lower = s.to_lowercase();
+
+        if let Some(duration) = lower.strip_prefix("last ") {
+            return Ok(TimeSpec::Last(parse_duration(duration)?));
+        }
+        if let Some(duration) = lower.strip_suffix(" ago") {
+            return Ok(TimeSpec::Ago(parse_duration(duration)?));
+        }
+        if let Some(duration) = lower.strip_prefix('-')
+            && duration.starts_with(|c: char| c.is_ascii_digit())
+            && let Ok(duration) = parse_duration(duration)
+        {
+            return Ok(TimeSpec::Ago(duration));
+        }
+        for (word, days) in [("today", 0), ("yesterday", 1)] {
+            if let Some(rest) = lower.strip_prefix(word) {
+                let time = match rest.trim() {
+                    "" => NaiveTime::MIN,
+                    time => parse_time(time).ok_or_else(invalid)?,
+                };
+                return Ok(TimeSpec::DaysBack(days, time));
+            }
+        }
+        if let Some(time) = parse_time(s) {
+            return Ok(TimeSpec::TimeOfDay(time));
+        }
+        parse_datetime(s).map(TimeSpec::Absolute).ok_or_else(invalid)
+    }
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    ["%H:%M:%S%.f", "%H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveTime::parse_from_str(s, fmt).ok())
+}
+
+/// Parse an absolute timestamp: RFC 3339 / ISO 8601 with or without offset,
+/// `%Y-%m-%d %H:%M[:%S]`, or a bare date.
+pub fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.naive_utc());
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S%.f%:z", "%Y-%m-%d %H:%M:%S%.f%z"] {
+        if let Ok(dt) = DateTime::parse_from_str(s, fmt) {
+            return Some(dt.naive_utc());
+        }
+    }
+    let normalized = s.trim_end_matches('Z').replacen('T', " ", 1);
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(&normalized, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(&normalized, "%Y-%m-%d")
+                .ok()
+                .map(|d| d.and_time(NaiveTime::MIN))
+        })
+}
+
+/// `--since` / `--until` as given, before the anchor is known.
+#[derive(Debug, Clone, Default)]
+pub struct TimeWindow {
+    pub since: Option<TimeSpec>,
+    pub until: Option<TimeSpec>,
+}
+
+impl TimeWindow {
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn needs_anchor(&self) -> bool {
+        self.since.iter().chain(&self.until).any(TimeSpec::is_relative)
+    }
+
+    /// Resolve both bounds. `anchor` is only consulted for relative specs.
+    pub fn resolve(&self, anchor: Option<NaiveDateTime>) -> Result<TimeRangeFilter, AnalyzerError> {
+        let resolve = |spec: &Option<TimeSpec>| -> Result<Option<NaiveDateTime>, AnalyzerError> {
+            match spec {
+                None => Ok(None),
+                Some(TimeSpec::Absolute(ts)) => Ok(Some(*ts)),
+                Some(spec) => {
+                    let anchor = anchor.ok_or_else(|| {
+                        AnalyzerError::InvalidFilter(
+                            "relative time needs at least one timestamped entry".to_string(),
+                        )
+                    })?;
+                    spec.resolve(anchor).map(Some)
+                }
+            }
+        };
+        Ok(TimeRangeFilter::new(resolve(&self.since)?, resolve(&self.until)?))
+    }
+}
+
+/// The newest timestamp among already parsed entries.
+pub fn newest_timestamp(entries: &[LogEntry]) -> Option<NaiveDateTime> {
+    entries.iter().filter_map(LogEntry::parsed_timestamp).max()
+}
+
+/// Timestamp of the last timestamped line of a time-sorted file, found by
+/// reading backwards from the end instead of parsing the whole file. Lines
+/// are parsed with `options`, as in the full read.
+pub fn last_timestamp_in_file(
+    file: &mut File,
+    options: &ParseOptions,
+) -> Result<Option<NaiveDateTime>, AnalyzerError> {
+    let len = file.metadata()?.len();
+    let mut chunk = 4096u64;
+    loop {
+        let start = len.saturating_sub(chunk);
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::new();
+        file.by_ref().take(len - start).read_to_end(&mut buf)?;
+        let text = String::from_utf8_lossy(&buf);
+        // The first line of a chunk may be cut in half unless it starts the file.
+        let skip = usize::from(start > 0);
+        let found = text
+            .lines()
+            .skip(skip)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .find_map(|line| line_timestamp(line, options));
+        if found.is_some() || start == 0 {
+            return Ok(found);
+        }
+        chunk *= 4;
+    }
+}
+
+/// Byte offset of the first line whose timestamp is at or after `target`,
+/// found by binary search. The file must be sorted by time; lines without a
+/// timestamp take the timestamp of the next line that has one.
+pub fn seek_to_time(file: &mut File, target: NaiveDateTime, options: &ParseOptions) -> Result<u64, AnalyzerError> {
+    let len = file.metadata()?.len();
+    let (mut low, mut high) = (0u64, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match probe(file, mid, options)?.1 {
+            Some(ts) if ts < target => low = mid + 1,
+            _ => high = mid,
+        }
+    }
+    Ok(probe(file, low, options)?.0)
+}
+
+/// Find the first line starting at or after `offset` and return its start
+/// with the first timestamp found from there on.
+fn probe(
+    file: &mut File,
+    offset: u64,
+    options: &ParseOptions,
+) -> Result<(u64, Option<NaiveDateTime>), AnalyzerError> {
+    let mut line = Vec::new();
+    let line_start = if offset == 0 {
+        file.seek(SeekFrom::Start(0))?;
+        0
+    } else {
+        file.seek(SeekFrom::Start(offset - 1))?;
+        let mut reader = BufReader::new(file.by_ref());
+        let skipped = reader.read_until(b'\n', &mut line)? as u64;
+        offset - 1 + skipped
+    };
+    file.seek(SeekFrom::Start(line_start))?;
+    let mut reader = BufReader::new(file.by_ref());
+    for _ in 0..MAX_PROBE_LINES {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if let Some(ts) = line_timestamp(&String::from_utf8_lossy(&line), options) {
+            return Ok((line_start, Some(ts)));
+        }
+    }
+    Ok((line_start, None))
+}
+
+fn line_timestamp(line: &str, options: &ParseOptions) -> Option<NaiveDateTime> {
+    LogEntry::parse_log_with(line, options).ok()?.parsed_timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::log_analyzer::Logs;
+    use std::path::PathBuf;
+
+    fn at(s: &str) -> NaiveDateTime {
+        parse_datetime(s).unwrap()
+    }
+
+    fn spec(s: &str) -> TimeSpec {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn specs_parse() {
+        let time = |s: &str| NaiveTime::parse_from_str(s, "%H:%M:%S").unwrap();
+        assert_eq!(spec("2h ago"), TimeSpec::Ago(Duration::hours(2)));
+        assert_eq!(spec("-90s"), TimeSpec::Ago(Duration::seconds(90)));
+        assert_eq!(spec("Last 15m"), TimeSpec::Last(Duration::minutes(15)));
+        assert_eq!(spec("today"), TimeSpec::DaysBack(0, NaiveTime::MIN));
+        assert_eq!(spec("yesterday 14:00"), TimeSpec::DaysBack(1, time("14:00:00")));
+        assert_eq!(spec("08:16"), TimeSpec::TimeOfDay(time("08:16:00")));
+        assert_eq!(spec("08:16:30.5"), TimeSpec::TimeOfDay(NaiveTime::from_hms_milli_opt(8, 16, 30, 500).unwrap()));
+        assert_eq!(spec("2024-01-15"), TimeSpec::Absolute(at("2024-01-15 00:00")));
+        assert_eq!(spec("2024-01-15T08:15:00Z"), TimeSpec::Absolute(at("2024-01-15 08:15")));
+        assert_eq!(spec("2024-01-15 10:15:00+02:00"), TimeSpec::Absolute(at("2024-01-15 08:15")));
+        assert_eq!(spec("2024-01-15T10:15:00.250+02:00"), TimeSpec::Absolute(at("2024-01-15 08:15:00.250")));
+        for bad in ["", "soon", "yesterday noon", "2h", "last week", "-", "25:00", "2024-13-01"] {
+            assert!(bad.parse::<TimeSpec>().is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn specs_resolve_against_the_anchor() {
+        let anchor = at("2024-01-15 08:30:00");
+        assert_eq!(spec("2h ago").resolve(anchor).unwrap(), at("2024-01-15 06:30"));
+        assert_eq!(spec("last 45m").resolve(anchor).unwrap(), at("2024-01-15 07:45"));
+        assert_eq!(spec("yesterday 14:00").resolve(anchor).unwrap(), at("2024-01-14 14:00"));
+        assert_eq!(spec("today").resolve(anchor).unwrap(), at("2024-01-15 00:00"));
+        assert_eq!(spec("08:16").resolve(anchor).unwrap(), at("2024-01-15 08:16"));
+        assert_eq!(spec("2020-02-29").resolve(anchor).unwrap(), at("2020-02-29 00:00"));
+        assert!(TimeSpec::Ago(Duration::days(1)).resolve(NaiveDateTime::MIN).is_err());
+        assert!(TimeSpec::DaysBack(-1, NaiveTime::MIN).resolve(anchor).is_err());
+    }
+
+    #[test]
+    fn windows_resolve_relative_bounds_only_with_an_anchor() {
+        let window = TimeWindow { since: Some(spec("last 10m")), until: Some(spec("2024-01-15 08:25")) };
+        assert!(window.needs_anchor());
+        let range = window.resolve(Some(at("2024-01-15 08:30"))).unwrap();
+        assert_eq!((range.start, range.end), (Some(at("2024-01-15 08:20")), Some(at("2024-01-15 08:25"))));
+        assert!(window.resolve(None).is_err());
+
+        let absolute = TimeWindow { since: Some(spec("2024-01-15")), until: None };
+        assert!(!absolute.needs_anchor());
+        assert_eq!(absolute.resolve(None).unwrap().start, Some(at("2024-01-15 00:00")));
+        assert!(TimeWindow::default().is_empty());
+    }
+
+    const SORTED: &str = "\
+2024-01-15 08:00:00.000 INFO 10.0.0.1 GET /a 200 5ms
+2024-01-15 08:05:00.000 INFO 10.0.0.1 GET /b 200 5ms
+continuation line without a timestamp
+2024-01-15 08:10:00.000 ERROR 10.0.0.2 POST /c 500 50ms
+2024-01-15 08:10:00.000 INFO 10.0.0.2 GET /d 200 5ms
+another untimed line
+2024-01-15 08:15:00.000 WARNING 10.0.0.3 GET /e 404 5ms
+2024-01-15 08:20:00.000 INFO 10.0.0.3 GET /f 200 5ms
+";
+
+    fn raw_lines(logs: &Logs) -> Vec<&str> {
+        logs.entries.iter().map(|e| e.raw.as_str()).collect()
+    }
+
+    #[test]
+    fn sorted_seek_matches_a_full_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sorted.log");
+        std::fs::write(&path, SORTED).unwrap();
+
+        let mut full = Logs::default();
+        full.read_and_parse_log(path.clone()).unwrap();
+        let windows = [
+            (Some("2024-01-15 08:10"), None),
+            (Some("2024-01-15 08:09:59"), Some("2024-01-15 08:15")),
+            (Some("2024-01-15 08:10:00.001"), None),
+            (Some("2024-01-15 07:00"), Some("2024-01-15 08:05")),
+            (Some("2024-01-15 08:20"), None),
+            (Some("2024-01-15 09:00"), None),
+            (None, Some("2024-01-15 08:00")),
+            (Some("last 10m"), None),
+            (Some("08:05"), Some("-1m")),
+        ];
+        for (since, until) in windows {
+            let window = TimeWindow { since: since.map(spec), until: until.map(spec) };
+            let range = window.resolve(newest_timestamp(&full.entries)).unwrap();
+            let expected: Vec<&str> = full.entries.iter().filter(|e| range.matches(e)).map(|e| e.raw.as_str()).collect();
+            let mut sought = Logs::default();
+            sought.read_and_parse_log_window(PathBuf::from(&path), &window).unwrap();
+            assert_eq!(raw_lines(&sought), expected, "{since:?} .. {until:?}");
+        }
+    }
+
+    #[test]
+    fn seek_lands_on_the_untimed_line_before_the_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sorted.log");
+        std::fs::write(&path, SORTED).unwrap();
+        let mut file = File::open(&path).unwrap();
+        let options = ParseOptions::default();
+        let offset = |target: &str| seek_to_time(&mut File::open(&path).unwrap(), at(target), &options).unwrap() as usize;
+        // Untimed lines take the timestamp of the next timed line.
+        assert!(SORTED[offset("2024-01-15 08:10")..].starts_with("continuation line"));
+        assert!(SORTED[offset("2024-01-15 08:00")..].starts_with("2024-01-15 08:00:00"));
+        assert!(SORTED[offset("2024-01-15 08:12")..].starts_with("another untimed line"));
+        assert_eq!(offset("2024-01-15 09:00"), SORTED.len());
+        assert_eq!(last_timestamp_in_file(&mut file, &options).unwrap(), Some(at("2024-01-15 08:20")));
+    }
+
+    #[test]
+    fn untimed_files_give_an_empty_window_on_both_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("untimed.log");
+        std::fs::write(&path, "GET /api/users 200 40ms\nPOST /api/orders 500 900ms\n").unwrap();
+        let window = TimeWindow { since: Some(spec("2024-01-15 08:00")), until: None };
+
+        let mut full = Logs::default();
+        full.read_and_parse_log(path.clone()).unwrap();
+        assert_eq!(full.entries.len(), 2);
+        full.retain(&window.resolve(newest_timestamp(&full.entries)).unwrap());
+        assert!(full.entries.is_empty());
+
+        let mut sought = Logs::default();
+        let result = sought.read_and_parse_log_window(path.clone(), &window).unwrap();
+        assert_eq!(result.entries_parsed, 0);
+        assert!(sought.entries.is_empty());
+
+        std::fs::write(&path, "not a log line\n").unwrap();
+        let full = Logs::default().read_and_parse_log(path.clone()).unwrap_err();
+        let sought = Logs::default().read_and_parse_log_window(path, &window).unwrap_err();
+        assert_eq!(full.to_string(), sought.to_string());
+    }
+}
@@ -0,0 +1,601 @@
+//! Interactive explorer: a filter bar, the summary with the top-endpoint and
+//! error tables, a scrollable entry list and a detail view of the selected
+//! entry.
+//!
+//! The filter bar takes the same expressions as `--where`. Enter on a row of
+//! the endpoint or error table narrows the filter to that endpoint; Esc steps
+//! back to the previous filter.
+
+use crate::log_analyzer::{LogEntry, LogLevel, LogStats};
+use crate::query::Expr;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+
+/// Rows moved by PageUp/PageDown.
+const PAGE: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Endpoints,
+    Errors,
+    Entries,
+    Detail,
+}
+
+impl Pane {
+    const ORDER: [Pane; 4] = [Pane::Endpoints, Pane::Errors, Pane::Entries, Pane::Detail];
+
+    fn cycle(self, forward: bool) -> Pane {
+        let i = Self::ORDER.iter().position(|p| *p == self).unwrap_or(0);
+        let n = Self::ORDER.len();
+        Self::ORDER[if forward { (i + 1) % n } else { (i + n - 1) % n }]
+    }
+}
+
+/// Filter bar being edited: the text and the cursor as a char index.
+struct Editor {
+    text: String,
+    cursor: usize,
+}
+
+impl Editor {
+    fn byte_index(&self) -> usize {
+        self.text.char_indices().nth(self.cursor).map_or(self.text.len(), |(i, _)| i)
+    }
+}
+
+struct App<'a> {
+    entries: &'a [LogEntry],
+    /// Current filter text; empty selects everything.
+    filter: String,
+    /// Earlier filters, for stepping back out of a drill-down.
+    history: Vec<String>,
+    /// Indices into `entries` matching the filter.
+    visible: Vec<usize>,
+    stats: LogStats,
+    endpoints: Vec<(String, usize)>,
+    errors: Vec<(String, usize)>,
+    endpoint_state: TableState,
+    error_state: TableState,
+    entry_state: ListState,
+    detail_scroll: u16,
+    focus: Pane,
+    editor: Option<Editor>,
+    message: Option<String>,
+}
+
+fn sorted_counts(map: &std::collections::HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = map.iter().map(|(k, v)| (k.clone(), *v)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// Quote a value for the query language.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn level_color(level: Option<&LogLevel>) -> Color {
+    match level {
+        Some(LogLevel::Error) => Color::Red,
+        Some(LogLevel::Warning) => Color::Yellow,
+        Some(LogLevel::Info) => Color::Green,
+        None => Color::Gray,
+    }
+}
+
+impl<'a> App<'a> {
+    fn new(entries: &'a [LogEntry], filter: String) -> Result<Self, String> {
+        let mut app = App {
+            entries,
+            filter: String::new(),
+            history: Vec::new(),
+            visible: Vec::new(),
+            stats: LogStats::new(),
+            endpoints: Vec::new(),
+            errors: Vec::new(),
+            endpoint_state: TableState::default(),
+            error_state: TableState::default(),
+            entry_state: ListState::default(),
+            detail_scroll: 0,
+            focus: Pane::Entries,
+            editor: None,
+            message: None,
+        };
+        app.apply(filter)?;
+        Ok(app)
+    }
+
+    /// Parse `filter` and recompute the view; on a parse error nothing changes.
+    fn apply(&mut self, filter: String) -> Result<(), String> {
+        let expr = match filter.trim() {
+            "" => None,
+            query => Some(Expr::parse(query).map_err(|e| {
+                let column = e.query[..e.position].chars().count() + 1;
+                format!("{} at column {column}", e.message)
+            })?),
+        };
+        self.visible = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| expr.as_ref().is_none_or(|expr| expr.matches(entry)))
+            .map(|(i, _)| i)
+            .collect();
+        let selected: Vec<&LogEntry> = self.visible.iter().map(|&i| &self.entries[i]).collect();
+        self.stats = LogStats::from_refs(&selected);
+        self.endpoints = sorted_counts(&self.stats.endpoint_frequency);
+        self.errors = sorted_counts(&self.stats.errors_by_endpoint);
+        self.endpoint_state.select((!self.endpoints.is_empty()).then_some(0));
+        self.error_state.select((!self.errors.is_empty()).then_some(0));
+        self.entry_state = ListState::default().with_selected((!self.visible.is_empty()).then_some(0));
+        self.detail_scroll = 0;
+        self.filter = filter;
+        Ok(())
+    }
+
+    /// Apply a new filter, remembering the current one for Esc.
+    fn push_filter(&mut self, filter: String) {
+        let previous = self.filter.clone();
+        match self.apply(filter) {
+            Ok(()) => {
+                self.history.push(previous);
+                self.message = None;
+            }
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    fn pop_filter(&mut self) {
+        if let Some(previous) = self.history.pop() {
+            // The previous filter parsed before, so it parses again.
+            let _ = self.apply(previous);
+            self.message = None;
+        }
+    }
+
+    /// Narrow the filter to the endpoint selected in the focused table.
+    fn drill_down(&mut self) {
+        let (rows, state, errors_only) = match self.focus {
+            Pane::Endpoints => (&self.endpoints, &self.endpoint_state, false),
+            Pane::Errors => (&self.errors, &self.error_state, true),
+            _ => return,
+        };
+        let Some((endpoint, _)) = state.selected().and_then(|i| rows.get(i)) else {
+            return;
+        };
+        let mut narrowed = format!("endpoint = {}", quote(endpoint));
+        if errors_only {
+            narrowed.push_str(" and level = ERROR");
+        }
+        let filter = match self.filter.trim() {
+            "" => narrowed,
+            current => format!("({current}) and {narrowed}"),
+        };
+        self.push_filter(filter);
+        self.focus = Pane::Entries;
+    }
+
+    fn selected_entry(&self) -> Option<&LogEntry> {
+        let i = self.entry_state.selected()?;
+        self.visible.get(i).map(|&idx| &self.entries[idx])
+    }
+
+    /// Move the selection of the focused pane by `delta` rows.
+    fn move_by(&mut self, delta: isize) {
+        let step = |selected: Option<usize>, len: usize| {
+            if len == 0 {
+                return None;
+            }
+            let current = selected.unwrap_or(0) as isize;
+            Some((current + delta).clamp(0, len as isize - 1) as usize)
+        };
+        match self.focus {
+            Pane::Endpoints => {
+                let next = step(self.endpoint_state.selected(), self.endpoints.len());
+                self.endpoint_state.select(next);
+            }
+            Pane::Errors => {
+                let next = step(self.error_state.selected(), self.errors.len());
+                self.error_state.select(next);
+            }
+            Pane::Entries => {
+                let next = step(self.entry_state.selected(), self.visible.len());
+                self.entry_state.select(next);
+                self.detail_scroll = 0;
+            }
+            Pane::Detail => {
+                self.detail_scroll = (self.detail_scroll as isize + delta).clamp(0, u16::MAX as isize) as u16;
+            }
+        }
+    }
+
+    /// Handle a key; returns false to quit.
+    fn on_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(editor) = &mut self.editor {
+            match key.code {
+                KeyCode::Enter => {
+                    let text = editor.text.clone();
+                    self.editor = None;
+                    if text != self.filter {
+                        self.push_filter(text);
+                    }
+                }
+                KeyCode::Esc => self.editor = None,
+                KeyCode::Backspace if editor.cursor > 0 => {
+                    editor.cursor -= 1;
+                    let at = editor.byte_index();
+                    editor.text.remove(at);
+                }
+                KeyCode::Delete if editor.cursor < editor.text.chars().count() => {
+                    let at = editor.byte_index();
+                    editor.text.remove(at);
+                }
+                KeyCode::Left => editor.cursor = editor.cursor.saturating_sub(1),
+                KeyCode::Right => editor.cursor = (editor.cursor + 1).min(editor.text.chars().count()),
+                KeyCode::Home => editor.cursor = 0,
+                KeyCode::End => editor.cursor = editor.text.chars().count(),
+                KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    editor.text.clear();
+                    editor.cursor = 0;
+                }
+                KeyCode::Char(c) => {
+                    let at = editor.byte_index();
+                    editor.text.insert(at, c);
+                    editor.cursor += 1;
+                }
+                _ => {}
+            }
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('/') => {
+                self.editor = Some(Editor {
+                    cursor: self.filter.chars().count(),
+                    text: self.filter.clone(),
+                });
+                self.message = None;
+            }
+            KeyCode::Tab => self.focus = self.focus.cycle(true),
+            KeyCode::BackTab => self.focus = self.focus.cycle(false),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(PAGE as isize),
+            KeyCode::PageUp => self.move_by(-(PAGE as isize)),
+            KeyCode::Home | KeyCode::Char('g') => self.move_by(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.move_by(isize::MAX / 2),
+            KeyCode::Enter => self.drill_down(),
+            KeyCode::Esc | KeyCode::Backspace => self.pop_filter(),
+            _ => {}
+        }
+        true
+    }
+
+    fn block(&self, title: String, pane: Option<Pane>) -> Block<'static> {
+        let focused = pane.is_some_and(|p| p == self.focus) && self.editor.is_none();
+        let style = if focused { Style::new().cyan().bold() } else { Style::new().dark_gray() };
+        Block::new().borders(Borders::ALL).border_style(style).title(Span::styled(title, Style::new().bold()))
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [filter_area, main, status] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(8), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(main);
+        let [summary, endpoints, errors] =
+            Layout::vertical([Constraint::Length(9), Constraint::Percentage(55), Constraint::Percentage(45)]).areas(left);
+        let [list, detail] = Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(right);
+
+        self.draw_filter(frame, filter_area);
+        self.draw_summary(frame, summary);
+        self.draw_counts(frame, endpoints, Pane::Endpoints);
+        self.draw_counts(frame, errors, Pane::Errors);
+        self.draw_entries(frame, list);
+        self.draw_detail(frame, detail);
+
+        let status_line = match &self.message {
+            Some(message) => Line::from(Span::styled(message.clone(), Style::new().red())),
+            None if self.editor.is_some() => Line::from("Enter apply · Esc cancel · Ctrl-U clear".dark_gray()),
+            None => Line::from(
+                "q quit · / filter · Tab switch pane · ↑↓ PgUp PgDn g G move · Enter drill down · Esc back".dark_gray(),
+            ),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+
+    fn draw_filter(&self, frame: &mut Frame, area: Rect) {
+        let title = if self.history.is_empty() {
+            "Filter".to_string()
+        } else {
+            format!("Filter ({} back with Esc)", self.history.len())
+        };
+        let block = self.block(title, None);
+        let text = match &self.editor {
+            Some(editor) => {
+                let style = Style::new().cyan().bold();
+                let x = area.x + 1 + editor.text.chars().take(editor.cursor).count() as u16;
+                frame.set_cursor_position((x.min(area.right().saturating_sub(2)), area.y + 1));
+                Line::from(Span::styled(editor.text.clone(), style))
+            }
+            None if self.filter.trim().is_empty() => Line::from("(all entries; press / to filter)".dark_gray()),
+            None => Line::from(self.filter.clone()),
+        };
+        frame.render_widget(Paragraph::new(text).block(block), area);
+    }
+
+    fn draw_summary(&self, frame: &mut Frame, area: Rect) {
+        let stats = &self.stats;
+        let ms = |v: Option<f64>| v.map_or("–".to_string(), |v| format!("{v:.2}ms"));
+        let row = |label: &str, value: String, color: Color| {
+            Line::from(vec![Span::raw(format!("{label:<10}")), Span::styled(value, Style::new().fg(color))])
+        };
+        let lines = vec![
+            row("Entries", format!("{} of {}", self.visible.len(), self.entries.len()), Color::White),
+            row("INFO", stats.info_count.to_string(), Color::Green),
+            row("WARNING", stats.warning_count.to_string(), Color::Yellow),
+            row("ERROR", format!("{} ({:.1}%)", stats.error_count, stats.error_rate()), Color::Red),
+            row("P50", ms(stats.response_time_percentile(50.0)), Color::White),
+            row("P95", ms(stats.response_time_percentile(95.0)), Color::White),
+            row("P99", ms(stats.response_time_percentile(99.0)), Color::White),
+        ];
+        frame.render_widget(Paragraph::new(lines).block(self.block("Summary".to_string(), None)), area);
+    }
+
+    fn draw_counts(&mut self, frame: &mut Frame, area: Rect, pane: Pane) {
+        let (title, rows, color) = match pane {
+            Pane::Endpoints => ("Top endpoints", &self.endpoints, Color::Cyan),
+            _ => ("Errors by endpoint", &self.errors, Color::Red),
+        };
+        let table_rows: Vec<Row> = rows
+            .iter()
+            .map(|(endpoint, count)| Row::new(vec![Span::raw(endpoint.clone()), Span::styled(count.to_string(), Style::new().fg(color))]))
+            .collect();
+        let block = self.block(format!("{title} ({})", rows.len()), Some(pane));
+        let table = Table::new(table_rows, [Constraint::Fill(1), Constraint::Length(7)])
+            .block(block)
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let state = match pane {
+            Pane::Endpoints => &mut self.endpoint_state,
+            _ => &mut self.error_state,
+        };
+        frame.render_stateful_widget(table, area, state);
+    }
+
+    fn draw_entries(&mut self, frame: &mut Frame, area: Rect) {
+        // Only the rows around the selection are built, so large logs stay fast.
+        let height = area.height.saturating_sub(2) as usize;
+        let selected = self.entry_state.selected().unwrap_or(0);
+        let offset = self.entry_state.offset().min(selected).max((selected + 1).saturating_sub(height));
+        let items: Vec<ListItem> = self.visible[offset.min(self.visible.len())..]
+            .iter()
+            .take(height)
+            .map(|&i| {
+                let entry = &self.entries[i];
+                let mut spans = vec![
+                    Span::styled(format!("{:>6} ", entry.line_number), Style::new().dark_gray()),
+                    Span::raw(format!("{} ", entry.timestamp.as_deref().unwrap_or(""))),
+                    Span::styled(
+                        format!("{:<7} ", entry.level.as_ref().map(|l| l.to_string()).unwrap_or_default()),
+                        Style::new().fg(level_color(entry.level.as_ref())),
+                    ),
+                ];
+                if let Some(endpoint) = &entry.endpoint {
+                    let method = entry.method.as_ref().map(|m| m.to_string()).unwrap_or_default();
+                    spans.push(Span::raw(format!("{method} {endpoint}")));
+                    if let Some(status) = entry.status_code {
+                        spans.push(Span::raw(format!(" {status}")));
+                    }
+                    if let Some(ms) = entry.response_time {
+                        spans.push(Span::styled(format!(" {ms}ms"), Style::new().dark_gray()));
+                    }
+                } else {
+                    spans.push(Span::raw(entry.message.clone().unwrap_or_default()));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let title = format!("Entries ({}/{})", self.visible.len(), self.entries.len());
+        let list = List::new(items)
+            .block(self.block(title, Some(Pane::Entries)))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut window = ListState::default().with_selected(self.entry_state.selected().map(|s| s - offset));
+        frame.render_stateful_widget(list, area, &mut window);
+        *self.entry_state.offset_mut() = offset;
+    }
+
+    fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+        let block = self.block("Detail".to_string(), Some(Pane::Detail));
+        let Some(entry) = self.selected_entry() else {
+            frame.render_widget(Paragraph::new("No entry selected.".dark_gray()).block(block), area);
+            return;
+        };
+        let mut lines = vec![Line::from(entry.raw.clone()), Line::default()];
+        let field = |name: &str, value: Option<String>| {
+            value.map(|v| Line::from(vec![Span::styled(format!("{name:<14}"), Style::new().cyan()), Span::raw(v)]))
+        };
+        lines.extend(
+            [
+                field("line", Some(entry.line_number.to_string())),
+                field("timestamp", entry.timestamp.clone()),
+                field("level", entry.level.as_ref().map(|l| l.to_string())),
+                field("ip", entry.ip_address.map(|ip| ip.to_string())),
+                field("method", entry.method.as_ref().map(|m| m.to_string())),
+                field("endpoint", entry.endpoint.clone()),
+                field("status", entry.status_code.map(|s| s.to_string())),
+                field("response_time", entry.response_time.map(|ms| format!("{ms}ms"))),
+                field("message", entry.message.clone()),
+                field("template", entry.message_template()),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        if !entry.extra.is_empty() {
+            lines.push(Line::from("extra".cyan()));
+            let pretty = serde_json::to_string_pretty(&entry.extra).expect("extra fields are serializable");
+            lines.extend(pretty.lines().map(|l| Line::from(l.to_string())));
+        }
+        let paragraph = Paragraph::new(Text::from(lines))
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .scroll((self.detail_scroll, 0));
+        frame.render_widget(paragraph, area);
+    }
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && !app.on_key(key)
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Run the explorer over `entries` until the user quits. `filter` is the
+/// initial filter text (the `--where` query).
+pub fn run(entries: &[LogEntry], filter: Option<&str>) -> io::Result<()> {
+    let mut app = App::new(entries, filter.unwrap_or_default().to_string())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<LogEntry> {
+        let mut entries: Vec<LogEntry> = [
+            "2024-01-15 08:00:00.000 INFO 10.0.0.1 GET /api/users 200 40ms",
+            "2024-01-15 08:00:01.000 ERROR 10.0.0.2 GET /api/orders 500 900ms",
+            "2024-01-15 08:00:02.000 INFO 10.0.0.1 GET /api/orders 200 100ms",
+            "2024-01-15 08:00:03.000 ERROR 10.0.0.3 GET /api/q 500 10ms",
+        ]
+        .iter()
+        .map(|line| LogEntry::parse_log(line).unwrap())
+        .collect();
+        entries[3].endpoint = Some("/api/\"q\"".to_string());
+        entries
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn apply_recomputes_the_view_from_the_matching_entries() {
+        let entries = entries();
+        let mut app = App::new(&entries, String::new()).unwrap();
+        assert_eq!(app.visible, vec![0, 1, 2, 3]);
+        assert_eq!(app.stats.total_requests, 4);
+
+        app.apply("level = ERROR".to_string()).unwrap();
+        assert_eq!(app.visible, vec![1, 3]);
+        assert_eq!(app.stats.total_requests, 2);
+        assert_eq!(app.stats.error_count, 2);
+        assert_eq!(app.stats.slowest_requests[0].response_time, Some(900.0));
+        assert_eq!(app.errors, vec![("/api/\"q\"".to_string(), 1), ("/api/orders".to_string(), 1)]);
+        assert_eq!(app.entry_state.selected(), Some(0));
+
+        app.apply("endpoint = \"/nowhere\"".to_string()).unwrap();
+        assert!(app.visible.is_empty());
+        assert_eq!(app.entry_state.selected(), None);
+        assert_eq!(app.endpoint_state.selected(), None);
+    }
+
+    #[test]
+    fn apply_reports_the_error_column_in_chars_and_keeps_the_view() {
+        let entries = entries();
+        let mut app = App::new(&entries, "level = ERROR".to_string()).unwrap();
+        let error = app.apply("endpoint = \"é\" and".to_string()).unwrap_err();
+        let expected = Expr::parse("endpoint = \"é\" and").unwrap_err();
+        let column = "endpoint = \"é\" and"[..expected.position].chars().count() + 1;
+        assert_eq!(error, format!("{} at column {column}", expected.message));
+        assert!(column < expected.position + 1, "column counts chars, not bytes");
+        assert_eq!(app.filter, "level = ERROR");
+        assert_eq!(app.visible, vec![1, 3]);
+    }
+
+    #[test]
+    fn drill_down_quotes_the_endpoint_and_nests_the_current_filter() {
+        let entries = entries();
+        let mut app = App::new(&entries, String::new()).unwrap();
+        app.focus = Pane::Errors;
+        app.drill_down();
+        assert_eq!(app.filter, "endpoint = \"/api/\\\"q\\\"\" and level = ERROR");
+        assert_eq!(app.visible, vec![3]);
+        assert_eq!(app.focus, Pane::Entries);
+
+        let mut app = App::new(&entries, "status >= 200 or level = ERROR".to_string()).unwrap();
+        app.focus = Pane::Endpoints;
+        app.drill_down();
+        assert_eq!(app.filter, "(status >= 200 or level = ERROR) and endpoint = \"/api/orders\"");
+        assert_eq!(app.visible, vec![1, 2]);
+        assert_eq!(app.history, vec!["status >= 200 or level = ERROR".to_string()]);
+    }
+
+    #[test]
+    fn pop_filter_steps_back_through_the_history() {
+        let entries = entries();
+        let mut app = App::new(&entries, String::new()).unwrap();
+        app.push_filter("level = ERROR".to_string());
+        app.push_filter("(level = ERROR) and endpoint = \"/api/orders\"".to_string());
+        assert_eq!(app.visible, vec![1]);
+
+        app.push_filter("level =".to_string());
+        assert!(app.message.is_some());
+        assert_eq!(app.history.len(), 2);
+
+        app.pop_filter();
+        assert_eq!(app.filter, "level = ERROR");
+        assert_eq!(app.visible, vec![1, 3]);
+        assert_eq!(app.message, None);
+        app.pop_filter();
+        assert_eq!(app.filter, "");
+        assert_eq!(app.visible.len(), 4);
+        app.pop_filter();
+        assert_eq!(app.filter, "");
+    }
+
+    #[test]
+    fn editor_moves_and_edits_by_char_on_multibyte_text() {
+        let entries = entries();
+        let mut app = App::new(&entries, String::new()).unwrap();
+        app.on_key(key(KeyCode::Char('/')));
+        for c in "é→x".chars() {
+            app.on_key(key(KeyCode::Char(c)));
+        }
+        let editor = app.editor.as_ref().unwrap();
+        assert_eq!(editor.cursor, 3);
+        assert_eq!(editor.byte_index(), editor.text.len());
+
+        app.on_key(key(KeyCode::Left));
+        app.on_key(key(KeyCode::Backspace));
+        let editor = app.editor.as_ref().unwrap();
+        assert_eq!((editor.text.as_str(), editor.cursor, editor.byte_index()), ("éx", 1, 2));
+
+        app.on_key(key(KeyCode::Home));
+        app.on_key(key(KeyCode::Delete));
+        app.on_key(key(KeyCode::Char('ü')));
+        app.on_key(key(KeyCode::End));
+        app.on_key(key(KeyCode::Right));
+        let editor = app.editor.as_ref().unwrap();
+        assert_eq!((editor.text.as_str(), editor.cursor), ("üx", 2));
+
+        app.on_key(key(KeyCode::Esc));
+        assert!(app.editor.is_none());
+        assert_eq!(app.filter, "");
+    }
+}